@@ -0,0 +1,78 @@
+use std::fs::DirBuilder;
+use std::path::Path;
+use std::process::Command;
+use std::str;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use super::{DiscoveredCluster, DiscoveryKind};
+
+#[derive(Debug, Deserialize)]
+struct Cluster {
+    id: String,
+    name: String,
+    region: String,
+}
+
+/// Name of the context created by `doctl kubernetes cluster kubeconfig save`.
+fn context_name(region: &str, name: &str) -> String {
+    format!("do-{region}-{name}")
+}
+
+/// List the DOKS clusters of the account of doctl.
+pub fn list() -> Result<Vec<DiscoveredCluster>> {
+    let result = Command::new("doctl")
+        .args(["kubernetes", "cluster", "list", "--output", "json"])
+        .output()
+        .context("Could not spawn doctl")?;
+
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of doctl as utf-8");
+        return Err(anyhow!("Error calling doctl:\n{}", stderr));
+    }
+
+    let clusters: Vec<Cluster> = serde_json::from_slice(&result.stdout).context("Could not parse output of doctl")?;
+    Ok(clusters
+        .into_iter()
+        .map(|c| DiscoveredCluster {
+            kind: DiscoveryKind::DigitalOcean,
+            context_name: context_name(&c.region, &c.name),
+            id: c.id,
+        })
+        .collect())
+}
+
+/// Run `doctl kubernetes cluster kubeconfig save`, which writes the cluster's context into the given
+/// kubeconfig.
+pub fn login(cluster_id: &str, kubeconfig: &Path) -> Result<()> {
+    if let Some(parent) = kubeconfig.parent() {
+        DirBuilder::new().recursive(true).create(parent)?;
+    }
+
+    let status = Command::new("doctl")
+        .args([
+            "kubernetes",
+            "cluster",
+            "kubeconfig",
+            "save",
+            cluster_id,
+            "--set-current-context=false",
+        ])
+        .env("KUBECONFIG", kubeconfig)
+        .status()
+        .context("Could not spawn doctl")?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "doctl kubernetes cluster kubeconfig save {} failed",
+            cluster_id
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_context_name() {
+    assert_eq!(context_name("fra1", "prod"), "do-fra1-prod");
+}
@@ -0,0 +1,88 @@
+use std::fs::DirBuilder;
+use std::path::Path;
+use std::process::Command;
+use std::str;
+
+use anyhow::{anyhow, Context, Result};
+use base64::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::{DiscoveredCluster, DiscoveryKind};
+use crate::kubeconfig::{self, KubeConfig};
+
+#[derive(Debug, Deserialize)]
+struct Cluster {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Kubeconfig {
+    kubeconfig: String,
+}
+
+fn linode_json<T: DeserializeOwned>(args: &[&str]) -> Result<T> {
+    let result = Command::new("linode-cli")
+        .args(args)
+        .arg("--json")
+        .output()
+        .context("Could not spawn linode-cli")?;
+
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of linode-cli as utf-8");
+        return Err(anyhow!("Error calling linode-cli:\n{}", stderr));
+    }
+
+    serde_json::from_slice(&result.stdout).context("Could not parse output of linode-cli")
+}
+
+/// Name of the context of the kubeconfig of an LKE cluster.
+fn context_name(cluster_id: u64) -> String {
+    format!("lke{cluster_id}-ctx")
+}
+
+/// List the LKE clusters of the account of linode-cli.
+pub fn list() -> Result<Vec<DiscoveredCluster>> {
+    let clusters: Vec<Cluster> = linode_json(&["lke", "clusters-list"])?;
+    Ok(clusters
+        .into_iter()
+        .map(|c| DiscoveredCluster {
+            kind: DiscoveryKind::Linode,
+            context_name: context_name(c.id),
+            id: c.id.to_string(),
+        })
+        .collect())
+}
+
+/// Fetch the kubeconfig of the cluster with `linode-cli lke kubeconfig-view`, and write its context into the
+/// given kubeconfig. linode-cli has no command which saves it.
+pub fn login(cluster_id: &str, kubeconfig: &Path) -> Result<()> {
+    let views: Vec<Kubeconfig> = linode_json(&["lke", "kubeconfig-view", cluster_id])?;
+    let view = views
+        .first()
+        .with_context(|| format!("linode-cli returned no kubeconfig for cluster {cluster_id}"))?;
+    let decoded = BASE64_STANDARD
+        .decode(view.kubeconfig.trim())
+        .context("Could not decode the kubeconfig returned by linode-cli")?;
+    let fetched: KubeConfig =
+        serde_yaml::from_slice(&decoded).context("Could not parse the kubeconfig returned by linode-cli")?;
+
+    if let Some(parent) = kubeconfig.parent() {
+        DirBuilder::new().recursive(true).create(parent)?;
+    }
+    let _lock = kubeconfig::lock_files([kubeconfig])?;
+    let mut config = if kubeconfig.exists() {
+        let mut config = kubeconfig::read_kubeconfig(kubeconfig)?;
+        config.replace(fetched);
+        config
+    } else {
+        fetched
+    };
+    config.current_context = None;
+    config.write_to_file(kubeconfig)
+}
+
+#[test]
+fn test_context_name() {
+    assert_eq!(context_name(12345), "lke12345-ctx");
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::settings::{expanduser, DiscoverySource, Settings};
+
+mod digitalocean;
+mod linode;
+mod teleport;
+
+/// External providers which kubie can ask for the list of available clusters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiscoveryKind {
+    Teleport,
+    DigitalOcean,
+    Linode,
+}
+
+impl DiscoveryKind {
+    const ALL: &'static [DiscoveryKind] = &[
+        DiscoveryKind::Teleport,
+        DiscoveryKind::DigitalOcean,
+        DiscoveryKind::Linode,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            DiscoveryKind::Teleport => "teleport",
+            DiscoveryKind::DigitalOcean => "digitalocean",
+            DiscoveryKind::Linode => "linode",
+        }
+    }
+
+    fn source(self, settings: &Settings) -> &DiscoverySource {
+        match self {
+            DiscoveryKind::Teleport => &settings.discovery.teleport,
+            DiscoveryKind::DigitalOcean => &settings.discovery.digitalocean,
+            DiscoveryKind::Linode => &settings.discovery.linode,
+        }
+    }
+
+    /// Path of the kubeconfig file managed by kubie in which the backend's credentials are written.
+    pub fn kubeconfig_path(self, settings: &Settings) -> PathBuf {
+        match &self.source(settings).kubeconfig {
+            Some(path) => PathBuf::from(expanduser(path)),
+            None => PathBuf::from(expanduser(&format!("~/.kube/kubie/{}.yaml", self.name()))),
+        }
+    }
+}
+
+/// A cluster reported by a discovery backend.
+#[derive(Clone, Debug)]
+pub struct DiscoveredCluster {
+    pub kind: DiscoveryKind,
+    /// Name of the context which exists once the cluster has been logged into.
+    pub context_name: String,
+    /// Identifier of the cluster for the backend.
+    pub id: String,
+}
+
+fn enabled_kinds(settings: &Settings) -> impl Iterator<Item = DiscoveryKind> + '_ {
+    DiscoveryKind::ALL
+        .iter()
+        .copied()
+        .filter(move |kind| kind.source(settings).enable)
+}
+
+/// Kubeconfig files written by the enabled discovery backends.
+pub fn managed_kubeconfigs(settings: &Settings) -> Vec<PathBuf> {
+    enabled_kinds(settings)
+        .map(|kind| kind.kubeconfig_path(settings))
+        .collect()
+}
+
+/// How long the kubeconfig file written by an enabled backend is used, if the path is one of them and its
+/// backend has a ttl.
+pub fn managed_kubeconfig_ttl(settings: &Settings, path: &Path) -> Option<Duration> {
+    enabled_kinds(settings)
+        .find(|kind| kind.kubeconfig_path(settings) == path)
+        .and_then(|kind| kind.source(settings).ttl)
+}
+
+/// List the clusters of every enabled backend. A backend which fails is reported and skipped.
+pub fn discover(settings: &Settings) -> Vec<DiscoveredCluster> {
+    let mut clusters = vec![];
+    for kind in enabled_kinds(settings) {
+        let result = match kind {
+            DiscoveryKind::Teleport => teleport::list(),
+            DiscoveryKind::DigitalOcean => digitalocean::list(),
+            DiscoveryKind::Linode => linode::list(),
+        };
+        match result {
+            Ok(found) => clusters.extend(found),
+            Err(err) => eprintln!("Warning: could not list {} clusters: {:#}", kind.name(), err),
+        }
+    }
+    clusters
+}
+
+/// Log into the cluster, writing fresh credentials into the backend's kubeconfig file.
+pub fn login(settings: &Settings, cluster: &DiscoveredCluster) -> Result<()> {
+    let kubeconfig = cluster.kind.kubeconfig_path(settings);
+    match cluster.kind {
+        DiscoveryKind::Teleport => teleport::login(&cluster.id, &kubeconfig),
+        DiscoveryKind::DigitalOcean => digitalocean::login(&cluster.id, &kubeconfig),
+        DiscoveryKind::Linode => linode::login(&cluster.id, &kubeconfig),
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::DirBuilder;
+use std::path::Path;
+use std::process::Command;
+use std::str;
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::{DiscoveredCluster, DiscoveryKind};
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    active: Profile,
+}
+
+#[derive(Debug, Deserialize)]
+struct Profile {
+    cluster: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KubeCluster {
+    kube_cluster_name: String,
+}
+
+fn tsh_json<T: DeserializeOwned>(args: &[&str]) -> Result<T> {
+    let result = Command::new("tsh").args(args).output().context("Could not spawn tsh")?;
+
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of tsh as utf-8");
+        return Err(anyhow!("Error calling tsh:\n{}", stderr));
+    }
+
+    serde_json::from_slice(&result.stdout).context("Could not parse output of tsh")
+}
+
+/// Name of the context created by `tsh kube login`.
+fn context_name(teleport_cluster: &str, kube_cluster: &str) -> String {
+    format!("{teleport_cluster}-{kube_cluster}")
+}
+
+/// List the Kubernetes clusters available through the active Teleport profile.
+pub fn list() -> Result<Vec<DiscoveredCluster>> {
+    let status: Status = tsh_json(&["status", "--format=json"])?;
+    let clusters: Vec<KubeCluster> = tsh_json(&["kube", "ls", "--format=json"])?;
+
+    Ok(clusters
+        .into_iter()
+        .map(|c| DiscoveredCluster {
+            kind: DiscoveryKind::Teleport,
+            context_name: context_name(&status.active.cluster, &c.kube_cluster_name),
+            id: c.kube_cluster_name,
+        })
+        .collect())
+}
+
+/// Run `tsh kube login`, which writes the cluster's context into the given kubeconfig.
+pub fn login(kube_cluster: &str, kubeconfig: &Path) -> Result<()> {
+    if let Some(parent) = kubeconfig.parent() {
+        DirBuilder::new().recursive(true).create(parent)?;
+    }
+
+    let status = Command::new("tsh")
+        .args(["kube", "login", kube_cluster])
+        .env("KUBECONFIG", kubeconfig)
+        .status()
+        .context("Could not spawn tsh")?;
+
+    if !status.success() {
+        return Err(anyhow!("tsh kube login {} failed", kube_cluster));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_context_name() {
+    assert_eq!(
+        context_name("teleport.example.com", "prod-eu"),
+        "teleport.example.com-prod-eu"
+    );
+}
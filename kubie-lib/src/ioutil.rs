@@ -0,0 +1,161 @@
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::{
+    fs::{DirBuilder, File, OpenOptions},
+    panic::{self, UnwindSafe},
+};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub fn read_json<P, T>(path: P) -> Result<T>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    let file = File::open(path.as_ref())?;
+    let reader = BufReader::new(file);
+    let obj = serde_json::from_reader(reader)?;
+    Ok(obj)
+}
+
+pub fn write_json<P, T>(path: P, obj: &T) -> Result<()>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+{
+    let path = path.as_ref();
+    DirBuilder::new()
+        .recursive(true)
+        .create(path.parent().expect("path has no parent"))?;
+    let file = create_private(path)?;
+    let writer = BufWriter::new(file);
+    serde_json::to_writer(writer, obj)?;
+    Ok(())
+}
+
+pub fn read_yaml<P, T>(path: P) -> Result<T>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    let file = File::open(path.as_ref())?;
+    let reader = BufReader::new(file);
+    let obj = serde_yaml::from_reader(reader)?;
+    Ok(obj)
+}
+
+pub fn write_yaml<P, T>(path: P, obj: &T) -> Result<()>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+{
+    let path = path.as_ref();
+    DirBuilder::new()
+        .recursive(true)
+        .create(path.parent().expect("path has no parent"))?;
+    let file = File::create(path)?;
+    let writer = BufWriter::new(file);
+    serde_yaml::to_writer(writer, obj)?;
+    Ok(())
+}
+
+/// Create or truncate a file which only its owner can read and write. A new file is created with these
+/// permissions, so that it is never readable by others whatever the umask.
+pub fn create_private(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    // An existing file keeps its permissions.
+    set_private_permissions(path)?;
+    Ok(file)
+}
+
+/// Restrict a file to its owner. Windows files do not have unix permission bits, they are left as-is.
+pub fn set_private_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::fs::{self, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Create a directory and its missing parents, which only their owner can access.
+pub fn create_private_dir(path: &Path) -> Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)?;
+    Ok(())
+}
+
+/// Take the exclusive lock of a lock file without waiting. Returns nothing when another process holds the
+/// lock. The lock is released when the returned file is closed.
+pub fn try_file_lock(path: &Path) -> Result<Option<File>> {
+    DirBuilder::new()
+        .recursive(true)
+        .create(path.parent().expect("path has no parent"))?;
+    let file = OpenOptions::new()
+        .append(true)
+        .read(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("Could not open lock file at {}", path.display()))?;
+
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(Some(file)),
+        Err(err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Could not lock file at {}", path.display())),
+    }
+}
+
+pub fn file_lock<P, F, T>(path: P, scope: F) -> Result<T, anyhow::Error>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> Result<T, anyhow::Error> + UnwindSafe,
+{
+    let path = path.as_ref();
+    let file = OpenOptions::new()
+        .append(true)
+        .truncate(false)
+        .read(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("Could not open lock file at {}", path.display()))?;
+
+    file.lock_exclusive()
+        .with_context(|| format!("Could not lock file at {}", path.display()))?;
+
+    // Run the given closure, but catch any panics so we can unlock before resuming the panic.
+    let exception = panic::catch_unwind(scope);
+
+    // Ignore errors during unlock. If we had a panic, we don't want to return the potential error.
+    // If we did not panic, we want to return the closure's result.
+    let _ = FileExt::unlock(&file);
+
+    match exception {
+        Ok(result) => result,
+        Err(x) => panic::resume_unwind(x),
+    }
+}
+
+#[test]
+fn test_try_file_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("locks").join("config.lock");
+
+    let lock = try_file_lock(&path).unwrap();
+    assert!(lock.is_some());
+    assert!(try_file_lock(&path).unwrap().is_none());
+    drop(lock);
+    assert!(try_file_lock(&path).unwrap().is_some());
+}
@@ -0,0 +1,1020 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use wildmatch::WildMatch;
+
+use crate::ioutil;
+use crate::settings::{Conflict, Settings, SkippedFile};
+use crate::sops;
+use crate::state;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KubeConfig {
+    pub clusters: Vec<NamedCluster>,
+    pub users: Vec<NamedUser>,
+    pub contexts: Vec<NamedContext>,
+    #[serde(rename = "current-context")]
+    pub current_context: Option<String>,
+    #[serde(flatten)]
+    pub others: HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NamedCluster {
+    pub name: String,
+    pub cluster: Mapping,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NamedUser {
+    pub name: String,
+    pub user: Mapping,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NamedContext {
+    pub name: String,
+    pub context: Context,
+    /// Name of the context in its file, when it was renamed because another file defines a context with the
+    /// same name.
+    #[serde(skip)]
+    pub name_in_file: Option<String>,
+}
+
+impl NamedContext {
+    /// Name of the context in the file defining it.
+    pub fn file_name(&self) -> &str {
+        self.name_in_file.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Context {
+    pub cluster: String,
+    pub namespace: Option<String>,
+    pub user: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sourced<T> {
+    pub source: Rc<PathBuf>,
+    pub item: T,
+}
+
+impl<T> Sourced<T> {
+    pub fn new(source: &Rc<PathBuf>, item: T) -> Self {
+        Sourced {
+            source: source.clone(),
+            item,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Installed {
+    pub clusters: Vec<Sourced<NamedCluster>>,
+    pub users: Vec<Sourced<NamedUser>>,
+    pub contexts: Vec<Sourced<NamedContext>>,
+    /// Files which were found but not loaded.
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl KubeConfig {
+    pub fn write_to_file(&self, path: &Path) -> anyhow::Result<()> {
+        let file = ioutil::create_private(path).context("could not write file")?;
+
+        let buffer = BufWriter::new(file);
+        serde_yaml::to_writer(buffer, self)?;
+        Ok(())
+    }
+
+    /// Make every request of the context's user impersonate the given user and groups.
+    pub fn impersonate(&mut self, user: &str, groups: &[String]) {
+        let mapping = &mut self.users[0].user;
+        mapping.insert("as".into(), user.into());
+        if groups.is_empty() {
+            mapping.remove("as-groups");
+        } else {
+            mapping.insert(
+                "as-groups".into(),
+                Value::Sequence(groups.iter().map(|g| g.as_str().into()).collect()),
+            );
+        }
+    }
+
+    pub fn is_impersonating(&self) -> bool {
+        self.users.first().is_some_and(|u| u.user.contains_key("as"))
+    }
+
+    /// Replace the certificate and key files referenced by the clusters and users with their content, so
+    /// that the kubeconfig can be used on another machine.
+    pub fn embed_files(&mut self) -> Result<()> {
+        for cluster in &mut self.clusters {
+            embed_file(&mut cluster.cluster, "certificate-authority")?;
+        }
+        for user in &mut self.users {
+            embed_file(&mut user.user, "client-certificate")?;
+            embed_file(&mut user.user, "client-key")?;
+        }
+        Ok(())
+    }
+
+    /// Add the contexts, clusters and users of another kubeconfig. Those with the same name as one of this
+    /// kubeconfig are skipped when they are identical, and are an error otherwise.
+    pub fn merge(&mut self, other: KubeConfig) -> Result<()> {
+        for context in other.contexts {
+            match self.contexts.iter().find(|c| c.name == context.name) {
+                Some(existing) if serde_yaml::to_value(existing)? != serde_yaml::to_value(&context)? => {
+                    bail!("Context {} is defined differently by several kubeconfigs", context.name)
+                }
+                Some(_) => {}
+                None => self.contexts.push(context),
+            }
+        }
+        for cluster in other.clusters {
+            match self.clusters.iter().find(|c| c.name == cluster.name) {
+                Some(existing) if existing.cluster != cluster.cluster => {
+                    bail!("Cluster {} is defined differently by several kubeconfigs", cluster.name)
+                }
+                Some(_) => {}
+                None => self.clusters.push(cluster),
+            }
+        }
+        for user in other.users {
+            match self.users.iter().find(|u| u.name == user.name) {
+                Some(existing) if existing.user != user.user => {
+                    bail!("User {} is defined differently by several kubeconfigs", user.name)
+                }
+                Some(_) => {}
+                None => self.users.push(user),
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the contexts, clusters and users of another kubeconfig, replacing the ones with the same names.
+    pub fn replace(&mut self, other: KubeConfig) {
+        for context in other.contexts {
+            self.contexts.retain(|c| c.name != context.name);
+            self.contexts.push(context);
+        }
+        for cluster in other.clusters {
+            self.clusters.retain(|c| c.name != cluster.name);
+            self.clusters.push(cluster);
+        }
+        for user in other.users {
+            self.users.retain(|u| u.name != user.name);
+            self.users.push(user);
+        }
+    }
+}
+
+/// Print the contexts, clusters and users of `original` which are not in `updated`.
+fn print_removed(original: &KubeConfig, updated: &KubeConfig) {
+    let removed = |kind: &str, original: Vec<&String>, updated: Vec<&String>| {
+        for name in original.into_iter().filter(|name| !updated.contains(name)) {
+            println!("  {kind} {name}");
+        }
+    };
+    removed(
+        "context",
+        original.contexts.iter().map(|x| &x.name).collect(),
+        updated.contexts.iter().map(|x| &x.name).collect(),
+    );
+    removed(
+        "cluster",
+        original.clusters.iter().map(|x| &x.name).collect(),
+        updated.clusters.iter().map(|x| &x.name).collect(),
+    );
+    removed(
+        "user",
+        original.users.iter().map(|x| &x.name).collect(),
+        updated.users.iter().map(|x| &x.name).collect(),
+    );
+}
+
+/// Replace the file path under `key` with its content encoded in base64 under `<key>-data`.
+fn embed_file(mapping: &mut Mapping, key: &str) -> Result<()> {
+    let Some(path) = mapping.remove(key) else {
+        return Ok(());
+    };
+    let path = path.as_str().ok_or_else(|| anyhow!("{} should be a path", key))?;
+    let content = fs::read(path).with_context(|| format!("Could not read {} file {}", key, path))?;
+    mapping.insert(format!("{key}-data").into(), BASE64_STANDARD.encode(content).into());
+    Ok(())
+}
+
+/// A GKE cluster, whose project and location are found in the names `gcloud container clusters get-credentials`
+/// gives to its context, cluster and user, such as `gke_my-project_europe-west1_prod`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GkeCluster {
+    pub project: String,
+    pub location: String,
+    pub cluster: String,
+}
+
+impl GkeCluster {
+    /// Parse a name given by gcloud. Projects, locations and cluster names cannot contain underscores.
+    pub fn parse(name: &str) -> Option<GkeCluster> {
+        let parts: Vec<_> = name.strip_prefix("gke_")?.split('_').collect();
+        match parts[..] {
+            [project, location, cluster] if !project.is_empty() && !location.is_empty() && !cluster.is_empty() => {
+                Some(GkeCluster {
+                    project: project.into(),
+                    location: location.into(),
+                    cluster: cluster.into(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Installed {
+    pub fn find_context_by_name(&self, name: &str) -> Option<&Sourced<NamedContext>> {
+        self.contexts.iter().find(|s| s.item.name == name)
+    }
+
+    /// Server of the cluster of a context, or "unknown" when the cluster is not defined.
+    fn context_server(&self, context: &Sourced<NamedContext>) -> &str {
+        self.find_cluster_by_name(&context.item.context.cluster, &context.source)
+            .and_then(|c| c.item.cluster.get("server"))
+            .and_then(|s| s.as_str())
+            .unwrap_or("unknown")
+    }
+
+    /// The server, user and source file of a context, for the columns of the wide listings.
+    pub fn context_columns(&self, name: &str) -> Option<[String; 3]> {
+        let context = self.find_context_by_name(name)?;
+        Some([
+            self.context_server(context).into(),
+            context.item.context.user.clone(),
+            context.source.display().to_string(),
+        ])
+    }
+
+    /// The GKE cluster of a context. Contexts which were renamed are recognized by the names of their cluster
+    /// and user, which gcloud gives the same name.
+    pub fn gke_cluster(&self, name: &str) -> Option<GkeCluster> {
+        let context = self.find_context_by_name(name)?;
+        GkeCluster::parse(&context.item.name)
+            .or_else(|| GkeCluster::parse(&context.item.context.cluster))
+            .or_else(|| GkeCluster::parse(&context.item.context.user))
+    }
+
+    /// Describe the cluster, user and source file of a context, for the preview of the context menu.
+    pub fn describe_context(&self, name: &str) -> Option<String> {
+        let context = self.find_context_by_name(name)?;
+        let user = self.find_user_by_name(&context.item.context.user, &context.source);
+
+        let server = self.context_server(context);
+        let auth = user
+            .map(|u| auth_type(&u.item.user))
+            .unwrap_or_else(|| "unknown".into());
+
+        let mut text = format!("Cluster:   {} ({})\n", context.item.context.cluster, server);
+        text.push_str(&format!("User:      {}\n", context.item.context.user));
+        text.push_str(&format!("Auth:      {}\n", auth));
+        if let Some(gke) = self.gke_cluster(name) {
+            text.push_str(&format!("Project:   {}\n", gke.project));
+            text.push_str(&format!("Location:  {}\n", gke.location));
+        }
+        if let Some(namespace) = &context.item.context.namespace {
+            text.push_str(&format!("Namespace: {}\n", namespace));
+        }
+        text.push_str(&format!("Source:    {}\n", context.source.display()));
+        Some(text)
+    }
+
+    pub fn find_cluster_by_name(&self, name: &str, source: &Path) -> Option<&Sourced<NamedCluster>> {
+        self.clusters
+            .iter()
+            .find(|s| s.item.name == name && *s.source == source)
+            .or_else(|| self.clusters.iter().find(|s| s.item.name == name))
+    }
+
+    pub fn find_user_by_name(&self, name: &str, source: &Path) -> Option<&Sourced<NamedUser>> {
+        self.users
+            .iter()
+            .find(|s| s.item.name == name && *s.source == source)
+            .or_else(|| self.users.iter().find(|s| s.item.name == name))
+    }
+
+    pub fn find_contexts_by_cluster(&self, name: &str, source: &Path) -> Vec<&Sourced<NamedContext>> {
+        self.contexts
+            .iter()
+            .filter(|s| s.item.context.cluster == name && *s.source == source)
+            .collect()
+    }
+
+    pub fn find_contexts_by_user(&self, name: &str, source: &Path) -> Vec<&Sourced<NamedContext>> {
+        self.contexts
+            .iter()
+            .filter(|s| s.item.context.user == name && *s.source == source)
+            .collect()
+    }
+
+    /// Files defining a context with this name, in the order in which they were loaded.
+    fn context_sources(&self, name: &str) -> Vec<&Rc<PathBuf>> {
+        let mut sources: Vec<&Rc<PathBuf>> = vec![];
+        for context in self.contexts.iter().filter(|c| c.item.name == name) {
+            if !sources.contains(&&context.source) {
+                sources.push(&context.source);
+            }
+        }
+        sources
+    }
+
+    /// Whether the context with this name defined in `source` is hidden by a context with the same name
+    /// defined in another file, according to the conflict strategy.
+    pub fn is_hidden_context(&self, name: &str, source: &Path, conflict: Conflict) -> bool {
+        let sources = self.context_sources(name);
+        match conflict {
+            Conflict::First => sources.first().is_some_and(|s| s.as_path() != source),
+            Conflict::Last => sources.last().is_some_and(|s| s.as_path() != source),
+            Conflict::Error | Conflict::Rename => false,
+        }
+    }
+
+    /// Handle the contexts whose name is defined in several files: fail, keep only the contexts of the first
+    /// or last file, or suffix their names with the name of their file.
+    pub fn resolve_conflicts(&mut self, conflict: Conflict) -> Result<()> {
+        let mut names: Vec<&str> = self.contexts.iter().map(|c| c.item.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        let conflicting: Vec<String> = names
+            .into_iter()
+            .filter(|name| self.context_sources(name).len() > 1)
+            .map(String::from)
+            .collect();
+
+        for name in &conflicting {
+            match conflict {
+                Conflict::Error => {
+                    let sources: Vec<_> = self
+                        .context_sources(name)
+                        .iter()
+                        .map(|s| s.display().to_string())
+                        .collect();
+                    bail!(
+                        "Context {} is defined in several files: {}. Set configs.conflict to first, last or rename \
+                         to use them anyway.",
+                        name,
+                        sources.join(", ")
+                    );
+                }
+                Conflict::First | Conflict::Last => {
+                    let sources = self.context_sources(name);
+                    let kept = match conflict {
+                        Conflict::First => sources[0].clone(),
+                        _ => sources[sources.len() - 1].clone(),
+                    };
+                    self.contexts.retain(|c| &c.item.name != name || c.source == kept);
+                }
+                Conflict::Rename => {
+                    let sources: Vec<PathBuf> = self.context_sources(name).iter().map(|s| s.to_path_buf()).collect();
+                    for context in self.contexts.iter_mut().filter(|c| &c.item.name == name) {
+                        context.item.name_in_file = Some(name.clone());
+                        context.item.name = format!("{}{}", name, suffix(&context.source, &sources));
+                    }
+                }
+            }
+        }
+
+        // The renamed contexts can still clash, with each other or with a context named like them.
+        if conflict == Conflict::Rename {
+            for context in &self.contexts {
+                let Some(name_in_file) = &context.item.name_in_file else {
+                    continue;
+                };
+                let sources = self.context_sources(&context.item.name);
+                if sources.len() > 1 {
+                    let sources: Vec<_> = sources.iter().map(|s| s.display().to_string()).collect();
+                    bail!(
+                        "Context {} is defined in several files: {}, and renaming it to {} clashes again. Rename \
+                         it in one of them.",
+                        name_in_file,
+                        sources.join(", "),
+                        context.item.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_contexts_matching(
+        &self,
+        pattern: &str,
+        allow_multiple_context_patterns: bool,
+    ) -> Vec<&Sourced<NamedContext>> {
+        let mut result = vec![];
+
+        let patterns = if allow_multiple_context_patterns {
+            pattern.split_whitespace().collect()
+        } else {
+            vec![pattern]
+        };
+
+        for p in &patterns {
+            let matcher = WildMatch::new(p);
+            let m: Vec<_> = self.contexts.iter().filter(|s| matcher.matches(&s.item.name)).collect();
+            if patterns.len() > 1 && m.is_empty() {
+                println!("WARNING: No context matching {p}");
+            }
+            result.extend(m);
+        }
+        result.sort_by_key(|k| &k.item.name);
+        result.dedup_by_key(|k| &k.item.name);
+        result
+    }
+
+    /// Delete contexts from the files defining them, rewriting every file once. The clusters and users referenced
+    /// by the contexts are deleted with them. With `prune_orphans`, the other clusters and users of the files which
+    /// are not used by any context anymore are deleted as well. With `dry_run`, only print what would be deleted.
+    pub fn delete_contexts(&self, names: &[String], prune_orphans: bool, dry_run: bool) -> Result<()> {
+        let mut by_source: Vec<(&Rc<PathBuf>, Vec<&NamedContext>)> = vec![];
+        for name in names {
+            let context = self
+                .find_context_by_name(name)
+                .ok_or_else(|| anyhow!("Context {} not found", name))?;
+            match by_source.iter_mut().find(|(source, _)| *source == &context.source) {
+                Some((_, contexts)) => contexts.push(&context.item),
+                None => by_source.push((&context.source, vec![&context.item])),
+            }
+        }
+
+        let _locks = if dry_run {
+            None
+        } else {
+            Some(lock_files(by_source.iter().map(|(source, _)| source.as_path()))?)
+        };
+        // Every file is checked and read before any is written, so that a delete is not left half done.
+        for (source, _) in &by_source {
+            check_rewritable(source)?;
+        }
+        let mut rewrites = vec![];
+        for (source, contexts) in by_source {
+            let mut kubeconfig: KubeConfig = ioutil::read_yaml(source.as_ref())?;
+            let original = kubeconfig.clone();
+
+            // Retain all contexts whose name is not one of our contexts.
+            let deleted = |name: &String| contexts.iter().any(|c| c.file_name() == name);
+            kubeconfig.contexts.retain(|x| !deleted(&x.name));
+
+            // Retain all clusters and users which are not referenced by our contexts, or which are still used by
+            // another context. With `prune_orphans`, retain only the ones still used.
+            let remaining = &kubeconfig.contexts;
+            let cluster_kept = |name: &String| {
+                remaining.iter().any(|c| &c.context.cluster == name)
+                    || !prune_orphans && !contexts.iter().any(|c| &c.context.cluster == name)
+            };
+            let user_kept = |name: &String| {
+                remaining.iter().any(|c| &c.context.user == name)
+                    || !prune_orphans && !contexts.iter().any(|c| &c.context.user == name)
+            };
+            kubeconfig.clusters.retain(|x| cluster_kept(&x.name));
+            kubeconfig.users.retain(|x| user_kept(&x.name));
+
+            let empty = kubeconfig.contexts.is_empty() && kubeconfig.clusters.is_empty() && kubeconfig.users.is_empty();
+            if dry_run {
+                if empty {
+                    println!(
+                        "Would delete kubeconfig {} because it would be empty.",
+                        source.display()
+                    );
+                } else {
+                    println!("Would update kubeconfig {}.", source.display());
+                }
+            } else if empty {
+                // If the kubeconfig is empty after removing the contexts and dangling references,
+                // we simply remove the file.
+                println!("Deleting kubeconfig {} because is it now empty.", source.display());
+            } else {
+                println!("Updating kubeconfig {}.", source.display());
+            }
+            print_removed(&original, &kubeconfig);
+            rewrites.push((source, kubeconfig, empty));
+        }
+
+        if dry_run {
+            return Ok(());
+        }
+        for (source, kubeconfig, empty) in rewrites {
+            if empty {
+                fs::remove_file(source.as_ref()).context("Could not delete empty kubeconfig file")?;
+            } else {
+                // If the kubeconfig is not empty, we rewrite it with the contexts and dangling references removed.
+                ioutil::write_yaml(source.as_ref(), &kubeconfig)
+                    .context("Could not open kubeconfig file to rewrite it.")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rename a context in the file defining it. The cluster and user of the context are renamed as well
+    /// when they have the same name as the context and no other context of the file uses them.
+    pub fn rename_context(&self, name: &str, new_name: &str) -> Result<()> {
+        let context = self
+            .find_context_by_name(name)
+            .ok_or_else(|| anyhow!("Context not found"))?;
+
+        if self.find_context_by_name(new_name).is_some() {
+            bail!("A context named {} already exists", new_name);
+        }
+        let _lock = lock_files([context.source.as_path()])?;
+        check_rewritable(&context.source)?;
+
+        // The context may have been renamed because another file defines a context with the same name.
+        let name = context.item.file_name();
+
+        let mut kubeconfig: KubeConfig = ioutil::read_yaml(context.source.as_ref())?;
+        let uses = |f: fn(&Context) -> &str, reference: &str| {
+            kubeconfig
+                .contexts
+                .iter()
+                .filter(|c| f(&c.context) == reference)
+                .count()
+        };
+        let rename_cluster = context.item.context.cluster == name
+            && uses(|c| &c.cluster, name) == 1
+            && !kubeconfig.clusters.iter().any(|c| c.name == new_name);
+        let rename_user = context.item.context.user == name
+            && uses(|c| &c.user, name) == 1
+            && !kubeconfig.users.iter().any(|u| u.name == new_name);
+
+        for named in kubeconfig.contexts.iter_mut().filter(|c| c.name == name) {
+            named.name = new_name.into();
+            if rename_cluster {
+                named.context.cluster = new_name.into();
+            }
+            if rename_user {
+                named.context.user = new_name.into();
+            }
+        }
+        if rename_cluster {
+            for cluster in kubeconfig.clusters.iter_mut().filter(|c| c.name == name) {
+                cluster.name = new_name.into();
+            }
+        }
+        if rename_user {
+            for user in kubeconfig.users.iter_mut().filter(|u| u.name == name) {
+                user.name = new_name.into();
+            }
+        }
+        if kubeconfig.current_context.as_deref() == Some(name) {
+            kubeconfig.current_context = Some(new_name.into());
+        }
+
+        println!("Updating kubeconfig {}.", context.source.display());
+        ioutil::write_yaml(context.source.as_ref(), &kubeconfig)
+            .context("Could not open kubeconfig file to rewrite it.")?;
+
+        Ok(())
+    }
+
+    fn make_path_absolute(mapping: &mut Mapping, key: &str, parent: &Path) {
+        if !mapping.contains_key(key) {
+            return;
+        }
+        let str = mapping.get(key).unwrap().as_str().expect("value should be a string");
+        let path = Path::new(str);
+        if !path.is_absolute() {
+            mapping.insert(
+                key.into(),
+                parent
+                    .join(path)
+                    .to_str()
+                    .expect("path should be a valid unicode string")
+                    .into(),
+            );
+        }
+    }
+
+    pub fn make_kubeconfig_for_context(
+        &self,
+        context_name: &str,
+        namespace_name: Option<impl Into<String>>,
+    ) -> Result<KubeConfig> {
+        let mut context_src = self
+            .contexts
+            .iter()
+            .find(|c| c.item.name == context_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Could not find context {}", context_name))?;
+
+        context_src.item.context.namespace = namespace_name.map(Into::into);
+        let kubeconfig_dir = context_src
+            .source
+            .parent()
+            .expect("kubeconfig path should have a parent dir");
+
+        let cluster_src = self
+            .find_cluster_by_name(&context_src.item.context.cluster, &context_src.source)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Could not find cluster {} referenced by context {}",
+                    context_src.item.context.cluster,
+                    context_name,
+                )
+            })?;
+
+        let mut named_cluster = cluster_src.item;
+        let cluster = &mut named_cluster.cluster;
+        Self::make_path_absolute(cluster, "certificate-authority", kubeconfig_dir);
+
+        let user_src = self
+            .find_user_by_name(&context_src.item.context.user, &context_src.source)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Could not find user {} referenced by context {}",
+                    context_src.item.context.user,
+                    context_name,
+                )
+            })?;
+
+        let mut named_user = user_src.item;
+        let user = &mut named_user.user;
+
+        Self::make_path_absolute(user, "client-certificate", kubeconfig_dir);
+        Self::make_path_absolute(user, "client-key", kubeconfig_dir);
+
+        Ok(KubeConfig {
+            clusters: vec![named_cluster],
+            contexts: vec![context_src.item],
+            users: vec![named_user],
+            current_context: Some(context_name.into()),
+            others: {
+                let mut m: HashMap<String, Value> = HashMap::new();
+                m.insert("apiVersion".into(), Value::String("v1".into()));
+                m.insert("kind".into(), Value::String("Config".into()));
+                m
+            },
+        })
+    }
+}
+
+/// Suffix of the contexts renamed because another file defines a context with the same name: the name of
+/// their file, along with its directory when the other files have the same name.
+fn suffix(source: &Path, sources: &[PathBuf]) -> String {
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let same_stem = sources.iter().filter(|s| s.file_stem() == source.file_stem()).count();
+    match source.parent().and_then(Path::file_name) {
+        Some(dir) if same_stem > 1 => format!("@{}/{stem}", dir.to_string_lossy()),
+        _ => format!("@{stem}"),
+    }
+}
+
+/// The non-empty YAML documents of a file, separated by `---`.
+fn parse_documents(text: &str) -> Result<Vec<Value>> {
+    let mut documents = vec![];
+    for document in serde_yaml::Deserializer::from_str(text) {
+        let value = Value::deserialize(document)?;
+        if !value.is_null() {
+            documents.push(value);
+        }
+    }
+    Ok(documents)
+}
+
+fn read_documents(path: &Path) -> Result<Vec<Value>> {
+    parse_documents(&fs::read_to_string(path)?)
+}
+
+/// Combine the documents of a kubeconfig file, keeping the contexts, clusters and users in document order.
+/// The current context and the other fields come from the first document setting them.
+fn merge_documents(documents: Vec<Value>) -> Result<KubeConfig> {
+    let mut documents = documents.into_iter();
+    let first = documents.next().ok_or_else(|| anyhow!("The file is empty"))?;
+    let mut kubeconfig: KubeConfig = serde_yaml::from_value(first)?;
+    for document in documents {
+        let mut next: KubeConfig = serde_yaml::from_value(document)?;
+        kubeconfig.clusters.append(&mut next.clusters);
+        kubeconfig.users.append(&mut next.users);
+        kubeconfig.contexts.append(&mut next.contexts);
+        if kubeconfig.current_context.is_none() {
+            kubeconfig.current_context = next.current_context;
+        }
+        for (key, value) in next.others {
+            kubeconfig.others.entry(key).or_insert(value);
+        }
+    }
+    Ok(kubeconfig)
+}
+
+/// Read a kubeconfig file, decrypting it in memory when it is encrypted with sops. The documents of files
+/// with several YAML documents are combined.
+pub fn read_kubeconfig(path: &Path) -> Result<KubeConfig> {
+    let documents = read_documents(path)?;
+    if documents.iter().any(sops::is_encrypted) {
+        let decrypted = sops::decrypt(path)?;
+        return merge_documents(parse_documents(&String::from_utf8(decrypted)?)?);
+    }
+    merge_documents(documents)
+}
+
+/// Advisory locks of kubeconfig files, held while kubie rewrites them so that two kubie processes do not
+/// modify the same file at once. They are released when dropped.
+pub struct FileLocks {
+    _files: Vec<fs::File>,
+}
+
+/// Lock kubeconfig files before rewriting them, failing right away when another kubie process is
+/// modifying one of them.
+pub fn lock_files<I, P>(paths: I) -> Result<FileLocks>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut files = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        match ioutil::try_file_lock(&state::paths::kubeconfig_lock(path))? {
+            Some(file) => files.push(file),
+            None => bail!(
+                "{} is being modified by another kubie process, try again once it is done",
+                path.display()
+            ),
+        }
+    }
+    Ok(FileLocks { _files: files })
+}
+
+/// Fail for the kubeconfig files which kubie cannot rewrite: the ones encrypted with sops, and the ones
+/// with several YAML documents, which would be combined into one.
+fn check_rewritable(path: &Path) -> Result<()> {
+    let documents = read_documents(path)?;
+    if documents.iter().any(sops::is_encrypted) {
+        bail!(
+            "Context is defined in {} which is encrypted with sops, edit it with sops instead",
+            path.display()
+        );
+    }
+    if documents.len() > 1 {
+        bail!(
+            "Context is defined in {} which contains several YAML documents, edit it by hand instead",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Name the authentication method of a kubeconfig user.
+fn auth_type(user: &Mapping) -> String {
+    if let Some(exec) = user.get("exec") {
+        let command = exec.get("command").and_then(|c| c.as_str()).unwrap_or("?");
+        format!("exec ({command})")
+    } else if let Some(provider) = user.get("auth-provider") {
+        let name = provider.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+        format!("auth provider ({name})")
+    } else if user.contains_key("client-certificate") || user.contains_key("client-certificate-data") {
+        "client certificate".into()
+    } else if user.contains_key("token") || user.contains_key("tokenFile") {
+        "token".into()
+    } else if user.contains_key("username") {
+        "basic".into()
+    } else {
+        "none".into()
+    }
+}
+
+/// Check if a kubeconfig file is encrypted with sops.
+pub fn is_encrypted_file(path: &Path) -> Result<bool> {
+    Ok(read_documents(path)?.iter().any(sops::is_encrypted))
+}
+
+pub fn is_multi_document_file(path: &Path) -> Result<bool> {
+    Ok(read_documents(path)?.len() > 1)
+}
+
+fn load_kubeconfigs<I, P>(kubeconfigs: I) -> Result<Installed>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut installed = Installed {
+        clusters: vec![],
+        contexts: vec![],
+        users: vec![],
+        skipped: vec![],
+    };
+
+    for path in kubeconfigs.into_iter() {
+        let path = path.as_ref();
+
+        // Avoid parsing things that aren't files or don't link to a file.
+        if !path.is_file() {
+            continue;
+        }
+
+        let kubeconfig = read_kubeconfig(path);
+
+        match kubeconfig {
+            Ok(mut kubeconfig) => {
+                let path = Rc::new(path.to_owned());
+                installed
+                    .clusters
+                    .extend(kubeconfig.clusters.drain(..).map(|x| Sourced::new(&path, x)));
+                installed
+                    .contexts
+                    .extend(kubeconfig.contexts.drain(..).map(|x| Sourced::new(&path, x)));
+                installed
+                    .users
+                    .extend(kubeconfig.users.drain(..).map(|x| Sourced::new(&path, x)));
+            }
+            Err(err) => installed.skipped.push(SkippedFile {
+                path: path.to_owned(),
+                reason: format!("could not be parsed as a kubeconfig: {err}"),
+                invalid: true,
+                expired: false,
+            }),
+        }
+    }
+
+    Ok(installed)
+}
+
+pub fn get_installed_contexts(settings: &Settings) -> Result<Installed> {
+    let mut installed = get_installed_contexts_with_conflicts(settings)?;
+
+    let invalid: Vec<_> = installed.skipped.iter().filter(|file| file.invalid).collect();
+    if settings.behavior.strict_parsing && !invalid.is_empty() {
+        let errors: Vec<_> = invalid
+            .iter()
+            .map(|file| format!("{} {}", file.path.display(), file.reason))
+            .collect();
+        bail!("Some kubeconfig files are invalid:\n{}", errors.join("\n"));
+    }
+    if !invalid.is_empty() {
+        eprintln!(
+            "Warning: skipped {} kubeconfig file(s) which could not be read or parsed, `kubie lint` lists them",
+            invalid.len()
+        );
+    }
+    installed.resolve_conflicts(settings.configs.conflict)?;
+    Ok(installed)
+}
+
+/// Like `get_installed_contexts`, but keeps every context whose name is defined in several files, and
+/// leaves reporting the files which could not be read or parsed to the caller.
+pub fn get_installed_contexts_with_conflicts(settings: &Settings) -> Result<Installed> {
+    // The files are loaded in their order of precedence, which resolves the conflicts.
+    let (paths, mut skipped) = settings.get_kube_configs_paths_and_skipped()?;
+    let mut installed = load_kubeconfigs(paths)?;
+    skipped.append(&mut installed.skipped);
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    installed.skipped = skipped;
+
+    if installed.contexts.is_empty() {
+        let mut message = String::from("Could not find any contexts in the Kubie kubeconfig directories!");
+        for file in &installed.skipped {
+            message.push_str(&format!("\nSkipped {}: {}", file.path.display(), file.reason));
+        }
+        bail!(message);
+    }
+    Ok(installed)
+}
+
+pub fn get_kubeconfigs_contexts(kubeconfigs: &Vec<String>) -> Result<Installed> {
+    let installed = load_kubeconfigs(kubeconfigs)?;
+    // The files were given explicitly, they are not skipped silently.
+    for file in &installed.skipped {
+        eprintln!("Error loading kubeconfig {}: {}", file.path.display(), file.reason);
+    }
+    if installed.contexts.is_empty() {
+        bail!("Could not find any contexts in the given set of files!");
+    }
+    Ok(installed)
+}
+
+pub fn get_kubeconfig_path() -> Result<PathBuf> {
+    let path = env::var_os("KUBIE_KUBECONFIG").context("KUBIE_CONFIG not found")?;
+    Ok(PathBuf::from(path))
+}
+
+pub fn get_current_config() -> Result<KubeConfig> {
+    ioutil::read_yaml(get_kubeconfig_path()?)
+}
+
+#[test]
+fn test_resolve_conflicts() {
+    let installed_with = |conflict, b: &str| {
+        let a = Rc::new(PathBuf::from("/kube/a.yaml"));
+        let b = Rc::new(PathBuf::from(b));
+        let context = |name: &str| NamedContext {
+            name: name.into(),
+            context: Context {
+                cluster: "cluster".into(),
+                namespace: None,
+                user: "user".into(),
+            },
+            name_in_file: None,
+        };
+        let mut installed = Installed {
+            clusters: vec![],
+            users: vec![],
+            contexts: vec![
+                Sourced::new(&a, context("dev")),
+                Sourced::new(&a, context("prod")),
+                Sourced::new(&b, context("prod")),
+            ],
+            skipped: vec![],
+        };
+        installed.resolve_conflicts(conflict).map(|_| installed)
+    };
+    let installed = |conflict| installed_with(conflict, "/kube/b.yaml");
+    let names = |installed: &Installed| -> Vec<(String, String)> {
+        installed
+            .contexts
+            .iter()
+            .map(|c| (c.item.name.clone(), c.source.display().to_string()))
+            .collect()
+    };
+
+    assert!(installed(Conflict::Error).is_err());
+    assert_eq!(
+        names(&installed(Conflict::First).unwrap()),
+        [
+            ("dev".into(), "/kube/a.yaml".into()),
+            ("prod".into(), "/kube/a.yaml".into())
+        ],
+    );
+    assert_eq!(
+        names(&installed(Conflict::Last).unwrap()),
+        [
+            ("dev".into(), "/kube/a.yaml".into()),
+            ("prod".into(), "/kube/b.yaml".into())
+        ],
+    );
+    let renamed = installed(Conflict::Rename).unwrap();
+    assert_eq!(
+        names(&renamed),
+        [
+            ("dev".into(), "/kube/a.yaml".into()),
+            ("prod@a".into(), "/kube/a.yaml".into()),
+            ("prod@b".into(), "/kube/b.yaml".into()),
+        ],
+    );
+    assert_eq!(renamed.contexts[2].item.file_name(), "prod");
+
+    // Files with the same name are told apart by their directory.
+    let renamed = installed_with(Conflict::Rename, "/kube/configs/a.yaml").unwrap();
+    assert_eq!(renamed.contexts[1].item.name, "prod@kube/a");
+    assert_eq!(renamed.contexts[2].item.name, "prod@configs/a");
+    assert!(installed_with(Conflict::Rename, "/other/kube/a.yaml").is_err());
+}
+
+#[test]
+fn test_merge_documents() {
+    let documents = parse_documents(
+        r#"
+apiVersion: v1
+kind: Config
+clusters: [{name: a, cluster: {server: "https://a"}}]
+users: [{name: a, user: {token: a}}]
+contexts: [{name: a, context: {cluster: a, user: a}}]
+---
+---
+clusters: [{name: b, cluster: {server: "https://b"}}]
+users: [{name: b, user: {token: b}}]
+contexts: [{name: b, context: {cluster: b, user: b}}]
+current-context: b
+"#,
+    )
+    .unwrap();
+    assert_eq!(documents.len(), 2);
+
+    let kubeconfig = merge_documents(documents).unwrap();
+    let names: Vec<_> = kubeconfig.contexts.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(kubeconfig.clusters.len(), 2);
+    assert_eq!(kubeconfig.users.len(), 2);
+    assert_eq!(kubeconfig.current_context.as_deref(), Some("b"));
+    assert_eq!(kubeconfig.others["kind"], "Config");
+}
+
+#[test]
+fn test_gke_cluster() {
+    assert_eq!(
+        GkeCluster::parse("gke_my-project_europe-west1_prod"),
+        Some(GkeCluster {
+            project: "my-project".into(),
+            location: "europe-west1".into(),
+            cluster: "prod".into(),
+        })
+    );
+    assert_eq!(GkeCluster::parse("gke_my-project_prod"), None);
+    assert_eq!(GkeCluster::parse("gke_my-project_europe-west1_prod_extra"), None);
+    assert_eq!(GkeCluster::parse("arn:aws:eks:eu-west-1:123:cluster/prod"), None);
+}
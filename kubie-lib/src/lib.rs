@@ -0,0 +1,47 @@
+//! Kubeconfig handling of [kubie](https://github.com/kubie-org/kubie), for tools which need to find the same
+//! contexts as kubie without re-implementing its rules.
+//!
+//! The settings are loaded like kubie does, from the layered config files, the trusted project file and the
+//! `KUBIE_*` environment variables. The kubeconfig files are then found from the include and exclude globs of
+//! the settings, along with the remote, git and discovered sources, and their contexts are combined.
+//!
+//! ```no_run
+//! use kubie_lib::kubeconfig;
+//! use kubie_lib::settings::Settings;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let settings = Settings::load()?;
+//! let installed = kubeconfig::get_installed_contexts(&settings)?;
+//! for context in installed.get_contexts_matching("prod-*", true) {
+//!     // An isolated kubeconfig with only this context, like the ones of kubie shells.
+//!     let config = installed.make_kubeconfig_for_context(&context.item.name, Some("default"))?;
+//!     println!("{} {}", context.item.name, serde_yaml::to_string(&config)?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+/// Discovery of the clusters of providers such as Teleport.
+pub mod discovery;
+/// Reading and writing of JSON and YAML files.
+pub mod ioutil;
+/// Parsing of kubeconfig files, and the contexts installed from them.
+pub mod kubeconfig;
+/// Overrides of the settings by `KUBIE_*` environment variables.
+pub mod overrides;
+/// Settings of the project file of a directory tree.
+pub mod project;
+/// Session of a kubie shell, the history of its contexts and namespaces.
+pub mod session;
+/// Settings of kubie, and the kubeconfig files they include.
+pub mod settings;
+/// Decryption of kubeconfig files encrypted with sops.
+pub mod sops;
+/// Kubeconfig files fetched from git repositories and remote URLs.
+pub mod sources;
+/// State kept by kubie between runs, such as the last namespace of every context.
+pub mod state;
+/// Storage of the JSON documents which concurrent kubie processes read and write, such as the state.
+pub mod store;
+/// Environment variables of kubie shells.
+pub mod vars;
@@ -0,0 +1,343 @@
+//! Settings overridden by `KUBIE_*` environment variables, such as `KUBIE_FZF_IGNORE_CASE=1` for
+//! `fzf.ignore_case`, on top of the values of the config file.
+
+use std::collections::HashSet;
+use std::env;
+
+use serde::de::{DeserializeOwned, DeserializeSeed, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde_yaml::{Mapping, Value};
+
+/// Variables which kubie exports in its shells, and which are not overrides.
+const IGNORED: &[&str] = &["KUBIE_ENV_UPDATE", "KUBIE_PROMPT", "KUBIE_SHELL"];
+
+/// Deserialize the settings from the value of the config file, replacing the values which have an
+/// environment variable.
+pub fn deserialize<T: DeserializeOwned>(value: Value) -> Result<T, serde_yaml::Error> {
+    T::deserialize(Overlay {
+        value,
+        var: Some("KUBIE".into()),
+        from_env: false,
+    })
+}
+
+/// Name of the variable overriding a field, `fields` of `prompt` being overridden by `KUBIE_PROMPT_FIELD`.
+fn var_name(parent: &str, field: &str) -> String {
+    format!("{}_{}", parent, field.to_uppercase())
+}
+
+/// Deserializer of a value which can be overridden by environment variables. `var` is the name of the
+/// variable of the value, none for the items of lists which cannot be overridden individually.
+struct Overlay {
+    value: Value,
+    var: Option<String>,
+    from_env: bool,
+}
+
+struct OverlayMap {
+    iter: serde_yaml::mapping::IntoIter,
+    value: Option<(String, Value)>,
+    var: Option<String>,
+    from_env: HashSet<String>,
+}
+
+struct OverlaySeq {
+    iter: std::vec::IntoIter<Value>,
+    from_env: bool,
+}
+
+impl Overlay {
+    /// The value of a variable read as a YAML scalar, so that `KUBIE_FZF_HEIGHT=40` is a number.
+    fn scalar(self) -> Value {
+        match &self.value {
+            Value::String(text) if self.from_env => serde_yaml::from_str(text).unwrap_or(self.value),
+            _ => self.value,
+        }
+    }
+}
+
+macro_rules! forward_scalar {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.scalar().$method(visitor)
+            }
+        )*
+    };
+}
+
+macro_rules! forward_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.value.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Overlay {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Mapping(mapping) => visitor.visit_map(OverlayMap {
+                iter: mapping.into_iter(),
+                value: None,
+                var: self.var,
+                from_env: HashSet::new(),
+            }),
+            Value::Sequence(sequence) => visitor.visit_seq(OverlaySeq {
+                iter: sequence.into_iter(),
+                from_env: self.from_env,
+            }),
+            _ => self.scalar().deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let Some(var) = self.var else {
+            return self.value.deserialize_struct(name, fields, visitor);
+        };
+        let mut mapping = match self.value {
+            Value::Mapping(mapping) => mapping,
+            Value::Null => Mapping::new(),
+            value => return value.deserialize_struct(name, fields, visitor),
+        };
+
+        let mut from_env = HashSet::new();
+        for field in fields {
+            let field_var = var_name(&var, field);
+            let value = if IGNORED.contains(&field_var.as_str()) {
+                None
+            } else {
+                env::var(&field_var).ok()
+            };
+            if let Some(value) = value {
+                mapping.insert(Value::from(*field), Value::String(value));
+                from_env.insert(field.to_string());
+            } else if !mapping.contains_key(*field) {
+                // The section is missing from the file, but some of its settings are overridden.
+                let prefix = format!("{field_var}_");
+                let overridden = env::vars_os().any(|(name, _)| {
+                    let name = name.to_string_lossy();
+                    name.starts_with(&prefix) && !IGNORED.contains(&name.as_ref())
+                });
+                if overridden {
+                    mapping.insert(Value::from(*field), Value::Mapping(Mapping::new()));
+                }
+            }
+        }
+
+        visitor.visit_map(OverlayMap {
+            iter: mapping.into_iter(),
+            value: None,
+            var: Some(var),
+            from_env,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match &self.value {
+            Value::Null => visitor.visit_none(),
+            Value::String(text) if self.from_env && text.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match &self.value {
+            Value::String(text) if self.from_env => match text.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => visitor.visit_bool(true),
+                "0" | "false" | "no" | "off" | "" => visitor.visit_bool(false),
+                _ => Err(Error::custom(format!("invalid boolean '{text}', expected 1 or 0"))),
+            },
+            _ => self.value.deserialize_bool(visitor),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            // Lists are given as comma separated values.
+            Value::String(text) if self.from_env => {
+                let items: Vec<Value> = text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(Value::from)
+                    .collect();
+                visitor.visit_seq(OverlaySeq {
+                    iter: items.into_iter(),
+                    from_env: true,
+                })
+            }
+            value => Overlay { value, ..self }.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value {
+            // Plain scalars such as `false` name a variant, as they do when the text of the file is parsed.
+            value @ (Value::Bool(_) | Value::Number(_)) => {
+                let variant = serde_yaml::to_string(&value)?.trim().to_string();
+                Value::String(variant).deserialize_enum(name, variants, visitor)
+            }
+            value => value.deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        self.value.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_scalar! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128 deserialize_u8
+        deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64
+        deserialize_unit
+    }
+
+    forward_value! {
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_identifier
+    }
+}
+
+impl<'de> MapAccess<'de> for OverlayMap {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                let result = seed.deserialize(key.clone()).map(Some);
+                let name = match key {
+                    Value::String(name) => name,
+                    key => serde_yaml::to_string(&key)?.trim().to_string(),
+                };
+                self.value = Some((name, value));
+                result
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let (key, value) = self.value.take().expect("value should be read after its key");
+        let from_env = self.from_env.contains(&key);
+        let var = self.var.as_deref().map(|var| var_name(var, &key));
+        seed.deserialize(Overlay { value, var, from_env })
+            .map_err(|err| match (from_env, &self.var) {
+                (true, Some(var)) => Error::custom(format!("{}: {}", var_name(var, &key), err)),
+                _ => err,
+            })
+    }
+}
+
+impl<'de> SeqAccess<'de> for OverlaySeq {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some(value) => seed
+                .deserialize(Overlay {
+                    value,
+                    var: None,
+                    from_env: self.from_env,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[test]
+fn test_overrides() {
+    use serde::Deserialize;
+
+    use crate::settings::Settings;
+
+    // An empty section means the same as a missing one, which is how overridden settings of missing
+    // sections are deserialized.
+    for section in [
+        "configs",
+        "prompt",
+        "behavior",
+        "hooks",
+        "fzf",
+        "discovery",
+        "vault",
+        "audit",
+        "credential_cache",
+        "oidc",
+        "openshift",
+        "integrations",
+        "update",
+    ] {
+        let empty: Settings = deserialize(serde_yaml::from_str(&format!("{section}: {{}}")).unwrap()).unwrap();
+        assert_eq!(format!("{empty:?}"), format!("{:?}", Settings::default()), "{section}");
+    }
+
+    env::set_var("KUBIE_TEST_OVERRIDES_FZF_IGNORE_CASE", "yes");
+    env::set_var("KUBIE_TEST_OVERRIDES_FZF_HEIGHT", "40%");
+    env::set_var("KUBIE_TEST_OVERRIDES_CONFIGS_INCLUDE", "/kube/a.yaml, /kube/*.yml");
+    env::set_var("KUBIE_TEST_OVERRIDES_PROMPT_DISABLE", "maybe");
+    let value: Value =
+        serde_yaml::from_str("fzf:\n  reverse: true\nbehavior:\n  validate_namespaces: false\n").unwrap();
+    let overlay = |value| {
+        Settings::deserialize(Overlay {
+            value,
+            var: Some("KUBIE_TEST_OVERRIDES".into()),
+            from_env: false,
+        })
+    };
+    let err = overlay(value.clone()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("KUBIE_TEST_OVERRIDES_PROMPT_DISABLE: invalid boolean 'maybe'"));
+
+    env::set_var("KUBIE_TEST_OVERRIDES_PROMPT_DISABLE", "1");
+    let settings = overlay(value).unwrap();
+    assert!(settings.fzf.reverse);
+    assert!(settings.fzf.ignore_case);
+    assert_eq!(settings.fzf.height.as_deref(), Some("40%"));
+    assert_eq!(settings.configs.include, ["/kube/a.yaml", "/kube/*.yml"]);
+    assert!(settings.prompt.disable);
+    assert!(!settings.behavior.validate_namespaces.can_list_namespaces());
+}
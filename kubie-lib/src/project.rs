@@ -0,0 +1,125 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+
+use crate::state::State;
+
+/// Name of the file declaring the context of a project directory.
+pub const FILE_NAME: &str = ".kubie.yaml";
+
+/// Context and namespace declared by a `.kubie.yaml` file, entered with `kubie up`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    pub context: String,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Settings of the project, merged over the config files once the project file is trusted.
+    #[serde(default)]
+    pub settings: Option<Value>,
+}
+
+/// A project file along with its raw contents, which are what the user trusts.
+pub struct ProjectFile {
+    pub path: PathBuf,
+    pub contents: String,
+    pub project: Project,
+}
+
+impl ProjectFile {
+    pub fn load(path: &Path) -> Result<ProjectFile> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let project = serde_yaml::from_str(&contents).with_context(|| format!("Could not parse {}", path.display()))?;
+        Ok(ProjectFile {
+            path: path.to_path_buf(),
+            contents,
+            project,
+        })
+    }
+
+    /// Key of the file in the trusted projects of the state.
+    pub fn key(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    /// SHA-256 digest of the contents, so that a modified file has to be trusted again.
+    pub fn digest(&self) -> String {
+        Sha256::digest(self.contents.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Whether the user trusted the file with its current contents.
+    pub fn is_trusted(&self, state: &State) -> bool {
+        state.trusted_projects.get(&self.key()) == Some(&self.digest())
+    }
+}
+
+/// Find the project file of a directory, looking in its parents when it has none.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|d| d.join(FILE_NAME)).find(|p| p.is_file())
+}
+
+/// Settings of the project file of the current directory, when it has some and the user trusts it.
+pub fn trusted_settings() -> Result<Option<(PathBuf, Value)>> {
+    match env::current_dir() {
+        Ok(dir) => trusted_settings_of(&dir),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Settings of the project file of a directory, when it has some and the user trusts it. A project file
+/// which cannot be parsed is skipped with a warning, so that kubie keeps working in its directory.
+fn trusted_settings_of(dir: &Path) -> Result<Option<(PathBuf, Value)>> {
+    let Some(path) = find(dir) else {
+        return Ok(None);
+    };
+    let file = match ProjectFile::load(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Warning: {err:#}");
+            return Ok(None);
+        }
+    };
+    if file.project.settings.is_none() {
+        return Ok(None);
+    }
+    if !file.is_trusted(&State::load()?) {
+        eprintln!(
+            "Warning: the settings of {} are ignored until it is trusted with `kubie up`",
+            path.display()
+        );
+        return Ok(None);
+    }
+    Ok(file.project.settings.map(|settings| (path, settings)))
+}
+
+#[test]
+fn test_find() {
+    let root = tempfile::tempdir().unwrap();
+    let nested = root.path().join("project/src/module");
+    fs::create_dir_all(&nested).unwrap();
+    assert_eq!(find(&nested), None);
+
+    fs::write(root.path().join("project").join(FILE_NAME), "context: dev\n").unwrap();
+    assert_eq!(find(&nested), Some(root.path().join("project").join(FILE_NAME)));
+
+    fs::write(nested.join(FILE_NAME), "context: prod\n").unwrap();
+    assert_eq!(find(&nested), Some(nested.join(FILE_NAME)));
+}
+
+#[test]
+fn test_malformed_project_is_skipped() {
+    let root = tempfile::tempdir().unwrap();
+    fs::write(root.path().join(FILE_NAME), "context: dev\nunknown: true\n").unwrap();
+    assert!(trusted_settings_of(root.path()).unwrap().is_none());
+
+    fs::write(root.path().join(FILE_NAME), "context: [dev\n").unwrap();
+    assert!(trusted_settings_of(root.path()).unwrap().is_none());
+}
@@ -3,8 +3,8 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::ioutil;
 use crate::state::State;
+use crate::store::{self, Document};
 use crate::vars;
 
 /// Session contains information which is scoped to a kubie shell.
@@ -14,6 +14,9 @@ use crate::vars;
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Session {
     history: Vec<HistoryEntry>,
+    /// Prompt of the shell as plain text, for the tools which show it without running kubie.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt: Option<String>,
 }
 
 impl Session {
@@ -23,11 +26,7 @@ impl Session {
             Some(x) => x,
         };
 
-        if !session_path.exists() {
-            return Ok(Default::default());
-        }
-
-        ioutil::read_json(session_path)
+        store::load(&session_path)
     }
 
     pub fn save(&self, path: Option<&Path>) -> Result<()> {
@@ -36,7 +35,11 @@ impl Session {
             None => vars::get_session_path().context("KUBIE_SESSION env variable missing")?,
         };
 
-        ioutil::write_json(session_path, self)
+        store::save(&session_path, self)
+    }
+
+    pub fn set_prompt(&mut self, prompt: Option<String>) {
+        self.prompt = prompt;
     }
 
     pub fn add_history_entry(&mut self, context: impl Into<String>, namespace: Option<impl Into<String>>) {
@@ -57,6 +60,7 @@ impl Session {
         self.add_history_entry(&ctx, ns.as_deref());
 
         State::modify(|s| {
+            s.record_switch(&ctx, ns.as_deref());
             if ns.is_some() {
                 s.namespace_history.insert(ctx.clone(), ns);
             }
@@ -65,6 +69,29 @@ impl Session {
         })
     }
 
+    /// The contexts entered in this session with the namespace they were last used in, the most
+    /// recent first.
+    pub fn recent_contexts(&self) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = vec![];
+        for entry in self.history.iter().rev() {
+            if !entries.iter().any(|e| e.context == entry.context) {
+                entries.push(entry.clone());
+            }
+        }
+        entries
+    }
+
+    /// The namespaces used with the context in this session, the most recent first.
+    pub fn recent_namespaces(&self, context_name: &str) -> Vec<Option<&str>> {
+        let mut namespaces = vec![];
+        for entry in self.history.iter().rev().filter(|e| e.context == context_name) {
+            if !namespaces.contains(&entry.namespace.as_deref()) {
+                namespaces.push(entry.namespace.as_deref());
+            }
+        }
+        namespaces
+    }
+
     pub fn get_last_context(&self) -> Option<&HistoryEntry> {
         let current_context = self.history.last()?;
         self.history
@@ -88,7 +115,12 @@ impl Session {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Document for Session {
+    // Version 1 only adds the version to the unversioned sessions.
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct HistoryEntry {
     pub context: String,
     pub namespace: Option<String>,
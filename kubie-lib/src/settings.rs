@@ -0,0 +1,1661 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use glob::glob;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use wildmatch::WildMatch;
+
+use crate::overrides;
+use crate::project;
+
+lazy_static! {
+    static ref HOME_DIR: String = dirs::home_dir()
+        .expect("could not get home directory path")
+        .to_str()
+        .expect("home directory contains non unicode characters")
+        .to_string();
+}
+
+#[inline]
+fn home_dir() -> &'static str {
+    &HOME_DIR
+}
+
+pub fn expanduser(path: &str) -> String {
+    if let Some(stripped) = path.strip_prefix("~/") {
+        format!("{}/{}", home_dir(), stripped)
+    } else {
+        path.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fzf {
+    #[serde(default = "def_bool_true")]
+    pub mouse: bool,
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub info_hidden: bool,
+    #[serde(default)]
+    pub height: Option<String>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default = "def_bool_true")]
+    pub preview: bool,
+    #[serde(default)]
+    pub columns: bool,
+    #[serde(default)]
+    pub static_menu: bool,
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    #[serde(default)]
+    pub ctx: FzfOverrides,
+    #[serde(default)]
+    pub ns: FzfOverrides,
+}
+
+impl Default for Fzf {
+    fn default() -> Self {
+        Fzf {
+            mouse: true,
+            reverse: false,
+            ignore_case: false,
+            info_hidden: false,
+            height: None,
+            prompt: None,
+            color: None,
+            preview: true,
+            columns: false,
+            static_menu: false,
+            extra_args: vec![],
+            ctx: FzfOverrides::default(),
+            ns: FzfOverrides::default(),
+        }
+    }
+}
+
+impl Fzf {
+    /// Settings of the menus of contexts, with `fzf.ctx` applied.
+    pub fn for_contexts(&self) -> Fzf {
+        self.with(&self.ctx)
+    }
+
+    /// Settings of the menus of namespaces, with `fzf.ns` applied.
+    pub fn for_namespaces(&self) -> Fzf {
+        self.with(&self.ns)
+    }
+
+    fn with(&self, overrides: &FzfOverrides) -> Fzf {
+        let mut extra_args = self.extra_args.clone();
+        extra_args.extend(overrides.extra_args.iter().cloned());
+        Fzf {
+            mouse: overrides.mouse.unwrap_or(self.mouse),
+            reverse: overrides.reverse.unwrap_or(self.reverse),
+            ignore_case: overrides.ignore_case.unwrap_or(self.ignore_case),
+            info_hidden: overrides.info_hidden.unwrap_or(self.info_hidden),
+            height: overrides.height.clone().or_else(|| self.height.clone()),
+            prompt: overrides.prompt.clone().or_else(|| self.prompt.clone()),
+            color: overrides.color.clone().or_else(|| self.color.clone()),
+            preview: overrides.preview.unwrap_or(self.preview),
+            extra_args,
+            ..self.clone()
+        }
+    }
+}
+
+/// Settings of `fzf` which only apply to the menus of contexts, or of namespaces. Their `extra_args` come
+/// after the ones of `fzf`, and override them.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FzfOverrides {
+    #[serde(default)]
+    pub mouse: Option<bool>,
+    #[serde(default)]
+    pub reverse: Option<bool>,
+    #[serde(default)]
+    pub ignore_case: Option<bool>,
+    #[serde(default)]
+    pub info_hidden: Option<bool>,
+    #[serde(default)]
+    pub height: Option<String>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub preview: Option<bool>,
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub default_editor: Option<String>,
+    #[serde(default)]
+    pub configs: Configs,
+    #[serde(default)]
+    pub prompt: Prompt,
+    #[serde(default)]
+    pub behavior: Behavior,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub fzf: Fzf,
+    #[serde(default)]
+    pub discovery: Discovery,
+    #[serde(default)]
+    pub vault: Vault,
+    #[serde(default)]
+    pub audit: Audit,
+    #[serde(default)]
+    pub credential_cache: CredentialCache,
+    #[serde(default)]
+    pub oidc: Oidc,
+    #[serde(default)]
+    pub openshift: OpenShift,
+    #[serde(default)]
+    pub integrations: Integrations,
+    #[serde(default)]
+    pub update: Update,
+    #[serde(default)]
+    pub env: Vec<ContextEnv>,
+    #[serde(default)]
+    pub tags: Vec<ContextTags>,
+    #[serde(default)]
+    pub metadata: Vec<ContextMetadata>,
+    #[serde(default)]
+    pub kubectl_path: Option<String>,
+    #[serde(default)]
+    pub kubectl_paths: Vec<ContextKubectl>,
+    #[serde(default)]
+    pub login: Vec<ContextLogin>,
+    #[serde(default)]
+    pub favorites: Vec<String>,
+    #[serde(default)]
+    pub namespaces: Vec<ContextNamespaces>,
+    #[serde(default)]
+    pub shells: Vec<ContextShell>,
+}
+
+/// Names of the config file in each of its directories, in the order in which they are looked up.
+const FILE_NAMES: &[&str] = &["kubie.yaml", "kubie.yml", "kubie.toml"];
+
+/// Directory of the system-wide config file, whose settings apply to every user.
+const SYSTEM_DIR: &str = "/etc/kubie";
+
+fn config_paths(dirs: &[String]) -> Vec<String> {
+    dirs.iter()
+        .flat_map(|dir| FILE_NAMES.iter().map(move |name| format!("{dir}/{name}")))
+        .collect()
+}
+
+/// Merge a layer of settings into the settings of the lower layers. Sections are merged key by key
+/// while other values, lists included, are replaced.
+fn merge(base: &mut serde_yaml::Value, layer: serde_yaml::Value) {
+    match (base, layer) {
+        (_, serde_yaml::Value::Null) => {}
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+impl Settings {
+    /// Paths at which the config file of the user is looked up, in ~/.kube and then in the XDG config
+    /// directory.
+    pub fn paths() -> Vec<String> {
+        let xdg_config_home = env::var("XDG_CONFIG_HOME")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or_else(|| format!("{}/.config", home_dir()));
+        config_paths(&[format!("{}/.kube", home_dir()), format!("{xdg_config_home}/kubie")])
+    }
+
+    /// Paths at which the system-wide config file is looked up.
+    pub fn system_paths() -> Vec<String> {
+        if cfg!(windows) {
+            return vec![];
+        }
+        config_paths(&[SYSTEM_DIR.to_string()])
+    }
+
+    /// Path of the config file of the user, the first of `paths` which exists or ~/.kube/kubie.yaml.
+    pub fn path() -> String {
+        let mut paths = Self::paths();
+        let index = paths.iter().position(|p| Path::new(p).exists()).unwrap_or(0);
+        paths.swap_remove(index)
+    }
+
+    /// Config files which exist, from the lowest precedence to the highest: the system-wide file and the
+    /// file of the user.
+    pub fn layers() -> Vec<PathBuf> {
+        [Self::system_paths(), Self::paths()]
+            .into_iter()
+            .filter_map(|paths| paths.into_iter().map(PathBuf::from).find(|p| p.exists()))
+            .collect()
+    }
+
+    pub fn is_toml(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "toml")
+    }
+
+    /// Parse the text of the config file, in TOML when its extension is .toml and in YAML otherwise.
+    pub fn parse(path: &Path, text: &str) -> Result<serde_yaml::Value> {
+        // Parsed without the overrides first, for the errors to have their position in the file.
+        if Self::is_toml(path) {
+            toml::from_str::<Settings>(text)?;
+            let table: toml::Table = toml::from_str(text)?;
+            Ok(serde_yaml::to_value(table)?)
+        } else {
+            serde_yaml::from_str::<Settings>(text)?;
+            Ok(serde_yaml::from_str(text)?)
+        }
+    }
+
+    pub fn load() -> Result<Settings> {
+        let mut value = serde_yaml::Value::Null;
+        for path in Self::layers() {
+            let text = fs::read_to_string(&path)?;
+            let layer = Self::parse(&path, &text)
+                .with_context(|| format!("could not parse kubie config {}", path.display()))?;
+            merge(&mut value, layer);
+        }
+        if let Some((path, layer)) = project::trusted_settings()? {
+            // Checked as text, in which plain scalars such as `false` can name the variant of an enum.
+            serde_yaml::from_str::<Settings>(&serde_yaml::to_string(&layer)?)
+                .with_context(|| format!("could not parse the settings of {}", path.display()))?;
+            merge(&mut value, layer);
+        }
+        let mut settings: Settings =
+            overrides::deserialize(value).context("could not apply the KUBIE_* environment variables")?;
+
+        // Very important to exclude kubie's own config files such as ~/.kube/kubie.yaml from the results.
+        settings.configs.exclude.extend(Self::paths());
+        settings.configs.exclude.extend(Self::system_paths());
+        Ok(settings)
+    }
+
+    /// Paths of the kubeconfig files, in the order of precedence of their contexts: the order of the
+    /// includes, the files matching a glob being sorted by path. As with kubectl, the files of the
+    /// "$KUBECONFIG" entry keep the order of the variable. A file included several times keeps its first
+    /// position.
+    pub fn get_kube_configs_paths(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.get_kube_configs_paths_and_skipped()?.0)
+    }
+
+    /// Like `get_kube_configs_paths`, along with the files which were skipped because they match
+    /// `configs.exclude_patterns`, because they expired or because they could not be read.
+    pub fn get_kube_configs_paths_and_skipped(&self) -> Result<(Vec<PathBuf>, Vec<SkippedFile>)> {
+        let mut paths: Vec<PathBuf> = vec![];
+        let mut skipped = vec![];
+        let add = |paths: &mut Vec<PathBuf>, path: PathBuf| {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        };
+        let mut insert = |paths: &mut Vec<PathBuf>, entry: glob::GlobResult| match entry {
+            Ok(path) => add(paths, path),
+            Err(err) => skipped.push(SkippedFile {
+                path: err.path().to_path_buf(),
+                reason: format!("could not be read: {}", err.error()),
+                invalid: true,
+                expired: false,
+            }),
+        };
+        for inc in &self.configs.include {
+            if crate::sources::remote::is_remote(inc) {
+                if let Some(path) = crate::sources::remote::fetch(&self.configs.remote, inc) {
+                    add(&mut paths, path);
+                }
+                continue;
+            }
+
+            if inc == "$KUBECONFIG" {
+                for path in user_kubeconfig_paths().into_iter().filter(|p| p.exists()) {
+                    add(&mut paths, path);
+                }
+                continue;
+            }
+
+            let expanded = expanduser(inc);
+            for entry in glob(&expanded)? {
+                insert(&mut paths, entry);
+            }
+        }
+
+        for source in &self.configs.git_sources {
+            for pattern in crate::sources::git::include_globs(source) {
+                for entry in glob(&pattern)? {
+                    insert(&mut paths, entry);
+                }
+            }
+        }
+
+        for path in crate::discovery::managed_kubeconfigs(self) {
+            if path.exists() {
+                add(&mut paths, path);
+            }
+        }
+
+        for exc in &self.configs.exclude {
+            let expanded = expanduser(exc);
+            for entry in glob(&expanded)?.flatten() {
+                paths.retain(|path| *path != entry);
+            }
+        }
+
+        paths.retain(|path| match self.configs.excluded_by(path) {
+            Some(reason) => {
+                skipped.push(SkippedFile {
+                    path: path.clone(),
+                    reason,
+                    invalid: false,
+                    expired: false,
+                });
+                false
+            }
+            None => true,
+        });
+
+        paths.retain(|path| match self.expired_by(path) {
+            Some(reason) => {
+                skipped.push(SkippedFile {
+                    path: path.clone(),
+                    reason,
+                    invalid: false,
+                    expired: true,
+                });
+                false
+            }
+            None => true,
+        });
+
+        Ok((paths, skipped))
+    }
+
+    /// How long a kubeconfig file is used after it was last written: the ttl of the first entry of
+    /// `configs.expire` matching it, or the ttl of the discovery backend which writes it.
+    pub fn kubeconfig_ttl(&self, path: &Path) -> Option<Duration> {
+        let expire = self.configs.expire.iter().find(|expire| {
+            glob::Pattern::new(&expanduser(&expire.include)).is_ok_and(|pattern| pattern.matches_path(path))
+        });
+        match expire {
+            Some(expire) => Some(expire.ttl),
+            None => crate::discovery::managed_kubeconfig_ttl(self, path),
+        }
+    }
+
+    /// Why the kubeconfig file expired, if it did.
+    fn expired_by(&self, path: &Path) -> Option<String> {
+        let ttl = self.kubeconfig_ttl(path)?;
+        let age = fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()?
+            .elapsed()
+            .ok()?;
+        (age > ttl).then(|| format!("it expired, it was last written more than {} ago", format_duration(ttl)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Configs {
+    #[serde(default = "default_include_path")]
+    pub include: Vec<String>,
+    #[serde(default = "default_exclude_path")]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_exclude_patterns")]
+    pub exclude_patterns: Vec<ExcludePattern>,
+    #[serde(default)]
+    pub remote: Remote,
+    #[serde(default)]
+    pub git_sources: Vec<GitSource>,
+    #[serde(default)]
+    pub conflict: Conflict,
+    #[serde(default)]
+    pub expire: Vec<Expire>,
+}
+
+impl Default for Configs {
+    fn default() -> Self {
+        Configs {
+            include: default_include_path(),
+            exclude: default_exclude_path(),
+            exclude_patterns: default_exclude_patterns(),
+            remote: Remote::default(),
+            git_sources: vec![],
+            conflict: Conflict::default(),
+            expire: vec![],
+        }
+    }
+}
+
+impl Configs {
+    /// Why the file is excluded by `exclude_patterns`, if it is.
+    fn excluded_by(&self, path: &Path) -> Option<String> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let mut content = None;
+        for pattern in &self.exclude_patterns {
+            match pattern {
+                ExcludePattern::Name(regex) if regex.is_match(&name) => {
+                    return Some(format!("its name matches the exclude pattern {regex}"));
+                }
+                ExcludePattern::Content { content: regex } => {
+                    let content = content.get_or_insert_with(|| fs::read_to_string(path).unwrap_or_default());
+                    if regex.is_match(content) {
+                        return Some(format!("its content matches the exclude pattern {regex}"));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Regex excluding the kubeconfig files whose name, or whose content, matches it.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ExcludePattern {
+    Name(#[serde(deserialize_with = "deserialize_regex")] Regex),
+    Content {
+        #[serde(deserialize_with = "deserialize_regex")]
+        content: Regex,
+    },
+}
+
+/// A kubeconfig file which was found but not loaded, with the reason why.
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: String,
+    /// Whether the file could not be read or parsed, rather than being excluded on purpose.
+    pub invalid: bool,
+    /// Whether the file is older than its ttl, and can be deleted by `kubie prune --expired`.
+    pub expired: bool,
+}
+
+/// Kubeconfig files which are only used for some time after they were last written, such as the ones of
+/// ephemeral CI clusters.
+#[derive(Debug, Deserialize)]
+pub struct Expire {
+    /// Glob of the files, as in `include`.
+    pub include: String,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub ttl: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+pub struct Remote {
+    #[serde(
+        default = "default_remote_refresh_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub refresh_interval: Duration,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Remote {
+            refresh_interval: default_remote_refresh_interval(),
+            headers: HashMap::new(),
+            token_env: None,
+        }
+    }
+}
+
+/// What to do when several files define a context with the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    /// Fail with the names of the files.
+    Error,
+    /// Use the context of the first file, in the order of the includes.
+    #[default]
+    First,
+    /// Use the context of the last file, in the order of the includes.
+    Last,
+    /// Keep every context, suffixing their names with `@` and the name of their file.
+    Rename,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitSource {
+    pub url: String,
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Paths of the user's KUBECONFIG variable, split with the platform's separator (`:` or `;` on Windows).
+/// Inside of kubie shells, KUBECONFIG points to kubie's own file and the user's value is kept aside.
+fn user_kubeconfig_paths() -> Vec<PathBuf> {
+    let name = if crate::vars::is_kubie_active() {
+        "KUBIE_USER_KUBECONFIG"
+    } else {
+        "KUBECONFIG"
+    };
+    match env::var_os(name) {
+        Some(value) => env::split_paths(&value).filter(|p| !p.as_os_str().is_empty()).collect(),
+        None => vec![],
+    }
+}
+
+fn default_remote_refresh_interval() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn default_include_path() -> Vec<String> {
+    let home_dir = home_dir();
+    vec![
+        format!("{home_dir}/.kube/config"),
+        format!("{home_dir}/.kube/*.yml"),
+        format!("{home_dir}/.kube/*.yaml"),
+        format!("{home_dir}/.kube/configs/*.yml"),
+        format!("{home_dir}/.kube/configs/*.yaml"),
+        format!("{home_dir}/.kube/kubie/*.yml"),
+        format!("{home_dir}/.kube/kubie/*.yaml"),
+    ]
+}
+
+fn default_exclude_path() -> Vec<String> {
+    vec![]
+}
+
+/// Backups and temporary copies left by editors and tools.
+fn default_exclude_patterns() -> Vec<ExcludePattern> {
+    [r"\.bak$", r"~$", r"\.orig$", r"\.sw[po]$"]
+        .into_iter()
+        .map(|pattern| ExcludePattern::Name(Regex::new(pattern).expect("default exclude pattern is valid")))
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Prompt {
+    #[serde(default = "def_bool_false")]
+    pub disable: bool,
+    #[serde(default = "def_bool_true")]
+    pub show_depth: bool,
+    #[serde(default = "def_bool_false")]
+    pub zsh_use_rps1: bool,
+    #[serde(default = "def_bool_false")]
+    pub fish_use_rprompt: bool,
+    #[serde(default = "def_bool_false")]
+    pub xonsh_use_right_prompt: bool,
+    #[serde(default)]
+    pub context_rewrite: Vec<ContextRewrite>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub colors: Vec<ContextColor>,
+    #[serde(default)]
+    pub integration: PromptIntegration,
+}
+
+/// How kubie's prompt is combined with the prompt of the shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptIntegration {
+    /// Replace the prompt of the shell with kubie's prompt.
+    Replace,
+    /// Show kubie's prompt before the prompt of the shell.
+    #[default]
+    Prepend,
+    /// Leave the prompt of the shell alone, for prompt frameworks which show KUBIE_PROMPT.
+    External,
+}
+
+impl Prompt {
+    /// Whether kubie's prompt is added to the prompt of the shell.
+    pub fn is_injected(&self) -> bool {
+        !self.disable && self.integration != PromptIntegration::External
+    }
+
+    /// Whether kubie's prompt replaces the prompt of the shell.
+    pub fn is_replacing(&self) -> bool {
+        self.integration == PromptIntegration::Replace
+    }
+
+    /// Name of a context as shown in the prompt, the menus and the headers, after applying the rewrite rules
+    /// in order. The real name is still the one used to find contexts.
+    pub fn display_name(&self, context_name: &str) -> String {
+        let mut name = context_name.to_string();
+        for rule in &self.context_rewrite {
+            name = rule.pattern.replace_all(&name, rule.replacement.as_str()).into_owned();
+        }
+        name
+    }
+
+    /// Color of the first entry of `colors` matching the context.
+    pub fn color_for(&self, context_name: &str) -> Option<PromptColor> {
+        self.colors
+            .iter()
+            .find(|c| WildMatch::new(&c.context).matches(context_name))
+            .map(|c| c.color)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextColor {
+    pub context: String,
+    pub color: PromptColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum), clap(rename_all = "lower"))]
+#[serde(rename_all = "lowercase")]
+pub enum PromptColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl PromptColor {
+    pub const ALL: [PromptColor; 8] = [
+        PromptColor::Black,
+        PromptColor::Red,
+        PromptColor::Green,
+        PromptColor::Yellow,
+        PromptColor::Blue,
+        PromptColor::Magenta,
+        PromptColor::Cyan,
+        PromptColor::White,
+    ];
+
+    /// Code of the color in ANSI escape sequences.
+    pub fn code(self) -> u32 {
+        30 + self as u32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptColor::Black => "black",
+            PromptColor::Red => "red",
+            PromptColor::Green => "green",
+            PromptColor::Yellow => "yellow",
+            PromptColor::Blue => "blue",
+            PromptColor::Magenta => "magenta",
+            PromptColor::Cyan => "cyan",
+            PromptColor::White => "white",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextRewrite {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Prompt {
+            disable: false,
+            show_depth: true,
+            zsh_use_rps1: false,
+            fish_use_rprompt: false,
+            xonsh_use_right_prompt: false,
+            context_rewrite: vec![],
+            format: None,
+            colors: vec![],
+            integration: PromptIntegration::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum), clap(rename_all = "lower"))]
+#[serde(rename_all = "lowercase")]
+#[derive(Default)]
+pub enum ContextHeaderBehavior {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ContextHeaderBehavior {
+    pub fn should_print_headers(&self) -> bool {
+        match self {
+            ContextHeaderBehavior::Auto => io::stdout().is_terminal(),
+            ContextHeaderBehavior::Always => true,
+            ContextHeaderBehavior::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Behavior {
+    #[serde(default)]
+    pub validate_namespaces: ValidateNamespacesBehavior,
+    #[serde(default)]
+    pub print_context_in_exec: ContextHeaderBehavior,
+    #[serde(default)]
+    pub print_context_banner: Option<ContextHeaderBehavior>,
+    #[serde(default = "def_bool_false")]
+    pub allow_multiple_context_patterns: bool,
+    #[serde(default)]
+    pub protected_contexts: Vec<String>,
+    #[serde(default)]
+    pub read_only_contexts: Vec<String>,
+    #[serde(default)]
+    pub read_only_user: Option<String>,
+    #[serde(default)]
+    pub read_only_groups: Vec<String>,
+    #[serde(default = "def_bool_false")]
+    pub prompt_for_namespace: bool,
+    #[serde(default)]
+    pub namespace_lookup_timeout: Option<u64>,
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    #[serde(default)]
+    pub max_depth_action: MaxDepthAction,
+    #[serde(default)]
+    pub ambiguous_namespace: AmbiguousNamespaceAction,
+    #[serde(default = "def_bool_false")]
+    pub reuse_session: bool,
+    #[serde(default = "def_bool_false")]
+    pub strict_parsing: bool,
+    #[serde(default)]
+    pub session_dir: Option<String>,
+    #[serde(default)]
+    pub idle_contexts: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub idle_timeout: Option<Duration>,
+    #[serde(default)]
+    pub idle_action: IdleAction,
+}
+
+impl Behavior {
+    /// How long to wait for the cluster when listing its namespaces.
+    pub fn namespace_lookup_timeout(&self) -> Duration {
+        Duration::from_secs(self.namespace_lookup_timeout.unwrap_or(10))
+    }
+
+    /// Whether to print the banner when entering a context, which is never printed unless configured.
+    pub fn should_print_context_banner(&self) -> bool {
+        self.print_context_banner
+            .as_ref()
+            .is_some_and(ContextHeaderBehavior::should_print_headers)
+    }
+
+    pub fn is_protected_context(&self, context_name: &str) -> bool {
+        self.protected_contexts
+            .iter()
+            .any(|pattern| WildMatch::new(pattern).matches(context_name))
+    }
+
+    pub fn is_read_only_context(&self, context_name: &str) -> bool {
+        self.read_only_contexts
+            .iter()
+            .any(|pattern| WildMatch::new(pattern).matches(context_name))
+    }
+
+    /// How long a shell in the context can stay idle before `idle_action` is taken, if it is watched.
+    pub fn idle_timeout_for(&self, context_name: &str) -> Option<Duration> {
+        let watched = self
+            .idle_contexts
+            .iter()
+            .any(|pattern| WildMatch::new(pattern).matches(context_name));
+        self.idle_timeout.filter(|_| watched)
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidateNamespacesBehavior {
+    #[default]
+    True,
+    False,
+    Partial,
+}
+
+impl ValidateNamespacesBehavior {
+    pub fn can_list_namespaces(&self) -> bool {
+        match self {
+            ValidateNamespacesBehavior::True | ValidateNamespacesBehavior::Partial => true,
+            ValidateNamespacesBehavior::False => false,
+        }
+    }
+}
+
+/// What to do when the namespace given to `kubie ns` matches several namespaces.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AmbiguousNamespaceAction {
+    #[default]
+    Select,
+    Error,
+}
+
+/// What to do when a shell in one of `behavior.idle_contexts` stays idle for `behavior.idle_timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    #[default]
+    Warn,
+    Exit,
+}
+
+/// What to do when spawning a shell would nest more kubie shells than `behavior.max_depth`.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MaxDepthAction {
+    #[default]
+    Error,
+    Warn,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Hooks {
+    #[serde(default)]
+    pub start_ctx: String,
+    #[serde(default)]
+    pub stop_ctx: String,
+    #[serde(default)]
+    pub change_ns: String,
+    #[serde(default)]
+    pub pre_ctx: String,
+    #[serde(default)]
+    pub contexts: Vec<ContextHooks>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextHooks {
+    pub context: String,
+    #[serde(default)]
+    pub start_ctx: String,
+    #[serde(default)]
+    pub stop_ctx: String,
+}
+
+impl Hooks {
+    fn collect<'a>(&'a self, context_name: &str, global: &'a str, hook: fn(&ContextHooks) -> &str) -> String {
+        let mut hooks = vec![global];
+        hooks.extend(
+            self.contexts
+                .iter()
+                .filter(|c| WildMatch::new(&c.context).matches(context_name))
+                .map(hook),
+        );
+        hooks.retain(|h| !h.is_empty());
+        hooks.join("\n")
+    }
+
+    /// The global start hook followed by the start hooks of the matching contexts, in order.
+    pub fn start_ctx_for(&self, context_name: &str) -> String {
+        self.collect(context_name, &self.start_ctx, |c| &c.start_ctx)
+    }
+
+    /// The global stop hook followed by the stop hooks of the matching contexts, in order.
+    pub fn stop_ctx_for(&self, context_name: &str) -> String {
+        self.collect(context_name, &self.stop_ctx, |c| &c.stop_ctx)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextEnv {
+    pub context: String,
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+}
+
+impl Settings {
+    /// Environment variables of the entries matching the context. Later entries take precedence.
+    pub fn env_for(&self, context_name: &str) -> HashMap<&str, &str> {
+        self.env
+            .iter()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name))
+            .flat_map(|e| e.vars.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .collect()
+    }
+
+    /// Tags of the entries matching the context, without duplicates.
+    pub fn tags_for(&self, context_name: &str) -> Vec<&str> {
+        let mut tags: Vec<&str> = Vec::new();
+        for entry in self
+            .tags
+            .iter()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name))
+        {
+            for tag in &entry.tags {
+                if !tags.contains(&tag.as_str()) {
+                    tags.push(tag);
+                }
+            }
+        }
+        tags
+    }
+
+    /// Metadata of the entries matching the context, sorted by key. Later entries take precedence.
+    pub fn metadata_for(&self, context_name: &str) -> BTreeMap<&str, &str> {
+        self.metadata
+            .iter()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name))
+            .flat_map(|e| e.fields.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .collect()
+    }
+
+    /// Position of the first pattern of `favorites` matching the context, which orders the favorite contexts
+    /// at the top of the context menu.
+    pub fn favorite_rank(&self, context_name: &str) -> Option<usize> {
+        self.favorites
+            .iter()
+            .position(|pattern| WildMatch::new(pattern).matches(context_name))
+    }
+
+    /// kubectl executable of the context: the path of the last entry matching it, or `kubectl_path`.
+    pub fn kubectl_for(&self, context_name: &str) -> String {
+        self.configured_kubectl_for(context_name)
+            .unwrap_or_else(|| "kubectl".to_string())
+    }
+
+    /// kubectl executable configured for the context, if any, as for `kubectl_for`.
+    pub fn configured_kubectl_for(&self, context_name: &str) -> Option<String> {
+        self.kubectl_paths
+            .iter()
+            .rev()
+            .find(|e| WildMatch::new(&e.context).matches(context_name))
+            .map(|e| e.path.as_str())
+            .or(self.kubectl_path.as_deref())
+            .map(expanduser)
+    }
+
+    /// Shell of the context: the shell of the last entry matching it which sets one, or `shell`.
+    pub fn shell_for(&self, context_name: &str) -> Option<&str> {
+        self.shells
+            .iter()
+            .rev()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name))
+            .find_map(|e| e.shell.as_deref())
+            .or(self.shell.as_deref())
+    }
+
+    /// Snippets of the entries matching the context, in order, added to the startup script of its shells.
+    pub fn rc_for(&self, context_name: &str) -> String {
+        let snippets: Vec<&str> = self
+            .shells
+            .iter()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name) && !e.rc.is_empty())
+            .map(|e| e.rc.trim_end())
+            .collect();
+        snippets.join("\n")
+    }
+
+    /// Login command of the context, from the last entry matching it.
+    pub fn login_for(&self, context_name: &str) -> Option<&str> {
+        self.login
+            .iter()
+            .rev()
+            .find(|e| WildMatch::new(&e.context).matches(context_name))
+            .map(|e| e.command.as_str())
+    }
+
+    /// Whether the namespace can be selected in the context: every entry matching the context must allow it
+    /// and must not deny it.
+    pub fn is_namespace_allowed(&self, context_name: &str, namespace_name: &str) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| WildMatch::new(p).matches(namespace_name));
+        self.namespaces
+            .iter()
+            .filter(|e| WildMatch::new(&e.context).matches(context_name))
+            .all(|e| (e.allow.is_empty() || matches(&e.allow)) && !matches(&e.deny))
+    }
+
+    /// Whether a kubectl executable is configured, in which case kubie shells get a kubectl shim.
+    pub fn has_kubectl_paths(&self) -> bool {
+        self.kubectl_path.is_some() || !self.kubectl_paths.is_empty()
+    }
+}
+
+/// Namespaces which can be selected in the contexts matching a pattern.
+#[derive(Debug, Deserialize)]
+pub struct ContextNamespaces {
+    pub context: String,
+    /// Patterns of the only namespaces which can be selected, any namespace when empty.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Patterns of the namespaces which cannot be selected.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Freeform metadata of the contexts matching a pattern, such as their owner or their region.
+#[derive(Debug, Deserialize)]
+pub struct ContextMetadata {
+    pub context: String,
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextKubectl {
+    pub context: String,
+    pub path: String,
+}
+
+/// Shell spawned for the contexts matching a pattern, and a snippet added to its startup script.
+#[derive(Debug, Deserialize)]
+pub struct ContextShell {
+    pub context: String,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub rc: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextLogin {
+    pub context: String,
+    pub command: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContextTags {
+    pub context: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Discovery {
+    #[serde(default)]
+    pub teleport: DiscoverySource,
+    #[serde(default)]
+    pub digitalocean: DiscoverySource,
+    #[serde(default)]
+    pub linode: DiscoverySource,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DiscoverySource {
+    #[serde(default = "def_bool_false")]
+    pub enable: bool,
+    #[serde(default)]
+    pub kubeconfig: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub ttl: Option<Duration>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Audit {
+    #[serde(default)]
+    pub log_file: Option<String>,
+}
+
+/// Use the OIDC tokens obtained by `kubie login` for the contexts whose user runs kubelogin, or uses the
+/// `oidc` auth provider.
+#[derive(Debug, Deserialize, Default)]
+pub struct Oidc {
+    #[serde(default = "def_bool_false")]
+    pub enable: bool,
+    #[serde(default)]
+    pub contexts: Vec<String>,
+}
+
+impl Oidc {
+    /// Whether the tokens of kubie are used for the context. Without patterns, every context is.
+    pub fn enabled_for(&self, context_name: &str) -> bool {
+        self.enable
+            && (self.contexts.is_empty()
+                || self
+                    .contexts
+                    .iter()
+                    .any(|pattern| WildMatch::new(pattern).matches(context_name)))
+    }
+}
+
+/// OpenShift clusters, whose namespaces are listed and created as projects, and whose tokens obtained with
+/// `oc login` expire.
+#[derive(Debug, Deserialize)]
+pub struct OpenShift {
+    #[serde(default)]
+    pub contexts: Vec<String>,
+    #[serde(default = "def_bool_true")]
+    pub login: bool,
+}
+
+impl Default for OpenShift {
+    fn default() -> Self {
+        OpenShift {
+            contexts: vec![],
+            login: true,
+        }
+    }
+}
+
+impl OpenShift {
+    /// Whether the context is of an OpenShift cluster: it matches one of the patterns, or it was created by
+    /// `oc login`, which names the contexts `<project>/<server>/<user>` and their user `<user>/<server>`.
+    pub fn is_openshift(&self, context_name: &str, user_name: &str) -> bool {
+        if self
+            .contexts
+            .iter()
+            .any(|pattern| WildMatch::new(pattern).matches(context_name))
+        {
+            return true;
+        }
+        match context_name.split('/').collect::<Vec<_>>()[..] {
+            [_, server, user] => user_name == format!("{user}/{server}"),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CredentialCache {
+    #[serde(default = "def_bool_false")]
+    pub enable: bool,
+    #[serde(default)]
+    pub contexts: Vec<String>,
+}
+
+impl CredentialCache {
+    /// Whether the credentials of the context are cached. Without patterns, every context is.
+    pub fn enabled_for(&self, context_name: &str) -> bool {
+        self.enable
+            && (self.contexts.is_empty()
+                || self
+                    .contexts
+                    .iter()
+                    .any(|pattern| WildMatch::new(pattern).matches(context_name)))
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Integrations {
+    #[serde(default)]
+    pub tmux: bool,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Update {
+    #[serde(default)]
+    pub channel: UpdateChannel,
+    #[serde(default)]
+    pub require_checksum: bool,
+}
+
+/// Which releases `kubie update` installs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Only the releases which are not marked as pre-releases.
+    #[default]
+    Stable,
+    /// Every release, including pre-releases.
+    Prerelease,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Vault {
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub contexts: Vec<VaultContext>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VaultContext {
+    pub context: String,
+    pub path: String,
+    #[serde(default)]
+    pub kubeconfig_field: Option<String>,
+    #[serde(default)]
+    pub token_field: Option<String>,
+    #[serde(default)]
+    pub ca_field: Option<String>,
+}
+
+fn def_bool_true() -> bool {
+    true
+}
+
+fn def_bool_false() -> bool {
+    false
+}
+
+/// Parse a duration such as `90`, `30s`, `15m`, `12h` or `7d`. A bare number is in seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => value.split_at(idx),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number * multiplier))
+}
+
+/// Format a duration in the largest unit dividing it, as parsed by `parse_duration`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    for (unit, size) in [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)] {
+        if seconds >= size && seconds.is_multiple_of(size) {
+            return format!("{}{unit}", seconds / size);
+        }
+    }
+    format!("{seconds}s")
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(secs) => Ok(Duration::from_secs(secs)),
+        Raw::Text(text) => {
+            parse_duration(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid duration '{text}'")))
+        }
+    }
+}
+
+fn deserialize_optional_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
+
+fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_duration("15m"), Some(Duration::from_secs(15 * 60)));
+    assert_eq!(parse_duration("2d"), Some(Duration::from_secs(2 * 24 * 60 * 60)));
+    assert_eq!(parse_duration("1w"), None);
+    assert_eq!(parse_duration("m"), None);
+
+    assert_eq!(format_duration(Duration::from_secs(2 * 24 * 60 * 60)), "2d");
+    assert_eq!(format_duration(Duration::from_secs(90 * 60)), "90m");
+    assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+}
+
+#[test]
+fn test_expired_kubeconfigs() {
+    use std::time::SystemTime;
+
+    let dir = tempfile::tempdir().unwrap();
+    let fresh = dir.path().join("ci-fresh.yaml");
+    let stale = dir.path().join("ci-stale.yaml");
+    let other = dir.path().join("prod.yaml");
+    for path in [&fresh, &stale, &other] {
+        fs::write(path, "").unwrap();
+    }
+    let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+    for path in [&stale, &other] {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+    }
+
+    let mut settings = Settings::default();
+    settings.configs.include = vec![format!("{}/*.yaml", dir.path().display())];
+    settings.configs.expire = vec![Expire {
+        include: format!("{}/ci-*.yaml", dir.path().display()),
+        ttl: Duration::from_secs(24 * 60 * 60),
+    }];
+    let (paths, skipped) = settings.get_kube_configs_paths_and_skipped().unwrap();
+    assert_eq!(paths, [fresh, other]);
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].path, stale);
+    assert!(skipped[0].expired);
+}
+
+#[test]
+fn test_idle_timeout_for() {
+    let behavior: Behavior = serde_yaml::from_str(
+        r#"
+idle_contexts: ["*prod*"]
+idle_timeout: 30m
+idle_action: exit
+"#,
+    )
+    .unwrap();
+    assert_eq!(behavior.idle_timeout_for("eu-prod"), Some(Duration::from_secs(30 * 60)));
+    assert_eq!(behavior.idle_timeout_for("dev"), None);
+    assert_eq!(behavior.idle_action, IdleAction::Exit);
+
+    let behavior: Behavior = serde_yaml::from_str("idle_contexts: [\"*prod*\"]").unwrap();
+    assert_eq!(behavior.idle_timeout_for("eu-prod"), None);
+}
+
+#[test]
+fn test_fzf_overrides() {
+    let fzf: Fzf = serde_yaml::from_str(
+        r#"
+height: 40%
+extra_args: ["--bind=ctrl-j:down"]
+ctx:
+  height: 80%
+  preview: false
+  extra_args: ["--layout=reverse"]
+"#,
+    )
+    .unwrap();
+    let contexts = fzf.for_contexts();
+    assert_eq!(contexts.height.as_deref(), Some("80%"));
+    assert!(!contexts.preview);
+    assert_eq!(contexts.extra_args, ["--bind=ctrl-j:down", "--layout=reverse"]);
+    let namespaces = fzf.for_namespaces();
+    assert_eq!(namespaces.height.as_deref(), Some("40%"));
+    assert!(namespaces.preview);
+    assert_eq!(namespaces.extra_args, ["--bind=ctrl-j:down"]);
+}
+
+#[test]
+fn test_hooks_for_context() {
+    let hooks: Hooks = serde_yaml::from_str(
+        r#"
+start_ctx: echo global
+contexts:
+  - context: "*eks*"
+    start_ctx: aws sso login
+  - context: "gke_*"
+    start_ctx: gcloud auth login
+  - context: "*"
+    stop_ctx: echo bye
+"#,
+    )
+    .unwrap();
+    assert_eq!(hooks.start_ctx_for("prod-eks-1"), "echo global\naws sso login");
+    assert_eq!(
+        hooks.start_ctx_for("gke_project_zone_name"),
+        "echo global\ngcloud auth login"
+    );
+    assert_eq!(hooks.stop_ctx_for("minikube"), "echo bye");
+}
+
+#[test]
+fn test_env_for() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+env:
+  - context: "*"
+    vars:
+      AWS_PROFILE: dev
+      VAULT_ADDR: https://vault.example.com
+  - context: "*prod-eks*"
+    vars:
+      AWS_PROFILE: prod
+"#,
+    )
+    .unwrap();
+    let env = settings.env_for("us-prod-eks-1");
+    assert_eq!(env["AWS_PROFILE"], "prod");
+    assert_eq!(env["VAULT_ADDR"], "https://vault.example.com");
+    assert_eq!(settings.env_for("minikube")["AWS_PROFILE"], "dev");
+}
+
+#[test]
+fn test_tags_for() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+tags:
+  - context: "*prod*"
+    tags: [prod, aws]
+  - context: "eu-*"
+    tags: [eu, aws]
+"#,
+    )
+    .unwrap();
+    assert_eq!(settings.tags_for("eu-prod-1"), vec!["prod", "aws", "eu"]);
+    assert!(settings.tags_for("minikube").is_empty());
+}
+
+#[test]
+fn test_metadata_for() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+metadata:
+  - context: "*prod*"
+    owner: platform
+    environment: production
+  - context: "eu-prod-*"
+    owner: payments
+    region: eu-west-1
+"#,
+    )
+    .unwrap();
+    let metadata = settings.metadata_for("eu-prod-1");
+    assert_eq!(
+        metadata.into_iter().collect::<Vec<_>>(),
+        [
+            ("environment", "production"),
+            ("owner", "payments"),
+            ("region", "eu-west-1")
+        ]
+    );
+    assert!(settings.metadata_for("minikube").is_empty());
+}
+
+#[test]
+fn test_favorite_rank() {
+    let settings: Settings = serde_yaml::from_str(r#"favorites: ["prod-eu", "dev-*"]"#).unwrap();
+    assert_eq!(settings.favorite_rank("prod-eu"), Some(0));
+    assert_eq!(settings.favorite_rank("dev-sandbox"), Some(1));
+    assert_eq!(settings.favorite_rank("prod-us"), None);
+}
+
+#[test]
+fn test_is_openshift() {
+    let openshift = OpenShift {
+        contexts: vec!["ocp-*".into()],
+        login: true,
+    };
+    assert!(openshift.is_openshift("ocp-prod", "admin"));
+    assert!(openshift.is_openshift(
+        "web/api-ocp-example-com:6443/developer",
+        "developer/api-ocp-example-com:6443"
+    ));
+    assert!(!openshift.is_openshift("web/api-ocp-example-com:6443/developer", "admin"));
+    assert!(!openshift.is_openshift("prod-eu", "admin"));
+}
+
+#[test]
+fn test_is_namespace_allowed() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+namespaces:
+  - context: "prod-*"
+    deny: ["kube-*"]
+  - context: "*"
+    allow: ["team-*", "default"]
+"#,
+    )
+    .unwrap();
+    assert!(settings.is_namespace_allowed("dev", "team-a"));
+    assert!(!settings.is_namespace_allowed("dev", "payments"));
+    assert!(settings.is_namespace_allowed("prod-eu", "default"));
+    assert!(!settings.is_namespace_allowed("prod-eu", "kube-system"));
+    assert!(Settings::default().is_namespace_allowed("prod-eu", "kube-system"));
+}
+
+#[test]
+fn test_kubectl_for() {
+    assert_eq!(Settings::default().kubectl_for("minikube"), "kubectl");
+
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+kubectl_path: /usr/local/bin/kubectl
+kubectl_paths:
+  - context: "old-*"
+    path: kubectl-1.27
+  - context: "old-eu-*"
+    path: ~/bin/kubectl-1.26
+"#,
+    )
+    .unwrap();
+    assert_eq!(settings.kubectl_for("minikube"), "/usr/local/bin/kubectl");
+    assert_eq!(settings.kubectl_for("old-us-1"), "kubectl-1.27");
+    assert_eq!(settings.kubectl_for("old-eu-1"), expanduser("~/bin/kubectl-1.26"));
+}
+
+#[test]
+fn test_shell_for() {
+    let settings: Settings = serde_yaml::from_str(
+        r#"
+shell: zsh
+shells:
+  - context: "*"
+    rc: alias k=kubectl
+  - context: "prod-*"
+    shell: bash
+    rc: |
+      source ~/toolbox/prod.sh
+"#,
+    )
+    .unwrap();
+    assert_eq!(settings.shell_for("prod-eu"), Some("bash"));
+    assert_eq!(settings.shell_for("dev"), Some("zsh"));
+    assert_eq!(settings.rc_for("prod-eu"), "alias k=kubectl\nsource ~/toolbox/prod.sh");
+    assert_eq!(settings.rc_for("dev"), "alias k=kubectl");
+}
+
+#[test]
+fn test_display_name() {
+    let prompt: Prompt = serde_yaml::from_str(
+        r#"
+context_rewrite:
+  - pattern: "^arn:aws:eks:[^:]+:[0-9]+:cluster/"
+  - pattern: "^gke_[^_]+_([^_]+)_(.+)$"
+    replacement: "gke/$2 ($1)"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        prompt.display_name("arn:aws:eks:eu-west-1:123456789012:cluster/prod"),
+        "prod"
+    );
+    assert_eq!(
+        prompt.display_name("gke_project_europe-west1_staging"),
+        "gke/staging (europe-west1)"
+    );
+    assert_eq!(prompt.display_name("minikube"), "minikube");
+    assert!(serde_yaml::from_str::<Prompt>("context_rewrite: [{pattern: '('}]").is_err());
+}
+
+#[test]
+fn test_color_for() {
+    let prompt: Prompt = serde_yaml::from_str(
+        r#"
+colors:
+  - context: "*prod*"
+    color: red
+  - context: "*staging*"
+    color: yellow
+  - context: "*"
+    color: green
+"#,
+    )
+    .unwrap();
+    assert_eq!(prompt.color_for("eu-prod-1"), Some(PromptColor::Red));
+    assert_eq!(prompt.color_for("prod-staging"), Some(PromptColor::Red));
+    assert_eq!(prompt.color_for("minikube"), Some(PromptColor::Green));
+    assert_eq!(PromptColor::Yellow.code(), 33);
+}
+
+#[test]
+fn test_expanduser() {
+    assert_eq!(
+        expanduser("~/hello/world/*.foo"),
+        format!("{}/hello/world/*.foo", home_dir())
+    );
+}
+
+#[test]
+fn test_merge() {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(
+        r#"
+configs:
+  include: [/etc/kube/*.yaml]
+behavior:
+  protected_contexts: [prod]
+fzf:
+  reverse: true
+"#,
+    )
+    .unwrap();
+    let layer = serde_yaml::from_str(
+        r#"
+configs:
+  include: [~/.kube/*.yaml]
+fzf:
+  ignore_case: true
+"#,
+    )
+    .unwrap();
+    merge(&mut value, layer);
+    merge(&mut value, serde_yaml::Value::Null);
+
+    let settings: Settings = serde_yaml::from_value(value).unwrap();
+    assert_eq!(settings.configs.include, ["~/.kube/*.yaml"]);
+    assert_eq!(settings.behavior.protected_contexts, ["prod"]);
+    assert!(settings.fzf.reverse);
+    assert!(settings.fzf.ignore_case);
+}
+
+#[test]
+fn test_kube_configs_paths_order() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.yaml", "b.yaml", "c.yaml", "d.yaml"] {
+        fs::write(dir.path().join(name), "").unwrap();
+    }
+    let path = |name: &str| dir.path().join(name);
+    let mut settings = Settings::default();
+    settings.configs.include = vec![
+        path("c.yaml").display().to_string(),
+        format!("{}/*.yaml", dir.path().display()),
+    ];
+    settings.configs.exclude = vec![path("d.yaml").display().to_string()];
+
+    let paths = settings.get_kube_configs_paths().unwrap();
+    assert_eq!(paths, [path("c.yaml"), path("a.yaml"), path("b.yaml")]);
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+use std::process::Command;
+use std::str;
+
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::Value;
+
+/// Check if a parsed YAML document was encrypted by sops, which stores its metadata under
+/// the top-level `sops` key.
+pub fn is_encrypted(document: &Value) -> bool {
+    document.get("sops").is_some_and(Value::is_mapping)
+}
+
+/// Decrypt a file with sops. The plaintext is kept in memory and never written to disk.
+pub fn decrypt(path: &Path) -> Result<Vec<u8>> {
+    let result = Command::new("sops")
+        .args(["--decrypt", "--input-type", "yaml", "--output-type", "yaml"])
+        .arg(path)
+        .output()
+        .context("Could not spawn sops")?;
+
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of sops as utf-8");
+        return Err(anyhow!("Error calling sops:\n{}", stderr));
+    }
+
+    Ok(result.stdout)
+}
+
+#[test]
+fn test_is_encrypted() {
+    let encrypted: Value = serde_yaml::from_str("clusters: []\nsops:\n  version: 3.8.1\n").unwrap();
+    let plain: Value = serde_yaml::from_str("clusters: []\n").unwrap();
+    assert!(is_encrypted(&encrypted));
+    assert!(!is_encrypted(&plain));
+}
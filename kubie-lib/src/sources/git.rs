@@ -0,0 +1,63 @@
+use std::fs::DirBuilder;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::settings::GitSource;
+use crate::state;
+
+/// Directory managed by kubie in which the repository is checked out.
+pub fn checkout_dir(source: &GitSource) -> PathBuf {
+    let key = format!("{}#{}", source.url, source.branch.as_deref().unwrap_or(""));
+    let digest = Sha256::digest(key.as_bytes());
+    let name: String = digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+    state::paths::data_dir().join("git").join(name)
+}
+
+/// Globs matching the kubeconfig files of the checked out repository.
+pub fn include_globs(source: &GitSource) -> Vec<String> {
+    let mut dir = checkout_dir(source);
+    if let Some(path) = &source.path {
+        dir = dir.join(path);
+    }
+    let dir = dir.to_str().expect("data dir path should be a valid unicode string");
+    vec![format!("{dir}/*.yml"), format!("{dir}/*.yaml")]
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<()> {
+    let mut cmd = Command::new("git");
+    if let Some(dir) = dir {
+        cmd.arg("-C").arg(dir);
+    }
+    let status = cmd.args(args).status().context("Could not spawn git")?;
+    if !status.success() {
+        return Err(anyhow!("git {} failed", args.join(" ")));
+    }
+    Ok(())
+}
+
+/// Clone the repository, or bring an existing checkout up to date with the remote branch.
+pub fn sync(source: &GitSource) -> Result<()> {
+    let dir = checkout_dir(source);
+
+    if dir.join(".git").exists() {
+        let branch = source.branch.as_deref().unwrap_or("HEAD");
+        git(Some(&dir), &["fetch", "--depth", "1", "origin", branch])?;
+        // The checkout is owned by kubie, local changes are discarded.
+        git(Some(&dir), &["reset", "--hard", "FETCH_HEAD"])?;
+    } else {
+        DirBuilder::new()
+            .recursive(true)
+            .create(dir.parent().expect("checkout dir should have a parent"))?;
+        let dir = dir.to_str().expect("data dir path should be a valid unicode string");
+        let mut args = vec!["clone", "--depth", "1"];
+        if let Some(branch) = &source.branch {
+            args.extend(["--branch", branch]);
+        }
+        args.extend([source.url.as_str(), dir]);
+        git(None, &args)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,4 @@
+//! Kubeconfig files which kubie fetches from somewhere else than the local filesystem.
+
+pub mod git;
+pub mod remote;
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::settings::Remote;
+use crate::state;
+
+pub fn is_remote(include: &str) -> bool {
+    include.starts_with("https://")
+}
+
+/// Path of the cached copy of the kubeconfig downloaded from the given URL.
+fn cache_path(url: &str) -> PathBuf {
+    let digest = Sha256::digest(url.as_bytes());
+    let name: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    state::paths::data_dir().join("remote").join(format!("{name}.yaml"))
+}
+
+/// Path of the file recording the last failed download of the URL.
+fn failure_path(url: &str) -> PathBuf {
+    cache_path(url).with_extension("failed")
+}
+
+/// Whether the file was written within the refresh interval.
+fn is_fresh(path: &Path, remote: &Remote) -> bool {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < remote.refresh_interval)
+}
+
+#[cfg(feature = "remote")]
+/// Replace `${NAME}` references with the value of the environment variable.
+fn expand_env(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        result.push_str(&std::env::var(&rest[start + 2..start + len]).unwrap_or_default());
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Get the path of an up-to-date copy of the remote kubeconfig, downloading it if the cached
+/// copy is older than the refresh interval. When the download fails, the stale copy is used, and
+/// the download is not tried again before the refresh interval, so that listing the contexts, as
+/// the shell completions do, does not wait for an unreachable server every time.
+pub fn fetch(remote: &Remote, url: &str) -> Option<PathBuf> {
+    let path = cache_path(url);
+    if is_fresh(&path, remote) {
+        return Some(path);
+    }
+    if is_fresh(&failure_path(url), remote) {
+        return path.exists().then_some(path);
+    }
+
+    match refresh(remote, url) {
+        Ok(()) => Some(path),
+        Err(err) => {
+            eprintln!("Warning: could not download kubeconfig from {url}: {err:#}");
+            if path.exists() {
+                eprintln!("Warning: using the cached copy of {url}");
+                Some(path)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Download the remote kubeconfig, replacing its cached copy, as `kubie sync` does.
+pub fn refresh(remote: &Remote, url: &str) -> Result<()> {
+    let result = download(remote, url, &cache_path(url));
+    if result.is_err() {
+        // Recorded on a best effort basis, a missing record only makes the next download happen sooner.
+        let _ = std::fs::create_dir_all(state::paths::data_dir().join("remote"))
+            .and_then(|_| std::fs::write(failure_path(url), b""));
+    } else {
+        let _ = std::fs::remove_file(failure_path(url));
+    }
+    result
+}
+
+/// How long a download can take, so that an unresponsive server does not hang kubie.
+#[cfg(feature = "remote")]
+const DOWNLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[cfg(feature = "remote")]
+fn download(remote: &Remote, url: &str, path: &Path) -> Result<()> {
+    use std::env;
+    use std::fs::DirBuilder;
+    use std::io::Write;
+
+    use anyhow::{bail, Context};
+    use attohttpc::header::HeaderName;
+
+    use crate::kubeconfig::KubeConfig;
+
+    let mut request = attohttpc::get(url);
+    for (name, value) in &remote.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("Invalid header name {name}"))?;
+        request = request.try_header(name, expand_env(value))?;
+    }
+    if let Some(token_env) = &remote.token_env {
+        let token = env::var(token_env).with_context(|| format!("{token_env} variable is not set"))?;
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.timeout(DOWNLOAD_TIMEOUT).send()?;
+    if !response.is_success() {
+        bail!("server responded with {}", response.status());
+    }
+    let body = response.bytes()?;
+
+    // Never replace a working cached copy with something that is not a kubeconfig.
+    serde_yaml::from_slice::<KubeConfig>(&body).context("Downloaded file is not a valid kubeconfig")?;
+
+    let dir = path.parent().expect("cache path should have a parent dir");
+    DirBuilder::new().recursive(true).create(dir)?;
+    let mut temp_file = tempfile::NamedTempFile::new_in(dir)?;
+    crate::ioutil::set_private_permissions(temp_file.path())?;
+    temp_file.write_all(&body)?;
+    temp_file.persist(path)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "remote"))]
+fn download(_remote: &Remote, _url: &str, _path: &Path) -> Result<()> {
+    anyhow::bail!("kubie was built without support for remote kubeconfigs")
+}
+
+#[test]
+#[cfg(feature = "remote")]
+fn test_expand_env() {
+    std::env::set_var("KUBIE_TEST_EXPAND_ENV", "secret");
+    assert_eq!(expand_env("Bearer ${KUBIE_TEST_EXPAND_ENV}"), "Bearer secret");
+    assert_eq!(expand_env("${KUBIE_TEST_EXPAND_ENV_MISSING}-x"), "-x");
+    assert_eq!(expand_env("no ${closing"), "no ${closing");
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    panic::UnwindSafe,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::session::HistoryEntry;
+use crate::store::{self, Document};
+
+pub mod paths {
+    use std::path::{Path, PathBuf};
+
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KUBIE_DATA_DIR: PathBuf = {
+            let base_data_dir = dirs::data_local_dir().expect("Could not get local data dir");
+            base_data_dir.join("kubie")
+        };
+        static ref KUBIE_STATE_PATH: PathBuf = KUBIE_DATA_DIR.join("state.json");
+        static ref KUBIE_STATE_LOCK_PATH: PathBuf = crate::store::lock_path(&KUBIE_STATE_PATH);
+    }
+
+    #[inline]
+    pub fn data_dir() -> &'static Path {
+        &KUBIE_DATA_DIR
+    }
+
+    #[inline]
+    pub fn state() -> &'static Path {
+        &KUBIE_STATE_PATH
+    }
+
+    #[inline]
+    pub fn state_lock() -> &'static Path {
+        &KUBIE_STATE_LOCK_PATH
+    }
+
+    /// Lock file of a kubeconfig file, named after the hash of its absolute path. It lives in the data
+    /// directory to leave the directories of the kubeconfig files untouched.
+    pub fn kubeconfig_lock(path: &Path) -> PathBuf {
+        use sha2::{Digest, Sha256};
+
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let hash = Sha256::digest(path.to_string_lossy().as_bytes());
+        let name: String = hash[..8].iter().map(|b| format!("{b:02x}")).collect();
+        KUBIE_DATA_DIR.join("locks").join(format!("{name}.lock"))
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    /// The most recently entered context across all sessions.
+    pub last_context: Option<String>,
+
+    /// This map stores the last namespace in which a context was used, in order to restore the namespace
+    /// when the context is entered again.
+    ///
+    /// The key represents the name of the context and the value is the namespace's name.
+    pub namespace_history: HashMap<String, Option<String>>,
+
+    /// The namespaces of each context the last time they were listed, shown by the namespace menu
+    /// of `kubie ctx` when the cluster cannot be reached.
+    #[serde(default)]
+    pub namespace_cache: HashMap<String, Vec<String>>,
+
+    /// Sessions saved with `kubie session save`, by name.
+    #[serde(default)]
+    pub saved_sessions: BTreeMap<String, SavedSession>,
+
+    /// Project files trusted by `kubie up`, by path, along with the SHA-256 digest of their contents
+    /// when they were trusted. A file whose contents changed must be trusted again.
+    #[serde(default)]
+    pub trusted_projects: BTreeMap<String, String>,
+
+    /// The context and namespace switches of every shell, the oldest first, shown by `kubie history`.
+    #[serde(default)]
+    pub switches: Vec<Switch>,
+
+    /// The Kubernetes version of the server of each context, shown by the banner printed when entering it.
+    #[serde(default)]
+    pub server_versions: HashMap<String, ServerVersion>,
+}
+
+/// Number of switches kept in the state, the oldest ones are forgotten.
+const MAX_SWITCHES: usize = 1000;
+
+/// A switch to a context and namespace with `kubie ctx` or `kubie ns`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Switch {
+    /// Time of the switch, in RFC 3339 format.
+    pub timestamp: String,
+    pub context: String,
+    pub namespace: Option<String>,
+}
+
+/// The version of the server of a context, along with when it was asked.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerVersion {
+    pub version: String,
+    /// Time at which the server was asked its version, in RFC 3339 format.
+    pub timestamp: String,
+}
+
+/// A snapshot of the contexts used in a kubie shell, which can be entered again with `kubie session load`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SavedSession {
+    /// Contexts and their namespace, the most recently used first.
+    pub contexts: Vec<HistoryEntry>,
+
+    /// Environment variables given to the shells spawned when loading the session.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Remember a switch to the context and namespace, for `kubie history`.
+    pub fn record_switch(&mut self, context: &str, namespace: Option<&str>) {
+        let timestamp = OffsetDateTime::now_utc()
+            .replace_nanosecond(0)
+            .ok()
+            .and_then(|time| time.format(&Rfc3339).ok())
+            .unwrap_or_default();
+        self.switches.push(Switch {
+            timestamp,
+            context: context.into(),
+            namespace: namespace.map(Into::into),
+        });
+        let excess = self.switches.len().saturating_sub(MAX_SWITCHES);
+        self.switches.drain(..excess);
+    }
+
+    /// Loads the state.json from the filesystem. It is always replaced at once, so it can be read while
+    /// other kubie processes modify it.
+    pub fn load() -> Result<State> {
+        store::load(paths::state())
+    }
+
+    /// Takes a closure that allows for modifications of the state. Automatically handles
+    /// locking/unlocking and saving after execution of the closure.
+    pub fn modify<F: FnOnce(&mut State) -> Result<()> + UnwindSafe>(func: F) -> Result<()> {
+        store::modify(paths::state(), func)
+    }
+}
+
+impl Document for State {
+    // Version 1 only adds the version to the unversioned state.
+    const VERSION: u32 = 1;
+}
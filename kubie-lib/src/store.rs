@@ -0,0 +1,161 @@
+use std::fs::DirBuilder;
+use std::io::{BufWriter, Write};
+use std::panic::UnwindSafe;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::ioutil;
+
+/// Field of the stored documents holding the version of their schema.
+const VERSION_FIELD: &str = "version";
+
+/// A JSON document persisted by kubie, such as the state or the session of a shell. Every document has a
+/// version, stored along with its fields, so that the documents written by older versions of kubie can be
+/// migrated when they are loaded.
+pub trait Document: Default + Serialize + DeserializeOwned {
+    /// Version of the schema of the document written by this version of kubie. The documents written before
+    /// the documents were versioned have version 0.
+    const VERSION: u32;
+
+    /// Upgrade a document from the given version to the next one.
+    fn migrate(_from: u32, _document: &mut Value) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Lock file of a document, next to it. The lock is only taken to modify the document: reading it does not
+/// need the lock, since it is always replaced at once.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let name = path.file_name().expect("path has no file name").to_string_lossy();
+    path.with_file_name(format!(".{name}.lock"))
+}
+
+/// Load a document, migrating it to the current version. A missing document is the default one.
+pub fn load<T: Document>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let document: Value = ioutil::read_json(path).with_context(|| format!("Could not read {}", path.display()))?;
+    from_value(document).with_context(|| format!("Could not load {}", path.display()))
+}
+
+/// Write a document with its version. The document is written to a temporary file which then replaces it,
+/// so that it is never seen half written, and only its owner can read it.
+pub fn save<T: Document>(path: &Path, document: &T) -> Result<()> {
+    let dir = path.parent().expect("path has no parent");
+    DirBuilder::new().recursive(true).create(dir)?;
+
+    let name = path.file_name().expect("path has no file name").to_string_lossy();
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!(".{name}"))
+        .suffix(".tmp")
+        .tempfile_in(dir)
+        .with_context(|| format!("Could not create a temporary file in {}", dir.display()))?;
+    {
+        let mut writer = BufWriter::new(file.as_file_mut());
+        serde_json::to_writer(&mut writer, &to_value(document)?)?;
+        writer.flush()?;
+    }
+    file.as_file().sync_all()?;
+    // The temporary file is only readable by its owner.
+    file.persist(path)
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(())
+}
+
+/// Load a document, let `func` modify it and save it, holding the lock of the document meanwhile so that
+/// the changes of concurrent kubie processes are not lost.
+pub fn modify<T, R, F>(path: &Path, func: F) -> Result<R>
+where
+    T: Document,
+    F: FnOnce(&mut T) -> Result<R> + UnwindSafe,
+{
+    let dir = path.parent().expect("path has no parent");
+    DirBuilder::new()
+        .recursive(true)
+        .create(dir)
+        .with_context(|| format!("Could not create {}", dir.display()))?;
+
+    ioutil::file_lock(lock_path(path), || {
+        let mut document = load(path)?;
+        let result = func(&mut document)?;
+        save(path, &document)?;
+        Ok(result)
+    })
+}
+
+fn from_value<T: Document>(mut document: Value) -> Result<T> {
+    let version = match document.get(VERSION_FIELD) {
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .context("The version of the document is not a number")?,
+        None => 0,
+    };
+    if version > T::VERSION {
+        bail!(
+            "The document has version {}, it was written by a newer version of kubie which supports version {}",
+            version,
+            T::VERSION
+        );
+    }
+    for from in version..T::VERSION {
+        T::migrate(from, &mut document).with_context(|| format!("Could not migrate from version {from}"))?;
+    }
+    if let Value::Object(fields) = &mut document {
+        fields.remove(VERSION_FIELD);
+    }
+    Ok(serde_json::from_value(document)?)
+}
+
+fn to_value<T: Document>(document: &T) -> Result<Value> {
+    let mut value = serde_json::to_value(document)?;
+    match &mut value {
+        Value::Object(fields) => {
+            fields.insert(VERSION_FIELD.into(), T::VERSION.into());
+        }
+        _ => bail!("A document must be a JSON object"),
+    }
+    Ok(value)
+}
+
+#[test]
+fn test_migrate() {
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+    struct Favorites {
+        contexts: Vec<String>,
+    }
+
+    impl Document for Favorites {
+        const VERSION: u32 = 1;
+
+        fn migrate(from: u32, document: &mut Value) -> Result<()> {
+            // Version 0 stored a single context.
+            if from == 0 {
+                let context = document["context"].take();
+                *document = serde_json::json!({ "contexts": [context] });
+            }
+            Ok(())
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("favorites.json");
+    std::fs::write(&path, r#"{"context": "prod"}"#).unwrap();
+
+    modify(&path, |favorites: &mut Favorites| {
+        favorites.contexts.push("dev".into());
+        Ok(())
+    })
+    .unwrap();
+    let saved: Value = ioutil::read_json(&path).unwrap();
+    assert_eq!(saved, serde_json::json!({ "version": 1, "contexts": ["prod", "dev"] }));
+
+    std::fs::write(&path, r#"{"version": 2, "contexts": []}"#).unwrap();
+    assert!(load::<Favorites>(&path).is_err());
+}
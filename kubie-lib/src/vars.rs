@@ -28,3 +28,8 @@ pub fn ensure_kubie_active() -> Result<()> {
 pub fn get_session_path() -> Option<PathBuf> {
     env::var_os("KUBIE_SESSION").map(PathBuf::from)
 }
+
+/// Get the identifier of the current kubie shell.
+pub fn get_session_id() -> Option<String> {
+    env::var("KUBIE_SESSION_ID").ok()
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::settings::{expanduser, Audit};
+
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    timestamp: String,
+    user: String,
+    command: &'a str,
+    context: &'a str,
+    namespace: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<&'a [String]>,
+}
+
+/// Append an entry to the audit log, if one is configured. `args` is the command line run by `kubie exec`.
+pub fn record(
+    audit: &Audit,
+    command: &str,
+    context: &str,
+    namespace: Option<&str>,
+    args: Option<&[String]>,
+) -> Result<()> {
+    let Some(log_file) = &audit.log_file else {
+        return Ok(());
+    };
+    let path = expanduser(log_file);
+    let path = Path::new(&path);
+
+    let entry = Entry {
+        timestamp: OffsetDateTime::now_utc().format(&Rfc3339)?,
+        user: env::var("USER").unwrap_or_else(|_| "unknown".into()),
+        command,
+        context,
+        namespace,
+        args,
+    };
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        DirBuilder::new().recursive(true).create(parent)?;
+    }
+    // A single write in append mode keeps lines from concurrent kubie processes intact.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Could not write to audit log {}", path.display()))
+}
+
+/// An entry of the audit log, as read back by `kubie stats`.
+#[derive(Debug, Deserialize)]
+pub struct LoggedEntry {
+    pub timestamp: String,
+    pub command: String,
+    pub context: String,
+}
+
+/// The entries of the audit log, if one is configured. The lines which cannot be parsed are skipped.
+pub fn read(audit: &Audit) -> Result<Vec<LoggedEntry>> {
+    let Some(log_file) = &audit.log_file else {
+        return Ok(vec![]);
+    };
+    let path = expanduser(log_file);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).with_context(|| format!("Could not read audit log {path}")),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
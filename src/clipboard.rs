@@ -0,0 +1,17 @@
+//! The system clipboard, to share the current context with `kubie info --copy` and `kubie copy --clipboard`.
+
+use anyhow::Result;
+
+#[cfg(feature = "clipboard")]
+pub fn copy(text: &str) -> Result<()> {
+    use anyhow::Context;
+
+    let mut clipboard = arboard::Clipboard::new().context("Could not open the clipboard")?;
+    clipboard.set_text(text).context("Could not copy to the clipboard")?;
+    Ok(())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_text: &str) -> Result<()> {
+    anyhow::bail!("kubie was built without the clipboard feature")
+}
@@ -0,0 +1,564 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glob::{glob, Pattern};
+use serde::de::DeserializeOwned;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::settings::{expanduser, Settings};
+use crate::sources;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathPart {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of a value in the config file, such as `configs.include[0]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct KeyPath(Vec<PathPart>);
+
+impl KeyPath {
+    fn join(&self, part: PathPart) -> KeyPath {
+        let mut parts = self.0.clone();
+        parts.push(part);
+        KeyPath(parts)
+    }
+
+    fn key(&self, key: &str) -> KeyPath {
+        self.join(PathPart::Key(key.into()))
+    }
+
+    fn index(&self, index: usize) -> KeyPath {
+        self.join(PathPart::Index(index))
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.0.iter().enumerate() {
+            match part {
+                PathPart::Key(key) if i == 0 => write!(f, "{key}")?,
+                PathPart::Key(key) => write!(f, ".{key}")?,
+                PathPart::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A key of the config file which is not a setting, along with the closest setting.
+struct UnknownKey {
+    path: KeyPath,
+    suggestion: Option<&'static str>,
+}
+
+/// Deserializer of a YAML value which records the keys of the mappings that are not fields of the struct
+/// they are deserialized into. Serde skips them silently otherwise.
+struct Strict<'de> {
+    value: &'de Value,
+    path: KeyPath,
+    unknown: &'de RefCell<Vec<UnknownKey>>,
+}
+
+struct StrictMap<'de> {
+    iter: serde_yaml::mapping::Iter<'de>,
+    value: Option<(&'de Value, &'de Value)>,
+    path: KeyPath,
+    unknown: &'de RefCell<Vec<UnknownKey>>,
+}
+
+struct StrictSeq<'de> {
+    iter: std::iter::Enumerate<std::slice::Iter<'de, Value>>,
+    path: KeyPath,
+    unknown: &'de RefCell<Vec<UnknownKey>>,
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.value.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Strict<'de> {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Mapping(mapping) => visitor.visit_map(StrictMap {
+                iter: mapping.iter(),
+                value: None,
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            Value::Sequence(sequence) => visitor.visit_seq(StrictSeq {
+                iter: sequence.iter().enumerate(),
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if let Value::Mapping(mapping) = self.value {
+            for key in mapping.keys() {
+                let key = key_name(key);
+                if !fields.contains(&key.as_str()) {
+                    self.unknown.borrow_mut().push(UnknownKey {
+                        path: self.path.key(&key),
+                        suggestion: closest(&key, fields),
+                    });
+                }
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_unit deserialize_identifier
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        self.value.deserialize_unit_struct(name, visitor)
+    }
+}
+
+impl<'de> MapAccess<'de> for StrictMap<'de> {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let (key, value) = self.value.take().expect("value should be read after its key");
+        seed.deserialize(Strict {
+            value,
+            path: self.path.key(&key_name(key)),
+            unknown: self.unknown,
+        })
+    }
+}
+
+impl<'de> SeqAccess<'de> for StrictSeq<'de> {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((index, value)) => seed
+                .deserialize(Strict {
+                    value,
+                    path: self.path.index(index),
+                    unknown: self.unknown,
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => serde_yaml::to_string(key)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The field closest to an unknown key, when it is close enough to be a typo.
+fn closest(key: &str, fields: &'static [&'static str]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|field| (edit_distance(key, field), *field))
+        .filter(|(distance, field)| *distance <= 2.max(field.len() / 4))
+        .min()
+        .map(|(_, field)| field)
+}
+
+/// Line and column, starting at 1, of the value at the given path of a block-style YAML document.
+fn locate(text: &str, path: &KeyPath) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut start = 0;
+    let mut min_column = 0;
+    let mut position = None;
+
+    for part in &path.0 {
+        let mut found = None;
+        let mut block_column = None;
+        let mut items = 0;
+        for (number, line) in lines.iter().enumerate().skip(start) {
+            let mut content = line.trim_start();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            if let PathPart::Key(_) = part {
+                // The first key of an item is on the same line as its dash, the next items end the item.
+                if let Some(rest) = content.strip_prefix("- ") {
+                    if number != start {
+                        break;
+                    }
+                    content = rest;
+                }
+            }
+            let column = line.len() - content.len();
+            if column < min_column {
+                break;
+            }
+            if *block_column.get_or_insert(column) != column {
+                continue;
+            }
+            match part {
+                PathPart::Key(key) => {
+                    let name = content.split(':').next().unwrap_or("").trim().trim_matches(['"', '\'']);
+                    if name == key && content.contains(':') {
+                        found = Some((number, column));
+                        break;
+                    }
+                }
+                PathPart::Index(index) if content.starts_with('-') => {
+                    if items == *index {
+                        found = Some((number, column));
+                        break;
+                    }
+                    items += 1;
+                }
+                PathPart::Index(_) => {}
+            }
+        }
+
+        let (number, column) = found?;
+        position = Some((number + 1, column + 1));
+        match part {
+            PathPart::Index(_) => {
+                start = number;
+                min_column = column + 2;
+            }
+            PathPart::Key(_) => {
+                start = number + 1;
+                min_column = column + 1;
+            }
+        }
+    }
+    position
+}
+
+/// An issue of the config file, with its line and column when they are known.
+pub struct Problem {
+    pub error: bool,
+    position: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Problem {
+    /// The problem prefixed with the file and the position, like compilers print them.
+    pub fn describe(&self, path: &Path) -> String {
+        let severity = if self.error { "error" } else { "warning" };
+        match self.position {
+            Some((line, column)) => format!("{}:{}:{}: {}: {}", path.display(), line, column, severity, self.message),
+            None => format!("{}: {}: {}", path.display(), severity, self.message),
+        }
+    }
+
+    fn from_toml(err: toml::de::Error, text: &str) -> Problem {
+        let position = err.span().map(|span| {
+            let before = &text[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (
+                before.matches('\n').count() + 1,
+                before[line_start..].chars().count() + 1,
+            )
+        });
+        Problem {
+            error: true,
+            position,
+            message: err.message().to_string(),
+        }
+    }
+
+    fn from_yaml(err: serde_yaml::Error) -> Problem {
+        let position = err.location().map(|l| (l.line(), l.column()));
+        let mut message = err.to_string();
+        // The position is printed before the message instead.
+        if let Some((line, column)) = position {
+            message = message.replacen(&format!(" at line {line} column {column}"), "", 1);
+        }
+        Problem {
+            error: true,
+            position,
+            message,
+        }
+    }
+}
+
+fn check_globs(
+    position: &dyn Fn(&KeyPath) -> Option<(usize, usize)>,
+    settings: &Settings,
+    problems: &mut Vec<Problem>,
+) {
+    let lists = [
+        ("include", &settings.configs.include),
+        ("exclude", &settings.configs.exclude),
+    ];
+    for (name, patterns) in lists {
+        let list_path = KeyPath::default().key("configs").key(name);
+        for (index, pattern) in patterns.iter().enumerate() {
+            if sources::remote::is_remote(pattern) || pattern == "$KUBECONFIG" {
+                continue;
+            }
+            let expanded = expanduser(pattern);
+            if let Err(err) = Pattern::new(&expanded) {
+                problems.push(Problem {
+                    error: true,
+                    position: position(&list_path.index(index)),
+                    message: format!("invalid glob pattern '{pattern}': {err}"),
+                });
+                continue;
+            }
+            let matches = glob(&expanded).map(|paths| paths.count()).unwrap_or(0);
+            if name == "include" && matches == 0 {
+                problems.push(Problem {
+                    error: false,
+                    position: position(&list_path.index(index)),
+                    message: format!("'{pattern}' does not match any file"),
+                });
+            }
+        }
+    }
+}
+
+/// Parse the config file into `T`, in TOML or in YAML.
+fn parse<T: DeserializeOwned>(text: &str, toml: bool) -> Result<T, Problem> {
+    if toml {
+        toml::from_str(text).map_err(|err| Problem::from_toml(err, text))
+    } else {
+        serde_yaml::from_str(text).map_err(Problem::from_yaml)
+    }
+}
+
+/// Find the problems of the text of a config file, in TOML or in YAML.
+pub fn check(text: &str, toml: bool) -> Vec<Problem> {
+    let value: Value = match parse::<toml::Table>(text, true) {
+        Ok(table) if toml => serde_yaml::to_value(table).expect("toml should convert to yaml"),
+        Err(problem) if toml => return vec![problem],
+        _ => match parse(text, false) {
+            Ok(value) => value,
+            Err(problem) => return vec![problem],
+        },
+    };
+    // Keys are only located in YAML files.
+    let position = |path: &KeyPath| if toml { None } else { locate(text, path) };
+
+    let mut problems = vec![];
+    let unknown = RefCell::new(vec![]);
+    // Errors are reported below along with their position, which is only known when parsing the text.
+    let _ = Settings::deserialize(Strict {
+        value: &value,
+        path: KeyPath::default(),
+        unknown: &unknown,
+    });
+    for key in unknown.into_inner() {
+        let mut message = format!("unknown key '{}'", key.path);
+        if let Some(suggestion) = key.suggestion {
+            message.push_str(&format!(", did you mean '{suggestion}'?"));
+        }
+        problems.push(Problem {
+            error: true,
+            position: position(&key.path),
+            message,
+        });
+    }
+
+    match parse::<Settings>(text, toml) {
+        Ok(settings) => check_globs(&position, &settings, &mut problems),
+        Err(problem) => problems.push(problem),
+    }
+    problems
+}
+
+/// Validate the config files strictly, printing every problem with its position in the file. Without a
+/// file, every config file merged into the settings is checked.
+pub fn check_config(file: Option<&Path>) -> Result<()> {
+    let paths = match file {
+        Some(file) => vec![file.to_path_buf()],
+        None => Settings::layers(),
+    };
+    if paths.is_empty() {
+        println!("{} does not exist, the default settings are used", Settings::path());
+        return Ok(());
+    }
+
+    let mut valid = true;
+    for path in &paths {
+        let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+
+        let problems = check(&text, Settings::is_toml(path));
+        for problem in &problems {
+            eprintln!("{}", problem.describe(path));
+        }
+
+        if problems.iter().any(|p| p.error) {
+            valid = false;
+        } else {
+            println!("{} is valid", path.display());
+        }
+    }
+
+    if !valid {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_check() {
+    let messages = |text, toml| -> Vec<_> { check(text, toml).into_iter().map(|p| (p.message, p.position)).collect() };
+
+    let text = r#"
+behaviour:
+  validate_namespaces: false
+prompt:
+  disable: true
+  colors:
+    - context: prod
+      colour: red
+"#;
+    assert_eq!(
+        messages(text, false),
+        [
+            (
+                "unknown key 'behaviour', did you mean 'behavior'?".to_string(),
+                Some((2, 1))
+            ),
+            (
+                "unknown key 'prompt.colors[0].colour', did you mean 'color'?".to_string(),
+                Some((8, 7))
+            ),
+            ("prompt.colors[0]: missing field `color`".to_string(), Some((7, 7))),
+        ]
+    );
+
+    let text = r#"
+configs:
+  include:
+    - /kube/*.yaml
+    - "/kube/[.yaml"
+"#;
+    assert_eq!(
+        messages(text, false),
+        [
+            ("'/kube/*.yaml' does not match any file".to_string(), Some((4, 5))),
+            (
+                "invalid glob pattern '/kube/[.yaml': Pattern syntax error near position 6: invalid range pattern"
+                    .to_string(),
+                Some((5, 5))
+            ),
+        ]
+    );
+
+    let text = r#"
+[behaviour]
+validate_namespaces = false
+
+[fzf]
+height = 40
+"#;
+    assert_eq!(
+        messages(text, true),
+        [
+            ("unknown key 'behaviour', did you mean 'behavior'?".to_string(), None),
+            (
+                "invalid type: integer `40`, expected a string".to_string(),
+                Some((6, 10))
+            ),
+        ]
+    );
+}
@@ -1,25 +1,105 @@
-use anyhow::Result;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
 
-use crate::cmd::{select_or_list_context, SelectResult};
-use crate::kubeconfig::{self, Installed};
+use anyhow::{bail, Context, Result};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::audit;
+use crate::cmd::meta::ContextCommand;
+use crate::cmd::{
+    check_namespace_allowed, confirm_create_namespace, confirm_protected_context, fetch_namespaces, format_table,
+    impersonate_if_read_only, metadata_column, namespaces_or_cached, select_context_names, select_namespace,
+    SelectResult,
+};
+use crate::credentials;
+use crate::discovery;
+use crate::hooks::{self, HookEnv};
+use crate::kubeconfig::{self, Installed, KubeConfig};
 use crate::kubectl;
+use crate::login;
+#[cfg(feature = "oidc")]
+use crate::oidc;
+use crate::openshift;
+use crate::output::{self, note};
 use crate::session::Session;
 use crate::settings::Settings;
-use crate::shell::spawn_shell;
-use crate::state::State;
+use crate::shell::{eval, prompt, spawn_shell, update};
+use crate::state::{ServerVersion, State};
+use crate::tmux;
 use crate::vars;
+use crate::vault;
+
+/// How long the version of a server is remembered before asking the server again.
+const SERVER_VERSION_TTL: Duration = Duration::days(1);
+
+/// The version of the server of the context, asked to the server when the remembered one is too old. The
+/// remembered one is used when the server cannot be reached.
+fn server_version(settings: &Settings, kubeconfig: &KubeConfig) -> Option<String> {
+    let context_name = &kubeconfig.contexts[0].name;
+    let cached = State::load().ok()?.server_versions.remove(context_name);
+    let is_fresh = cached.as_ref().is_some_and(|cached| {
+        OffsetDateTime::parse(&cached.timestamp, &Rfc3339)
+            .is_ok_and(|timestamp| timestamp + SERVER_VERSION_TTL > OffsetDateTime::now_utc())
+    });
+    if is_fresh {
+        return cached.map(|cached| cached.version);
+    }
+
+    let timeout = settings.behavior.namespace_lookup_timeout();
+    let kubectl = settings.configured_kubectl_for(context_name);
+    match kubectl::server_version(kubeconfig, kubectl.as_deref(), timeout) {
+        Ok(version) => {
+            let entry = ServerVersion {
+                version: version.clone(),
+                timestamp: OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
+            };
+            let context_name = context_name.clone();
+            let _ = State::modify(move |state| {
+                state.server_versions.insert(context_name, entry);
+                Ok(())
+            });
+            Some(version)
+        }
+        Err(_) => cached.map(|cached| cached.version),
+    }
+}
+
+/// The banner printed when entering a context, with the server of its cluster and its metadata.
+fn banner(kubeconfig: &KubeConfig, version: Option<&str>, metadata: &BTreeMap<&str, &str>) -> String {
+    let context = &kubeconfig.contexts[0];
+    let server = kubeconfig
+        .clusters
+        .first()
+        .and_then(|cluster| cluster.cluster.get("server"))
+        .and_then(|server| server.as_str())
+        .unwrap_or("unknown");
+    let mut text = format!("Context:   {}\n", context.name);
+    text.push_str(&format!("Server:    {}\n", server));
+    text.push_str(&format!("Version:   {}\n", version.unwrap_or("unknown")));
+    text.push_str(&format!(
+        "Namespace: {}\n",
+        context.context.namespace.as_deref().unwrap_or("default")
+    ));
+    for (key, value) in metadata {
+        text.push_str(&format!("{:<11}{}\n", format!("{key}:"), value));
+    }
+    text
+}
 
 fn enter_context(
     settings: &Settings,
     installed: Installed,
     context_name: &str,
     namespace_name: Option<&str>,
-    recursive: bool,
+    cmd: &ContextCommand,
 ) -> Result<()> {
     let state = State::load()?;
     let mut session = Session::load()?;
 
-    let kubeconfig = if context_name == "-" {
+    let mut kubeconfig = if context_name == "-" {
         if let Some(previous) = session.get_last_context() {
             // Inside a kubie shell: switch to the previous context from session history
             let ns = namespace_name.or(previous.namespace.as_deref());
@@ -36,6 +116,43 @@ fn enter_context(
         installed.make_kubeconfig_for_context(context_name, ns)?
     };
 
+    if let Some(namespace_name) = namespace_name {
+        check_namespace_allowed(settings, &kubeconfig.contexts[0].name, namespace_name)?;
+    }
+    confirm_protected_context(settings, &kubeconfig.contexts[0].name, cmd.yes)?;
+    vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+
+    impersonate_if_read_only(settings, &mut kubeconfig, cmd.read_only)?;
+
+    let in_place = vars::is_kubie_active() && (!cmd.recursive || settings.behavior.reuse_session);
+
+    if !cmd.no_shell {
+        login::ensure_credentials(settings, &kubeconfig)?;
+        openshift::ensure_login(settings, &installed, &mut kubeconfig)?;
+    }
+    #[cfg(feature = "oidc")]
+    oidc::wrap(&settings.oidc, &mut kubeconfig);
+    credentials::wrap_exec(&settings.credential_cache, &mut kubeconfig);
+
+    if !settings.hooks.pre_ctx.is_empty() {
+        let mut env = HookEnv::current(&kubeconfig);
+        if !in_place && cmd.eval.is_none() && !cmd.no_shell {
+            // The shell does not exist yet.
+            env.depth += 1;
+            env.kubeconfig = None;
+            env.session_id = None;
+        }
+        hooks::run_check("pre_ctx", &settings.hooks.pre_ctx, &env)?;
+    }
+
+    audit::record(
+        &settings.audit,
+        "ctx",
+        &kubeconfig.contexts[0].name,
+        kubeconfig.contexts[0].context.namespace.as_deref(),
+        None,
+    )?;
+
     session.record_context_entry(
         &kubeconfig.contexts[0].name,
         kubeconfig.contexts[0].context.namespace.as_deref(),
@@ -43,44 +160,206 @@ fn enter_context(
 
     if settings.behavior.validate_namespaces.can_list_namespaces() {
         if let Some(namespace_name) = namespace_name {
-            let namespaces = kubectl::get_namespaces(Some(&kubeconfig))?;
-            if !namespaces.iter().any(|x| x == namespace_name) {
-                eprintln!("Warning: namespace {namespace_name} does not exist.");
+            match namespaces_or_cached(settings, Some(&kubeconfig)) {
+                Ok(namespaces) if !namespaces.iter().any(|x| x == namespace_name) => {
+                    if confirm_create_namespace(namespace_name, cmd.create)? {
+                        let timeout = settings.behavior.namespace_lookup_timeout();
+                        let kubectl = settings.configured_kubectl_for(&kubeconfig.contexts[0].name);
+                        let projects = openshift::is_openshift(settings, &kubeconfig);
+                        kubectl::create_namespace(
+                            Some(&kubeconfig),
+                            kubectl.as_deref(),
+                            namespace_name,
+                            projects,
+                            timeout,
+                        )?;
+                        note!("Created namespace {namespace_name}");
+                    } else {
+                        eprintln!("Warning: namespace {namespace_name} does not exist.");
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("Warning: could not validate the namespace: {err}"),
             }
         }
     }
 
-    if vars::is_kubie_active() && !recursive {
+    if !cmd.no_shell && !output::quiet() && settings.behavior.should_print_context_banner() {
+        let version = server_version(settings, &kubeconfig);
+        let metadata = settings.metadata_for(&kubeconfig.contexts[0].name);
+        eprint!("{}", banner(&kubeconfig, version.as_deref(), &metadata));
+    }
+
+    if cmd.no_shell {
+        match &cmd.output {
+            Some(path) => kubeconfig.write_to_file(path)?,
+            None => print!("{}", serde_yaml::to_string(&kubeconfig)?),
+        }
+    } else if in_place {
         let path = kubeconfig::get_kubeconfig_path()?;
         kubeconfig.write_to_file(path.as_path())?;
+        session.set_prompt(prompt::text(settings, &kubeconfig, vars::get_depth()));
         session.save(None)?;
+        if let Err(err) = update::write(settings, &kubeconfig) {
+            eprintln!("Warning: could not update the variables of the shell: {err:#}");
+        }
+        if let Some(dir) = env::var_os(kubectl::SHIM_DIR_VAR).filter(|dir| !dir.is_empty()) {
+            let kubectl = settings.kubectl_for(&kubeconfig.contexts[0].name);
+            if let Err(err) = kubectl::write_shim(Path::new(&dir), &kubectl) {
+                eprintln!("Warning: could not update the kubectl shim: {err:#}");
+            }
+        }
+        tmux::rename_window(
+            settings,
+            &kubeconfig.contexts[0].name,
+            kubeconfig.contexts[0].context.namespace.as_deref(),
+        );
+    } else if let Some(shell) = cmd.eval {
+        eval::print_eval(settings, &kubeconfig, &mut session, shell)?;
     } else {
-        spawn_shell(settings, kubeconfig, &session)?;
+        spawn_shell(settings, kubeconfig, &mut session)?;
     }
 
     Ok(())
 }
 
-pub fn context(
-    settings: &Settings,
-    context_name: Option<String>,
-    namespace_name: Option<String>,
-    kubeconfigs: Vec<String>,
-    recursive: bool,
-) -> Result<()> {
-    let mut installed = if kubeconfigs.is_empty() {
+pub fn context(settings: &Settings, cmd: ContextCommand) -> Result<()> {
+    let mut installed = if cmd.kubeconfigs.is_empty() {
         kubeconfig::get_installed_contexts(settings)?
     } else {
-        kubeconfig::get_kubeconfigs_contexts(&kubeconfigs)?
+        kubeconfig::get_kubeconfigs_contexts(&cmd.kubeconfigs)?
+    };
+    if cmd.verbose {
+        for file in &installed.skipped {
+            eprintln!("Skipped {}: {}", file.path.display(), file.reason);
+        }
+    }
+
+    if cmd.list || cmd.wide {
+        let mut names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        if !cmd.wide {
+            for name in names {
+                println!("{name}");
+            }
+            return Ok(());
+        }
+
+        let header = ["NAME", "SERVER", "USER", "SOURCE", "METADATA"].map(String::from);
+        let rows: Vec<[String; 5]> = std::iter::once(header)
+            .chain(names.into_iter().map(|name| {
+                let [server, user, source] = installed.context_columns(name).unwrap_or_default();
+                [name.into(), server, user, source, metadata_column(settings, name)]
+            }))
+            .collect();
+        for line in format_table(&rows) {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    // Clusters from discovery backends are only offered when using the installed contexts, in the menu or
+    // when no installed context has the given name, since the backends run their CLI tools.
+    let discover = cmd.kubeconfigs.is_empty()
+        && match &cmd.context_name {
+            Some(name) => name != "-" && !installed.contexts.iter().any(|c| &c.item.name == name),
+            None => true,
+        };
+    let discovered = if discover {
+        discovery::discover(settings)
+    } else {
+        vec![]
+    };
+
+    let picked = cmd.context_name.is_none();
+    let menu = cmd.eval.is_some() || io::stdout().is_terminal();
+    let context_name = match &cmd.context_name {
+        Some(context_name) => context_name.clone(),
+        // The output of --eval is evaluated by the shell, a list cannot be printed. The menu is drawn on stderr.
+        None if cmd.eval.is_some() && !io::stderr().is_terminal() => bail!("A context name is required with --eval"),
+        None if cmd.no_shell => bail!("A context name is required with --no-shell"),
+        None => {
+            let mut names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.clone()).collect();
+            names.extend(discovered.iter().map(|c| c.context_name.clone()));
+            match select_context_names(settings, names, Some(&installed), menu)? {
+                SelectResult::Selected(x) => x,
+                _ => return Ok(()),
+            }
+        }
     };
 
-    let context_name = match context_name {
-        Some(context_name) => context_name,
-        None => match select_or_list_context(&settings.fzf, &mut installed)? {
-            SelectResult::Selected(x) => x,
+    // Logging in refreshes the credentials of a context picked in the menu, or creates it if it did not exist yet.
+    if let Some(cluster) = discovered.iter().find(|c| c.context_name == context_name) {
+        discovery::login(settings, cluster)?;
+        installed = kubeconfig::get_installed_contexts(settings)?;
+    }
+
+    let mut namespace_name = cmd.namespace_name.clone();
+    if picked && namespace_name.is_none() && settings.behavior.prompt_for_namespace {
+        let namespaces = namespaces_of(settings, &installed, &context_name)?;
+        match select_namespace(settings, Some(namespaces), menu)? {
+            SelectResult::Selected(x) => namespace_name = Some(x),
             _ => return Ok(()),
-        },
+        }
+    }
+
+    enter_context(settings, installed, &context_name, namespace_name.as_deref(), &cmd)
+}
+
+/// Enter the context used last by any shell, in the namespace it was last used with.
+pub fn last_context(settings: &Settings, yes: bool) -> Result<()> {
+    let state = State::load()?;
+    let context_name = state
+        .last_context
+        .context("No context was entered yet, use `kubie ctx` to enter one")?;
+    let cmd = ContextCommand {
+        context_name: Some(context_name),
+        yes,
+        ..Default::default()
     };
+    context(settings, cmd)
+}
+
+/// List the namespaces of the context, falling back to the namespaces it had the last time they were
+/// listed when the cluster cannot be reached.
+pub fn namespaces_of(settings: &Settings, installed: &Installed, context_name: &str) -> Result<Vec<String>> {
+    let mut kubeconfig = installed.make_kubeconfig_for_context(context_name, None::<&str>)?;
+    vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+    namespaces_or_cached(settings, Some(&kubeconfig))
+}
+
+/// List the namespaces of the context and remember them in the namespace cache. The namespaces which the
+/// settings do not allow in the context are left out.
+pub fn fetch_context_namespaces(settings: &Settings, installed: &Installed, context_name: &str) -> Result<Vec<String>> {
+    let mut kubeconfig = installed.make_kubeconfig_for_context(context_name, None::<&str>)?;
+    vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+    let mut namespaces = fetch_namespaces(settings, Some(&kubeconfig))?;
+    namespaces.retain(|ns| settings.is_namespace_allowed(context_name, ns));
+    Ok(namespaces)
+}
 
-    enter_context(settings, installed, &context_name, namespace_name.as_deref(), recursive)
+#[test]
+fn test_banner() {
+    let kubeconfig: KubeConfig = serde_yaml::from_str(
+        r#"
+clusters:
+  - name: prod
+    cluster:
+      server: https://prod.example.com
+users: []
+contexts:
+  - name: prod
+    context:
+      cluster: prod
+      user: admin
+      namespace: payments
+"#,
+    )
+    .unwrap();
+    let metadata = BTreeMap::from([("owner", "team-payments")]);
+    assert_eq!(
+        banner(&kubeconfig, Some("v1.29.4"), &metadata),
+        "Context:   prod\nServer:    https://prod.example.com\nVersion:   v1.29.4\nNamespace: payments\nowner:     team-payments\n"
+    );
 }
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::clipboard;
+use crate::kubeconfig;
+use crate::output::note;
+use crate::settings::Settings;
+use crate::vars;
+
+pub fn copy(
+    settings: &Settings,
+    context_name: Option<String>,
+    output: Option<PathBuf>,
+    to_clipboard: bool,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    // The current shell's namespace is kept when its context is copied.
+    let (context_name, namespace) = match context_name {
+        Some(context_name) => {
+            let context = installed
+                .find_context_by_name(&context_name)
+                .ok_or_else(|| anyhow!("Could not find context {}", context_name))?;
+            let namespace = context.item.context.namespace.clone();
+            (context_name, namespace)
+        }
+        None => {
+            vars::ensure_kubie_active()?;
+            let conf = kubeconfig::get_current_config()?;
+            let context_name = conf.current_context.clone().unwrap_or_default();
+            (context_name, conf.contexts[0].context.namespace.clone())
+        }
+    };
+
+    let mut kubeconfig = installed.make_kubeconfig_for_context(&context_name, namespace)?;
+    kubeconfig.embed_files()?;
+
+    match output {
+        _ if to_clipboard => {
+            clipboard::copy(&serde_yaml::to_string(&kubeconfig)?)?;
+            note!("Copied the kubeconfig of context {context_name} to the clipboard");
+        }
+        Some(path) if path.as_os_str() != "-" => kubeconfig.write_to_file(&path)?,
+        _ => print!("{}", serde_yaml::to_string(&kubeconfig)?),
+    }
+
+    Ok(())
+}
@@ -1,19 +1,48 @@
-use anyhow::Result;
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Result};
 
-use crate::cmd::{select_or_list_context, SelectResult};
 use crate::kubeconfig;
 use crate::settings::Settings;
 
-pub fn delete_context(settings: &Settings, context_name: Option<String>) -> Result<()> {
-    let mut installed = kubeconfig::get_installed_contexts(settings)?;
+pub fn delete_contexts(
+    settings: &Settings,
+    mut context_names: Vec<String>,
+    prune_orphans: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+
+    if context_names.is_empty() {
+        let mut names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.clone()).collect();
+        names.sort();
+        names.dedup();
+        if names.is_empty() {
+            bail!("No contexts found");
+        }
+
+        if !io::stdout().is_terminal() {
+            for name in names {
+                println!("{name}");
+            }
+            return Ok(());
+        }
 
-    let context_name = match context_name {
-        Some(context_name) => context_name,
-        None => match select_or_list_context(&settings.fzf, &mut installed)? {
-            SelectResult::Selected(x) => x,
-            _ => return Ok(()),
-        },
-    };
+        // NOTE: skim shows the list of context names in reverse order
+        let items = names
+            .into_iter()
+            .rev()
+            .map(|name| crate::skim::MenuItem {
+                text: settings.prompt.display_name(&name),
+                value: name,
+                preview: None,
+            })
+            .collect();
+        context_names = crate::skim::select_multi(&settings.fzf.for_contexts(), items)?;
+        if context_names.is_empty() {
+            return Ok(());
+        }
+    }
 
-    installed.delete_context(&context_name)
+    installed.delete_contexts(&context_names, prune_orphans, dry_run)
 }
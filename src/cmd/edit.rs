@@ -1,12 +1,17 @@
 use std::env;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use which::which;
 
-use crate::cmd::{select_or_list_context, SelectResult};
+use crate::cmd::check_config;
+use crate::cmd::lint;
+use crate::cmd::{confirm, select_or_list_context, SelectResult};
+use crate::ephemeral;
 use crate::kubeconfig;
 use crate::settings::Settings;
 
@@ -36,13 +41,11 @@ fn get_editor(settings: &Settings) -> Result<EditorCommand> {
         .default_editor
         .as_deref()
         .map(|editor| {
-            parse_editor_command(editor)
-                .with_context(|| format!("unable to parse default_editor command {}", editor))
+            parse_editor_command(editor).with_context(|| format!("unable to parse default_editor command {}", editor))
         })
         .or_else(|| {
             env::var("EDITOR").ok().map(|editor| {
-                parse_editor_command(&editor)
-                    .with_context(|| format!("unable to parse EDITOR command {}", editor))
+                parse_editor_command(&editor).with_context(|| format!("unable to parse EDITOR command {}", editor))
             })
         })
         .transpose()?
@@ -63,7 +66,7 @@ pub fn edit_context(settings: &Settings, context_name: Option<String>) -> Result
 
     let context_name = match context_name {
         Some(context_name) => context_name,
-        None => match select_or_list_context(&settings.fzf, &mut installed)? {
+        None => match select_or_list_context(settings, &mut installed)? {
             SelectResult::Selected(x) => x,
             _ => return Ok(()),
         },
@@ -74,27 +77,173 @@ pub fn edit_context(settings: &Settings, context_name: Option<String>) -> Result
         .ok_or_else(|| anyhow!("Could not find context {}", context_name))?;
 
     let command = get_editor(settings)?;
+    let _lock = kubeconfig::lock_files([context_src.source.as_path()])?;
 
-    let mut job = Command::new(&command.executable)
+    // sops decrypts the file into a temporary file, opens $EDITOR on it and encrypts it back.
+    if kubeconfig::is_encrypted_file(&context_src.source)? {
+        let sops = EditorCommand {
+            executable: which("sops").context("File is encrypted with sops but sops is not installed")?,
+            args: vec![],
+        };
+        return run_editor(&sops, &context_src.source, Some(command.to_string()));
+    }
+
+    edit_kubeconfig(&command, &context_src.source)
+}
+
+fn run_editor(command: &EditorCommand, path: &Path, env_editor: Option<String>) -> Result<()> {
+    let mut cmd = Command::new(&command.executable);
+    if let Some(editor) = env_editor {
+        cmd.env("EDITOR", editor);
+    }
+    let mut job = cmd
         .args(&command.args)
-        .arg(context_src.source.as_ref())
+        .arg(path)
         .spawn()
         .context(format!("Failed to spawn editor command '{}'", command))?;
     job.wait()?;
+    Ok(())
+}
+
+const ERROR_PREFIX: &str = "# kubie: ";
+
+/// Append the errors to the text as comments, to show them in the editor. They come last for the line
+/// numbers of the errors to stay right.
+fn with_errors(text: &str, errors: &[String]) -> String {
+    let mut output = text.to_string();
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&format!(
+        "{ERROR_PREFIX}The file is invalid, fix it or empty it to cancel.\n"
+    ));
+    for line in errors.iter().flat_map(|error| error.lines()) {
+        output.push_str(&format!("{ERROR_PREFIX}{line}\n"));
+    }
+    output
+}
+
+/// Remove the error comments added by `with_errors`.
+fn without_errors(text: &str) -> String {
+    let mut lines: Vec<_> = text.split_inclusive('\n').collect();
+    while lines.last().is_some_and(|line| line.starts_with(ERROR_PREFIX)) {
+        lines.pop();
+    }
+    lines.concat()
+}
+
+/// Open the editor on a copy of `original` until `validate` returns no error for it, like `kubectl edit`
+/// does. The editor is opened again with the errors at the end of the file. Returns the edited text, or
+/// nothing when it was not changed.
+fn edit_until_valid(
+    command: &EditorCommand,
+    original: &str,
+    suffix: &str,
+    mut validate: impl FnMut(&Path) -> Vec<String>,
+) -> Result<Option<String>> {
+    let temp = ephemeral::tempfile("edit", suffix)?;
+    fs::write(temp.path(), original)?;
+
+    let mut previous = original.to_string();
+    loop {
+        run_editor(command, temp.path(), None)?;
+        let edited = without_errors(&fs::read_to_string(temp.path())?);
+        if edited == original {
+            return Ok(None);
+        }
+        if edited.trim().is_empty() {
+            bail!("Edit cancelled, the file is empty");
+        }
+
+        fs::write(temp.path(), &edited)?;
+        let errors = validate(temp.path());
+        if errors.is_empty() {
+            return Ok(Some(edited));
+        }
+        for error in &errors {
+            eprintln!("{error}");
+        }
+        if edited == previous {
+            bail!("Edit cancelled, the file is still invalid");
+        }
+        fs::write(temp.path(), with_errors(&edited, &errors))?;
+        previous = edited;
+    }
+}
 
+/// Edit a copy of the kubeconfig, which replaces the original once it parses and the user accepted its diff.
+fn edit_kubeconfig(command: &EditorCommand, source: &Path) -> Result<()> {
+    let original = fs::read_to_string(source).with_context(|| format!("Could not read {}", source.display()))?;
+    let edited = edit_until_valid(command, &original, ".yaml", |path| {
+        match kubeconfig::read_kubeconfig(path) {
+            Ok(kubeconfig) => {
+                for message in lint::lint_kubeconfig(&kubeconfig, source) {
+                    println!("{message}");
+                }
+                vec![]
+            }
+            Err(err) => vec![format!("The kubeconfig could not be parsed: {err:#}")],
+        }
+    })?;
+    let Some(edited) = edited else {
+        println!("No changes to {}.", source.display());
+        return Ok(());
+    };
+
+    println!("--- {}", source.display());
+    println!("+++ {}", source.display());
+    for line in lint::diff_lines(&original, &edited) {
+        println!("{line}");
+    }
+    if !confirm(&format!("Save the changes to {}?", source.display()))? {
+        println!("The changes were discarded.");
+        return Ok(());
+    }
+    fs::write(source, edited).with_context(|| format!("Could not write {}", source.display()))?;
     Ok(())
 }
 
-pub fn edit_config(settings: &Settings) -> Result<()> {
-    let command = get_editor(settings)?;
-    let settings_path = Settings::path();
+/// Edit a copy of kubie's config file, which replaces the original once `check-config` finds no error in it.
+/// It runs before the settings are loaded, to fix a config file which cannot be loaded.
+pub fn edit_config() -> Result<()> {
+    let settings = Settings::load().unwrap_or_default();
+    let command = get_editor(&settings)?;
+    let path = PathBuf::from(Settings::path());
+    let toml = Settings::is_toml(&path);
 
-    let mut job = Command::new(&command.executable)
-        .args(&command.args)
-        .arg(settings_path)
-        .spawn()
-        .context(format!("Failed to spawn editor command '{}'", command))?;
-    job.wait()?;
+    let original = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("Could not read {}", path.display())),
+    };
+    let suffix = if toml { ".toml" } else { ".yaml" };
+    let edited = edit_until_valid(&command, &original, suffix, |temp| {
+        let text = fs::read_to_string(temp).unwrap_or_default();
+        // The warnings, such as the include globs matching no file, are left to `check-config`.
+        let errors = check_config::check(&text, toml)
+            .into_iter()
+            .filter(|problem| problem.error);
+        errors.map(|error| error.describe(&path)).collect()
+    })?;
+    let Some(edited) = edited else {
+        println!("No changes to {}.", path.display());
+        return Ok(());
+    };
 
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, edited).with_context(|| format!("Could not write {}", path.display()))?;
+    println!("{} is valid and was saved.", path.display());
     Ok(())
 }
+
+#[test]
+fn test_without_errors() {
+    let text = "clusters: []\n# comment\n";
+    let edited = with_errors(text, &["error at line 2\ninvalid type".into()]);
+    assert!(edited.starts_with(text));
+    assert_eq!(without_errors(&edited), text);
+    assert_eq!(without_errors(text), text);
+    assert_eq!(without_errors(&with_errors("shell: bash", &[])), "shell: bash\n");
+}
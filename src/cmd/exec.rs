@@ -1,88 +1,820 @@
-use std::process::Command;
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
-use signal_hook::consts::signal::*;
-use signal_hook::iterator::Signals;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+#[cfg(unix)]
+use signal_hook::{consts::signal::*, iterator::Signals};
+use wildmatch::WildMatch;
 
-use crate::kubeconfig::{self, KubeConfig};
-use crate::settings::{ContextHeaderBehavior, Settings};
+use crate::audit;
+use crate::cmd::meta::{ExecCommand, ExecOutput};
+use crate::cmd::{check_namespace_allowed, confirm_protected_context, impersonate_if_read_only, namespaces_or_cached};
+use crate::credentials;
+use crate::ephemeral;
+use crate::kubeconfig::{self, Installed, KubeConfig, NamedContext, Sourced};
+use crate::login;
+use crate::notify;
+#[cfg(feature = "oidc")]
+use crate::oidc;
+use crate::openshift;
+use crate::output::{self, note};
+use crate::settings::Settings;
+use crate::shell;
 use crate::vars;
+use crate::vault;
 
-fn run_in_context(kubeconfig: &KubeConfig, args: &[String]) -> anyhow::Result<i32> {
-    let temp_config_file = tempfile::Builder::new()
-        .prefix("kubie-config")
-        .suffix(".yaml")
-        .tempfile()?;
-    kubeconfig.write_to_file(temp_config_file.path())?;
+/// Process ids of the commands currently running, which receive the signals sent to kubie. The commands
+/// with their own process group are stored with a negative id, so that the whole group receives them.
+type Children = Arc<Mutex<Vec<i64>>>;
 
-    let depth = vars::get_depth();
-    let next_depth = depth + 1;
+/// Colors of the output prefixes in parallel mode, so that the lines of each context stand out.
+const PREFIX_COLORS: [u8; 6] = [36, 32, 33, 35, 34, 31];
 
-    let mut signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT, SIGWINCH, SIGUSR1, SIGUSR2])
-        .expect("could not install signal handler");
+/// Kubeconfig of a context which the command runs in, logged in and impersonating the read-only user of
+/// read-only contexts.
+fn context_kubeconfig(
+    settings: &Settings,
+    installed: &Installed,
+    context_name: &str,
+    namespace_name: &str,
+) -> Result<KubeConfig> {
+    let mut kubeconfig = installed.make_kubeconfig_for_context(context_name, Some(namespace_name))?;
+    vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+    impersonate_if_read_only(settings, &mut kubeconfig, false)?;
+    login::ensure_credentials(settings, &kubeconfig)?;
+    openshift::ensure_login(settings, installed, &mut kubeconfig)?;
+    #[cfg(feature = "oidc")]
+    oidc::wrap(&settings.oidc, &mut kubeconfig);
+    credentials::wrap_exec(&settings.credential_cache, &mut kubeconfig);
+    Ok(kubeconfig)
+}
 
-    let mut child = Command::new(&args[0])
-        .args(&args[1..])
-        .env("KUBECONFIG", temp_config_file.path())
-        .env("KUBIE_KUBECONFIG", temp_config_file.path())
+fn context_command(settings: &Settings, kubeconfig: &KubeConfig, config_path: &Path, args: &[String]) -> Command {
+    let next_depth = vars::get_depth() + 1;
+
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(&args[1..])
+        .envs(settings.env_for(&kubeconfig.contexts[0].name))
+        .env("KUBECONFIG", config_path)
+        .env("KUBIE_KUBECONFIG", config_path)
         .env("KUBIE_ACTIVE", "1")
-        .env("KUBIE_DEPTH", next_depth.to_string())
-        .spawn()?;
+        .env("KUBIE_DEPTH", next_depth.to_string());
+    cmd
+}
+
+/// How the command is run in every context.
+struct RunOptions {
+    /// Number of times a failed command is run again.
+    retries: u32,
+    retry_delay: Duration,
+    /// The command is killed when it runs longer than this.
+    timeout: Option<Duration>,
+    /// Contents given to the command as its stdin, instead of the stdin of kubie.
+    stdin: Option<Vec<u8>>,
+}
+
+/// Exit code of a command killed because it ran longer than the timeout, the same as with the timeout
+/// command.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Run the command until it succeeds or has been retried `options.retries` times.
+fn with_retries<T>(
+    options: &RunOptions,
+    context_name: &str,
+    mut run: impl FnMut() -> anyhow::Result<(i32, T)>,
+) -> anyhow::Result<(i32, T)> {
+    let mut attempt = 0;
+    loop {
+        let (code, output) = run()?;
+        if code == 0 || attempt >= options.retries {
+            return Ok((code, output));
+        }
+        attempt += 1;
+        let retries = options.retries;
+        note!("Command failed in context {context_name} with exit code {code}, retrying ({attempt}/{retries})");
+        thread::sleep(options.retry_delay);
+    }
+}
+
+/// Spawn the command, writing the given contents to its stdin from another thread.
+fn spawn_with_stdin(mut cmd: Command, stdin: Option<&[u8]>) -> io::Result<Child> {
+    let Some(contents) = stdin else {
+        return cmd.spawn();
+    };
+    let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+    let mut pipe = child.stdin.take().expect("stdin is piped");
+    let contents = contents.to_vec();
+    // The command may exit without reading all of it.
+    thread::spawn(move || pipe.write_all(&contents));
+    Ok(child)
+}
+
+/// Spawn the command with its output piped. On unix, it gets its own process group so that the processes
+/// it starts are killed with it on timeout.
+fn spawn_piped(mut cmd: Command, stdin: Option<&[u8]>) -> io::Result<Child> {
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    spawn_with_stdin(cmd, stdin)
+}
 
-    let child_pid = child.id();
+/// Wait for the command to exit and return its exit code. The command is killed when it runs longer than
+/// the timeout, along with its process group when `group` is set.
+fn wait(
+    child: &mut Child,
+    group: bool,
+    context_name: &str,
+    timeout: Option<Duration>,
+    children: &Children,
+) -> io::Result<i32> {
+    let id = if group {
+        -i64::from(child.id())
+    } else {
+        i64::from(child.id())
+    };
+    children.lock().unwrap().push(id);
+    let status = wait_timeout(child, group, timeout);
+    children.lock().unwrap().retain(|&pid| pid != id);
 
-    thread::spawn(move || {
+    match status? {
+        Some(status) => Ok(status.code().unwrap_or(0)),
+        None => {
+            let timeout = timeout.unwrap_or_default().as_secs();
+            eprintln!("Command timed out after {timeout}s in context {context_name}");
+            Ok(TIMEOUT_EXIT_CODE)
+        }
+    }
+}
+
+fn wait_timeout(child: &mut Child, group: bool, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            #[cfg(unix)]
+            if group {
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            #[cfg(not(unix))]
+            let _ = group;
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn run_in_context(
+    settings: &Settings,
+    kubeconfig: &KubeConfig,
+    args: &[String],
+    options: &RunOptions,
+    children: &Children,
+) -> anyhow::Result<i32> {
+    let temp_config_file = ephemeral::tempfile("config", ".yaml")?;
+    kubeconfig.write_to_file(temp_config_file.path())?;
+
+    let context_name = &kubeconfig.contexts[0].name;
+    let (code, ()) = with_retries(options, context_name, || {
+        let cmd = context_command(settings, kubeconfig, temp_config_file.path(), args);
+        let mut child = spawn_with_stdin(cmd, options.stdin.as_deref())?;
+        Ok((wait(&mut child, false, context_name, options.timeout, children)?, ()))
+    })?;
+    Ok(code)
+}
+
+/// Run the command with its output piped, writing every line of it with the given prefix.
+fn run_prefixed(
+    settings: &Settings,
+    kubeconfig: &KubeConfig,
+    args: &[String],
+    prefix: &str,
+    options: &RunOptions,
+    children: &Children,
+) -> anyhow::Result<i32> {
+    let temp_config_file = ephemeral::tempfile("config", ".yaml")?;
+    kubeconfig.write_to_file(temp_config_file.path())?;
+
+    let context_name = &kubeconfig.contexts[0].name;
+    let (code, ()) = with_retries(options, context_name, || {
+        let mut child = spawn_piped(
+            context_command(settings, kubeconfig, temp_config_file.path(), args),
+            options.stdin.as_deref(),
+        )?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let code = thread::scope(|s| {
+            s.spawn(|| write_prefixed(stdout, prefix, io::stdout()));
+            s.spawn(|| write_prefixed(stderr, prefix, io::stderr()));
+            wait(&mut child, cfg!(unix), context_name, options.timeout, children)
+        })?;
+        Ok((code, ()))
+    })?;
+    Ok(code)
+}
+
+/// Stdout and stderr of a command.
+type Captured = (Vec<u8>, Vec<u8>);
+
+/// Run the command with its output captured.
+fn run_captured(
+    settings: &Settings,
+    kubeconfig: &KubeConfig,
+    args: &[String],
+    options: &RunOptions,
+    children: &Children,
+) -> anyhow::Result<(i32, Captured)> {
+    let temp_config_file = ephemeral::tempfile("config", ".yaml")?;
+    kubeconfig.write_to_file(temp_config_file.path())?;
+
+    let context_name = &kubeconfig.contexts[0].name;
+    with_retries(options, context_name, || {
+        let mut child = spawn_piped(
+            context_command(settings, kubeconfig, temp_config_file.path(), args),
+            options.stdin.as_deref(),
+        )?;
+
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let (mut out, mut err) = (vec![], vec![]);
+        let code = thread::scope(|s| {
+            s.spawn(|| stdout.read_to_end(&mut out));
+            s.spawn(|| stderr.read_to_end(&mut err));
+            wait(&mut child, cfg!(unix), context_name, options.timeout, children)
+        })?;
+        Ok((code, (out, err)))
+    })
+}
+
+/// Result of the command in a context, printed with `--output json`.
+#[derive(Clone, Serialize)]
+struct ContextOutput<'a> {
+    context: &'a str,
+    namespace: &'a str,
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+/// Copy the lines of `reader` to `out`. Every line is written at once so that the lines of commands
+/// running at the same time do not get mixed up.
+fn write_prefixed(reader: impl Read, prefix: &str, mut out: impl Write) {
+    for line in BufReader::new(reader).split(b'\n') {
+        let Ok(line) = line else { break };
+        let mut buf = Vec::with_capacity(prefix.len() + line.len() + 2);
+        buf.extend_from_slice(prefix.as_bytes());
+        buf.push(b' ');
+        buf.extend_from_slice(&line);
+        buf.push(b'\n');
+        let _ = out.write_all(&buf);
+    }
+}
+
+/// Forward the signals received by kubie to the running commands. On Windows, console events already
+/// reach every process attached to the console.
+#[cfg(unix)]
+fn forward_signals(children: Children) {
+    let mut signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT, SIGWINCH, SIGUSR1, SIGUSR2])
+        .expect("could not install signal handler");
+    std::thread::spawn(move || {
         for sig in signals.forever() {
-            unsafe {
-                libc::kill(child_pid as libc::pid_t, sig as libc::c_int);
+            for &pid in children.lock().unwrap().iter() {
+                unsafe {
+                    libc::kill(pid as libc::pid_t, sig as libc::c_int);
+                }
             }
         }
     });
+}
 
-    let status = child.wait()?;
+/// Names of the contexts listed one per line, without the empty lines and the comments.
+fn parse_context_list(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
 
-    Ok(status.code().unwrap_or(0))
+/// Contexts listed in a file, or in the stdin of kubie with `-`, in the order of the file.
+fn listed_contexts<'a>(installed: &'a Installed, path: &Path) -> Result<Vec<&'a Sourced<NamedContext>>> {
+    let text = if path.as_os_str() == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?
+    };
+
+    let mut contexts: Vec<&Sourced<NamedContext>> = vec![];
+    let mut unknown = vec![];
+    for name in parse_context_list(&text) {
+        match installed.find_context_by_name(name) {
+            Some(_) if contexts.iter().any(|c| c.item.name == name) => {}
+            Some(context) => contexts.push(context),
+            None => unknown.push(name),
+        }
+    }
+    if !unknown.is_empty() {
+        bail!("Unknown contexts in {}: {}", path.display(), unknown.join(", "));
+    }
+    Ok(contexts)
 }
 
-pub fn exec(
-    settings: &Settings,
-    context_name: String,
-    namespace_name: String,
-    exit_early: bool,
-    context_headers_flag: Option<ContextHeaderBehavior>,
-    args: Vec<String>,
-) -> Result<()> {
+pub fn exec(settings: &Settings, cmd: ExecCommand) -> Result<()> {
+    let ExecCommand {
+        context_name,
+        namespace_name,
+        dry_run,
+        pick,
+        exit_early,
+        keep_going,
+        summary,
+        notify,
+        context_headers_flag,
+        yes,
+        output,
+        parallel,
+        retries,
+        retry_delay,
+        timeout,
+        interactive,
+        stdin_file,
+        contexts_from,
+        args,
+    } = cmd;
+
+    // Without a context argument, the positional arguments start with the namespace.
+    let (context_name, namespace_name, args) = match &contexts_from {
+        Some(path) => {
+            let args = std::iter::once(namespace_name).chain(args).collect();
+            (path.display().to_string(), context_name, args)
+        }
+        None => (context_name, namespace_name, args),
+    };
+    let stdin = Some(Path::new("-"));
+    if contexts_from.as_deref() == stdin && stdin_file.as_deref() == stdin {
+        bail!("--contexts-from and --stdin-file cannot both read the stdin of kubie");
+    }
+
+    let mut options = RunOptions {
+        retries,
+        retry_delay: Duration::from_secs(retry_delay),
+        timeout: timeout.map(Duration::from_secs),
+        stdin: None,
+    };
+
     if args.is_empty() {
         return Ok(());
     }
 
     let installed = kubeconfig::get_installed_contexts(settings)?;
-    let matching = installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns);
+    let mut matching = match &contexts_from {
+        Some(path) => listed_contexts(&installed, path)?,
+        None => installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns),
+    };
 
     if matching.is_empty() {
         return Err(anyhow!("No context matching {}", context_name));
     }
 
-    let print_context = context_headers_flag
-        .as_ref()
-        .unwrap_or(&settings.behavior.print_context_in_exec)
-        .should_print_headers();
+    if pick {
+        if !io::stdin().is_terminal() {
+            bail!("--pick requires a terminal");
+        }
+        // NOTE: skim shows the list of context names in reverse order
+        let items = matching
+            .iter()
+            .rev()
+            .map(|c| crate::skim::MenuItem {
+                value: c.item.name.clone(),
+                text: settings.prompt.display_name(&c.item.name),
+                preview: None,
+            })
+            .collect();
+        let picked = crate::skim::select_multi(&settings.fzf.for_contexts(), items)?;
+        matching.retain(|c| picked.contains(&c.item.name));
+        if matching.is_empty() {
+            return Ok(());
+        }
+    }
 
-    for context_src in matching {
-        if print_context {
-            println!("CONTEXT => {}", context_src.item.name);
+    let targets = expand_namespaces(settings, &installed, &matching, &namespace_name)?;
+    if targets.is_empty() {
+        bail!("No namespace matching {} in the contexts", namespace_name);
+    }
+
+    if interactive && targets.len() > 1 {
+        bail!(
+            "--interactive requires a single context and namespace, {} match {} {}",
+            targets.len(),
+            context_name,
+            namespace_name
+        );
+    }
+
+    if dry_run {
+        print_dry_run(settings, &targets, &args);
+        return Ok(());
+    }
+
+    if let Some(path) = &stdin_file {
+        let contents = if path.as_os_str() == "-" {
+            let mut contents = vec![];
+            io::stdin().read_to_end(&mut contents)?;
+            contents
+        } else {
+            fs::read(path).with_context(|| format!("Could not read {}", path.display()))?
+        };
+        options.stdin = Some(contents);
+    }
+
+    let print_context = !interactive
+        && !output::quiet()
+        && context_headers_flag
+            .as_ref()
+            .unwrap_or(&settings.behavior.print_context_in_exec)
+            .should_print_headers();
+
+    for context_src in &matching {
+        confirm_protected_context(settings, &context_src.item.name, yes)?;
+    }
+
+    let children = Children::default();
+    #[cfg(unix)]
+    forward_signals(children.clone());
+
+    let codes = if parallel > 1 || output != ExecOutput::Text {
+        let mut kubeconfigs = vec![];
+        let target_args: Vec<Vec<String>> = targets.iter().map(|t| t.args(&args)).collect();
+        for (target, args) in targets.iter().zip(&target_args) {
+            let kubeconfig = context_kubeconfig(settings, &installed, &target.context.item.name, &target.namespace)?;
+            audit::record(
+                &settings.audit,
+                "exec",
+                &target.context.item.name,
+                Some(&target.namespace),
+                Some(args),
+            )?;
+            kubeconfigs.push(kubeconfig);
+        }
+
+        // Sourced contexts are not Sync, borrow only their names in the threads.
+        let names: Vec<(&str, &str)> = targets
+            .iter()
+            .map(|t| (t.context.item.name.as_str(), t.namespace.as_str()))
+            .collect();
+
+        if output == ExecOutput::Text {
+            let color = io::stdout().is_terminal() && output::color();
+            run_parallel(&kubeconfigs, parallel, exit_early, |index, kubeconfig| {
+                let label = format!("[{}/{}]", settings.prompt.display_name(names[index].0), names[index].1);
+                let prefix = if color {
+                    format!("\x1b[{}m{}\x1b[0m", PREFIX_COLORS[index % PREFIX_COLORS.len()], label)
+                } else {
+                    label
+                };
+                match run_prefixed(settings, kubeconfig, &target_args[index], &prefix, &options, &children) {
+                    Ok(code) => code,
+                    Err(err) => {
+                        eprintln!("{prefix} Error: {err}");
+                        1
+                    }
+                }
+            })
+        } else {
+            let outputs = Mutex::new(vec![None; kubeconfigs.len()]);
+            let codes = run_parallel(&kubeconfigs, parallel, exit_early, |index, kubeconfig| {
+                let args = &target_args[index];
+                let (exit_code, (stdout, stderr)) = match run_captured(settings, kubeconfig, args, &options, &children)
+                {
+                    Ok(captured) => captured,
+                    Err(err) => (1, (vec![], format!("Error: {err}").into_bytes())),
+                };
+                let result = ContextOutput {
+                    context: names[index].0,
+                    namespace: names[index].1,
+                    exit_code,
+                    stdout: String::from_utf8_lossy(&stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&stderr).into_owned(),
+                };
+                if output == ExecOutput::Json {
+                    let json = serde_json::to_string(&result).expect("output is serializable");
+                    let _ = writeln!(io::stdout(), "{json}");
+                }
+                outputs.lock().unwrap()[index] = Some(result);
+                exit_code
+            });
+            if output == ExecOutput::JsonArray {
+                let outputs: Vec<_> = outputs.into_inner().unwrap().into_iter().flatten().collect();
+                println!("{}", serde_json::to_string_pretty(&outputs)?);
+            }
+            codes
         }
-        let kubeconfig = installed.make_kubeconfig_for_context(&context_src.item.name, Some(&namespace_name))?;
-        let return_code = run_in_context(&kubeconfig, &args)?;
-        if print_context {
-            println!("{}", "-".repeat(20));
+    } else {
+        let mut codes = vec![None; targets.len()];
+        for (index, target) in targets.iter().enumerate() {
+            if print_context {
+                println!("CONTEXT => {}", settings.prompt.display_name(&target.context.item.name));
+                if target.namespace != namespace_name {
+                    println!("NAMESPACE => {}", target.namespace);
+                }
+            }
+            let args = target.args(&args);
+            let kubeconfig = context_kubeconfig(settings, &installed, &target.context.item.name, &target.namespace)?;
+            audit::record(
+                &settings.audit,
+                "exec",
+                &target.context.item.name,
+                Some(&target.namespace),
+                Some(&args),
+            )?;
+            let return_code = run_in_context(settings, &kubeconfig, &args, &options, &children)?;
+            if print_context {
+                println!("{}", "-".repeat(20));
+            }
+            codes[index] = Some(return_code);
+
+            if return_code != 0 && exit_early {
+                break;
+            }
+        }
+        codes
+    };
+
+    if summary && !output::quiet() {
+        print_summary(settings, &targets, &codes);
+    }
+    if notify {
+        notify::send("kubie exec", &notification(&args, &codes));
+    }
+
+    // Without a policy, failures only change the exit code when failing early.
+    let first_failure = codes.iter().flatten().copied().find(|&code| code != 0);
+    match first_failure {
+        Some(code) if exit_early || keep_going || summary => std::process::exit(code),
+        _ => std::process::exit(0),
+    }
+}
+
+/// A context and one of its namespaces in which the command runs.
+struct Target<'a> {
+    context: &'a Sourced<NamedContext>,
+    namespace: String,
+}
+
+impl Target<'_> {
+    /// The arguments of the command in this context, with the `{context}` and `{namespace}` placeholders
+    /// replaced with the names of the context and of the namespace.
+    fn args(&self, args: &[String]) -> Vec<String> {
+        expand_placeholders(args, &self.context.item.name, &self.namespace)
+    }
+}
+
+fn expand_placeholders(args: &[String], context_name: &str, namespace_name: &str) -> Vec<String> {
+    args.iter()
+        .map(|arg| {
+            arg.replace("{context}", context_name)
+                .replace("{namespace}", namespace_name)
+        })
+        .collect()
+}
+
+/// Pair the contexts with the namespace. When the namespace is a wildcard, the contexts are paired with
+/// every namespace of their cluster matching it instead.
+fn expand_namespaces<'a>(
+    settings: &Settings,
+    installed: &Installed,
+    matching: &[&'a Sourced<NamedContext>],
+    namespace_name: &str,
+) -> Result<Vec<Target<'a>>> {
+    if !namespace_name.contains(['*', '?']) {
+        for context in matching {
+            check_namespace_allowed(settings, &context.item.name, namespace_name)?;
         }
+        return Ok(matching
+            .iter()
+            .map(|&context| Target {
+                context,
+                namespace: namespace_name.to_string(),
+            })
+            .collect());
+    }
 
-        if return_code != 0 && exit_early {
-            std::process::exit(return_code);
+    let pattern = WildMatch::new(namespace_name);
+    let mut targets = vec![];
+    for &context in matching {
+        let mut kubeconfig = installed.make_kubeconfig_for_context(&context.item.name, None::<&str>)?;
+        vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+        match namespaces_or_cached(settings, Some(&kubeconfig)) {
+            Ok(namespaces) => targets.extend(
+                namespaces
+                    .into_iter()
+                    .filter(|ns| pattern.matches(ns))
+                    .map(|namespace| Target { context, namespace }),
+            ),
+            Err(err) => eprintln!(
+                "Warning: could not list the namespaces of context {}, skipping it: {err}",
+                context.item.name
+            ),
         }
     }
+    Ok(targets)
+}
+
+/// Print what would be run in every context with `--dry-run`.
+fn print_dry_run(settings: &Settings, targets: &[Target], args: &[String]) {
+    let kubeconfig = ephemeral::dir().join("kubie-configXXXXXX.yaml");
+
+    for target in targets {
+        let command: Vec<String> = target
+            .args(args)
+            .iter()
+            .map(|arg| shell::quote_if_needed(arg))
+            .collect();
+        let mut env: Vec<String> = settings
+            .env_for(&target.context.item.name)
+            .into_iter()
+            .map(|(k, v)| format!("{k}={}", shell::quote_if_needed(v)))
+            .collect();
+        env.sort();
+        env.push("KUBIE_ACTIVE=1".to_string());
+        env.push(format!("KUBIE_DEPTH={}", vars::get_depth() + 1));
+
+        println!("CONTEXT => {}", target.context.item.name);
+        println!("  source:     {}", target.context.source.display());
+        println!("  namespace:  {}", target.namespace);
+        println!("  kubeconfig: {} (created when the command runs)", kubeconfig.display());
+        println!("  env:        {}", env.join(" "));
+        println!("  command:    {}", command.join(" "));
+    }
+}
+
+/// Print a table of the exit code of the command in every context to stderr.
+fn print_summary(settings: &Settings, targets: &[Target], codes: &[Option<i32>]) {
+    let names: Vec<_> = targets
+        .iter()
+        .map(|t| settings.prompt.display_name(&t.context.item.name))
+        .collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("CONTEXT".len());
+    let ns_width = targets
+        .iter()
+        .map(|t| t.namespace.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAMESPACE".len());
+    eprintln!();
+    eprintln!("{:width$}  {:ns_width$}  EXIT CODE", "CONTEXT", "NAMESPACE");
+    for ((target, name), code) in targets.iter().zip(names).zip(codes) {
+        let code = match code {
+            Some(code) => code.to_string(),
+            None => "skipped".to_string(),
+        };
+        eprintln!("{:width$}  {:ns_width$}  {code}", name, target.namespace);
+    }
+}
+
+/// Text of the notification sent with --notify, counting the contexts in which the command succeeded,
+/// failed, or was not run.
+fn notification(args: &[String], codes: &[Option<i32>]) -> String {
+    let succeeded = codes.iter().filter(|code| **code == Some(0)).count();
+    let failed = codes.iter().flatten().filter(|code| **code != 0).count();
+    let skipped = codes.iter().filter(|code| code.is_none()).count();
+    let mut text = format!("{}: succeeded in {succeeded}, failed in {failed}", args[0]);
+    if skipped > 0 {
+        text.push_str(&format!(", skipped {skipped}"));
+    }
+    text
+}
+
+/// Call `run` for up to `parallel` kubeconfigs at the same time, and return the exit code of the command
+/// in every context. With `exit_early`, no command is started after one fails.
+fn run_parallel(
+    kubeconfigs: &[KubeConfig],
+    parallel: usize,
+    exit_early: bool,
+    run: impl Fn(usize, &KubeConfig) -> i32 + Sync,
+) -> Vec<Option<i32>> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let codes = Mutex::new(vec![None; kubeconfigs.len()]);
+
+    thread::scope(|s| {
+        for _ in 0..parallel.min(kubeconfigs.len()) {
+            s.spawn(|| loop {
+                if exit_early && failed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(kubeconfig) = kubeconfigs.get(index) else {
+                    break;
+                };
+
+                let code = run(index, kubeconfig);
+                if code != 0 {
+                    failed.store(true, Ordering::SeqCst);
+                }
+                codes.lock().unwrap()[index] = Some(code);
+            });
+        }
+    });
+
+    codes.into_inner().unwrap()
+}
+
+#[test]
+fn test_notification() {
+    let args = ["kubectl".to_string(), "get".to_string()];
+    assert_eq!(
+        notification(&args, &[Some(0), Some(1), Some(0)]),
+        "kubectl: succeeded in 2, failed in 1"
+    );
+    assert_eq!(
+        notification(&args, &[Some(2), None, None]),
+        "kubectl: succeeded in 0, failed in 1, skipped 2"
+    );
+}
+
+#[test]
+fn test_parse_context_list() {
+    let text = "# generated by the inventory\nprod-eu\n\n  prod-us  \r\n";
+    assert_eq!(parse_context_list(text), ["prod-eu", "prod-us"]);
+}
+
+#[test]
+fn test_expand_placeholders() {
+    let args = ["sh", "-c", "echo {context}/{namespace} > report-{context}.txt"].map(String::from);
+    assert_eq!(
+        expand_placeholders(&args, "prod-eu", "default"),
+        ["sh", "-c", "echo prod-eu/default > report-prod-eu.txt"]
+    );
+}
+
+#[test]
+fn test_read_only_context_kubeconfig() {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use serde_yaml::Mapping;
+
+    use crate::kubeconfig::{Context, NamedCluster, NamedUser};
+
+    let file = Rc::new(PathBuf::from("/kube/config"));
+    let context = |name: &str| NamedContext {
+        name: name.into(),
+        context: Context {
+            cluster: "cluster".into(),
+            namespace: None,
+            user: "user".into(),
+        },
+        name_in_file: None,
+    };
+    let installed = Installed {
+        clusters: vec![Sourced::new(
+            &file,
+            NamedCluster {
+                name: "cluster".into(),
+                cluster: Mapping::new(),
+            },
+        )],
+        users: vec![Sourced::new(
+            &file,
+            NamedUser {
+                name: "user".into(),
+                user: Mapping::new(),
+            },
+        )],
+        contexts: vec![
+            Sourced::new(&file, context("prod")),
+            Sourced::new(&file, context("dev")),
+        ],
+        skipped: vec![],
+    };
+    let settings: Settings =
+        serde_yaml::from_str("behavior:\n  read_only_contexts: [prod]\n  read_only_user: viewer\n").unwrap();
 
-    std::process::exit(0);
+    let prod = context_kubeconfig(&settings, &installed, "prod", "default").unwrap();
+    assert_eq!(prod.users[0].user.get("as"), Some(&"viewer".into()));
+    let dev = context_kubeconfig(&settings, &installed, "dev", "default").unwrap();
+    assert!(!dev.is_impersonating());
 }
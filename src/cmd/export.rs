@@ -1,25 +1,81 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
+use crate::cmd::impersonate_if_read_only;
+use crate::cmd::meta::ExportCommand;
+use crate::credentials;
+use crate::ephemeral;
 use crate::kubeconfig;
+#[cfg(feature = "oidc")]
+use crate::oidc;
 use crate::settings::Settings;
+use crate::vault;
 
-pub fn export(settings: &Settings, context_name: String, namespace_name: String) -> Result<()> {
+pub fn export(settings: &Settings, cmd: ExportCommand) -> Result<()> {
     let installed = kubeconfig::get_installed_contexts(settings)?;
-    let matching = installed.get_contexts_matching(&context_name, settings.behavior.allow_multiple_context_patterns);
+    let matching =
+        installed.get_contexts_matching(&cmd.context_name, settings.behavior.allow_multiple_context_patterns);
 
     if matching.is_empty() {
-        return Err(anyhow!("No context matching {}", context_name));
+        return Err(anyhow!("No context matching {}", cmd.context_name));
     }
 
+    let mut kubeconfigs = vec![];
     for context_src in matching {
-        let kubeconfig = installed.make_kubeconfig_for_context(&context_src.item.name, Some(&namespace_name))?;
-        let temp_config_file = tempfile::Builder::new()
-            .prefix("kubie-config")
-            .suffix(".yaml")
-            .tempfile()?;
-        kubeconfig.write_to_file(temp_config_file.path())?;
-        let (_, path) = temp_config_file.keep()?;
-        println!("{}", path.display());
+        let mut kubeconfig =
+            installed.make_kubeconfig_for_context(&context_src.item.name, Some(&cmd.namespace_name))?;
+        vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+        impersonate_if_read_only(settings, &mut kubeconfig, false)?;
+        #[cfg(feature = "oidc")]
+        oidc::wrap(&settings.oidc, &mut kubeconfig);
+        credentials::wrap_exec(&settings.credential_cache, &mut kubeconfig);
+        kubeconfigs.push(kubeconfig);
+    }
+
+    if let Some(files) = &cmd.merge {
+        let mut merged = kubeconfigs.remove(0);
+        for kubeconfig in kubeconfigs {
+            merged.merge(kubeconfig)?;
+        }
+        for file in files {
+            let kubeconfig = kubeconfig::read_kubeconfig(file)
+                .with_context(|| format!("Could not read kubeconfig {}", file.display()))?;
+            merged.merge(kubeconfig)?;
+        }
+        kubeconfigs = vec![merged];
+    }
+
+    if let Some(namespace_name) = &cmd.set_namespace {
+        for context in kubeconfigs.iter_mut().flat_map(|kubeconfig| &mut kubeconfig.contexts) {
+            context.context.namespace = Some(namespace_name.clone());
+        }
+    }
+
+    match &cmd.output {
+        Some(path) => {
+            if kubeconfigs.len() > 1 {
+                bail!(
+                    "{} contexts match {}, use --merge to export them to a single kubeconfig",
+                    kubeconfigs.len(),
+                    cmd.context_name
+                );
+            }
+            if path.as_os_str() == "-" {
+                print!("{}", serde_yaml::to_string(&kubeconfigs[0])?);
+            } else {
+                kubeconfigs[0].write_to_file(path)?;
+            }
+        }
+        None => {
+            for kubeconfig in kubeconfigs {
+                let temp_config_file = tempfile::Builder::new()
+                    .prefix("kubie-config")
+                    .suffix(".yaml")
+                    .tempfile_in(ephemeral::dir())?;
+                kubeconfig.write_to_file(temp_config_file.path())?;
+                let (_, path) = temp_config_file.keep()?;
+                println!("{}", path.display());
+            }
+        }
     }
 
     std::process::exit(0);
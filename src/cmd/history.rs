@@ -0,0 +1,94 @@
+use anyhow::Result;
+
+use crate::cmd::format_table;
+use crate::kubeconfig;
+use crate::settings::Settings;
+use crate::state::{State, Switch};
+
+/// Print the most recent switches, the most recent first.
+fn list(settings: &Settings, switches: &[Switch], limit: usize) {
+    let mut rows = vec![["TIME", "CONTEXT", "NAMESPACE"].map(String::from)];
+    for switch in switches.iter().rev().take(limit) {
+        rows.push([
+            switch.timestamp.clone(),
+            settings.prompt.display_name(&switch.context),
+            switch.namespace.clone().unwrap_or_default(),
+        ]);
+    }
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+}
+
+/// The number of switches to each context and the time of the last one, the most used contexts first. The
+/// installed contexts which were never used come last.
+fn stats<'a>(switches: &'a [Switch], installed: &[&'a str]) -> Vec<(&'a str, usize, Option<&'a str>)> {
+    let mut stats: Vec<(&str, usize, Option<&str>)> = vec![];
+    for switch in switches {
+        match stats.iter_mut().find(|(context, _, _)| *context == switch.context) {
+            Some((_, count, last)) => {
+                *count += 1;
+                *last = Some(&switch.timestamp);
+            }
+            None => stats.push((&switch.context, 1, Some(&switch.timestamp))),
+        }
+    }
+    for name in installed {
+        if !stats.iter().any(|(context, _, _)| context == name) {
+            stats.push((name, 0, None));
+        }
+    }
+    stats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    stats
+}
+
+pub fn history(settings: &Settings, show_stats: bool, limit: usize) -> Result<()> {
+    let state = State::load()?;
+    if !show_stats {
+        if state.switches.is_empty() {
+            eprintln!("No context or namespace switches were recorded yet.");
+            return Ok(());
+        }
+        list(settings, &state.switches, limit);
+        return Ok(());
+    }
+
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.as_str()).collect();
+    let mut rows = vec![["CONTEXT", "SWITCHES", "LAST USED"].map(String::from)];
+    for (context, count, last) in stats(&state.switches, &names) {
+        rows.push([
+            settings.prompt.display_name(context),
+            count.to_string(),
+            last.unwrap_or("never").to_string(),
+        ]);
+    }
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_stats() {
+    let switch = |timestamp: &str, context: &str| Switch {
+        timestamp: timestamp.into(),
+        context: context.into(),
+        namespace: None,
+    };
+    let switches = [
+        switch("2024-05-01T10:00:00Z", "dev"),
+        switch("2024-05-01T11:00:00Z", "prod"),
+        switch("2024-05-01T12:00:00Z", "dev"),
+        switch("2024-05-01T13:00:00Z", "removed"),
+    ];
+    assert_eq!(
+        stats(&switches, &["dev", "prod", "unused"]),
+        [
+            ("dev", 2, Some("2024-05-01T12:00:00Z")),
+            ("prod", 1, Some("2024-05-01T11:00:00Z")),
+            ("removed", 1, Some("2024-05-01T13:00:00Z")),
+            ("unused", 0, None),
+        ]
+    );
+}
@@ -1,26 +1,82 @@
 use anyhow::Result;
 
+use crate::clipboard;
 use crate::cmd::meta::{KubieInfo, KubieInfoKind};
 use crate::kubeconfig;
+use crate::output::note;
+use crate::settings::Settings;
+use crate::shell::prompt::{self, Values};
 use crate::vars;
 
-pub fn info(info: KubieInfo) -> Result<()> {
-    match info.kind {
-        KubieInfoKind::Context => {
+pub fn info(settings: &Settings, info: KubieInfo) -> Result<()> {
+    let text = match info.kind {
+        None => {
             vars::ensure_kubie_active()?;
             let conf = kubeconfig::get_current_config()?;
-            println!("{}", conf.current_context.as_deref().unwrap_or(""));
+            format!(
+                "context: {}\nnamespace: {}",
+                conf.current_context.as_deref().unwrap_or(""),
+                conf.contexts[0].context.namespace.as_deref().unwrap_or("default")
+            )
         }
-        KubieInfoKind::Namespace => {
+        Some(KubieInfoKind::Context {
+            prompt,
+            color,
+            default_color,
+            color_name,
+        }) => {
             vars::ensure_kubie_active()?;
             let conf = kubeconfig::get_current_config()?;
-            println!("{}", conf.contexts[0].context.namespace.as_deref().unwrap_or("default"));
+            let name = conf.current_context.as_deref().unwrap_or("");
+            if color {
+                let color = settings.prompt.color_for(name).or(default_color);
+                match color {
+                    Some(color) if color_name => color.name().to_string(),
+                    Some(color) => color.code().to_string(),
+                    None if color_name => "default".to_string(),
+                    None => "0".to_string(),
+                }
+            } else if !prompt {
+                name.to_string()
+            } else if conf.is_impersonating() {
+                format!("{} (ro)", settings.prompt.display_name(name))
+            } else {
+                settings.prompt.display_name(name)
+            }
         }
-        KubieInfoKind::Depth => {
+        Some(KubieInfoKind::Namespace) => {
             vars::ensure_kubie_active()?;
-            println!("{}", vars::get_depth());
+            let conf = kubeconfig::get_current_config()?;
+            conf.contexts[0]
+                .context
+                .namespace
+                .clone()
+                .unwrap_or_else(|| "default".into())
+        }
+        Some(KubieInfoKind::Depth) => {
+            vars::ensure_kubie_active()?;
+            vars::get_depth().to_string()
+        }
+        Some(KubieInfoKind::Metadata { key }) => {
+            vars::ensure_kubie_active()?;
+            let conf = kubeconfig::get_current_config()?;
+            let name = conf.current_context.as_deref().unwrap_or("");
+            settings.metadata_for(name).get(key.as_str()).unwrap_or(&"").to_string()
+        }
+        Some(KubieInfoKind::Prompt) => {
+            vars::ensure_kubie_active()?;
+            let conf = kubeconfig::get_current_config()?;
+            let values = Values::new(settings, &conf, vars::get_depth());
+            print!("{}", prompt::render_current(settings, &values));
+            return Ok(());
         }
     };
 
+    if info.copy {
+        clipboard::copy(&text)?;
+        note!("Copied to the clipboard: {}", text.replace('\n', ", "));
+    } else {
+        println!("{text}");
+    }
     Ok(())
 }
@@ -0,0 +1,241 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::cmd::{confirm, read_answer};
+use crate::kubeconfig;
+use crate::settings::{expanduser, Configs, PromptIntegration, Settings};
+use crate::shell::{self, ShellKind};
+
+/// Ask a yes or no question. The default answer is taken with `yes`, or when stdin is not a terminal.
+fn ask(question: &str, default: bool, yes: bool) -> Result<bool> {
+    if yes || !io::stdin().is_terminal() {
+        return Ok(default);
+    }
+    if !default {
+        return confirm(question);
+    }
+    Ok(match read_answer(&format!("{question} [Y/n]"))?.as_str() {
+        "" => true,
+        answer => matches!(answer, "y" | "Y" | "yes"),
+    })
+}
+
+/// Configuration file of a shell, and its lines enabling the completions and `kubie hook`.
+struct RcFile {
+    path: PathBuf,
+    completion: &'static str,
+    hook: Option<&'static str>,
+}
+
+fn rc_file(shell: ShellKind) -> Option<RcFile> {
+    let (path, completion, hook) = match shell {
+        ShellKind::Bash => (
+            expanduser("~/.bashrc"),
+            "source <(kubie generate-completion bash)",
+            Some(r#"eval "$(kubie hook bash)""#),
+        ),
+        ShellKind::Zsh => (
+            match env::var("ZDOTDIR") {
+                Ok(dir) if !dir.is_empty() => format!("{dir}/.zshrc"),
+                _ => expanduser("~/.zshrc"),
+            },
+            "source <(kubie generate-completion zsh)",
+            Some(r#"eval "$(kubie hook zsh)""#),
+        ),
+        ShellKind::Fish => (
+            expanduser("~/.config/fish/config.fish"),
+            "kubie generate-completion fish | source",
+            Some("kubie hook fish | source"),
+        ),
+        ShellKind::Elvish => (
+            expanduser("~/.config/elvish/rc.elv"),
+            "eval (kubie generate-completion elvish | slurp)",
+            None,
+        ),
+        _ => return None,
+    };
+    Some(RcFile {
+        path: path.into(),
+        completion,
+        hook,
+    })
+}
+
+/// Append a line to a shell's configuration file, unless it already has it.
+fn append_line(path: &Path, line: &str) -> Result<()> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    if text.lines().any(|l| l.trim() == line) {
+        println!("{} already has: {line}", path.display());
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    let separator = if text.is_empty() || text.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    writeln!(file, "{separator}{line}")?;
+    println!("Added to {}: {line}", path.display());
+    Ok(())
+}
+
+/// Kubeconfig files found in the usual locations and in KUBECONFIG, with their number of contexts.
+fn scan_kubeconfigs() -> Vec<(PathBuf, usize)> {
+    let mut patterns = Configs::default().include;
+    patterns.push(expanduser("~/.kube/config.d/*"));
+    if let Some(paths) = env::var_os("KUBECONFIG") {
+        patterns.extend(env::split_paths(&paths).map(|path| path.display().to_string()));
+    }
+
+    let own = Settings::paths();
+    let mut found: Vec<(PathBuf, usize)> = vec![];
+    for pattern in patterns {
+        for path in glob::glob(&pattern).into_iter().flatten().flatten() {
+            if found.iter().any(|(p, _)| *p == path) || own.iter().any(|p| Path::new(p) == path) {
+                continue;
+            }
+            if let Ok(kubeconfig) = kubeconfig::read_kubeconfig(&path) {
+                found.push((path, kubeconfig.contexts.len()));
+            }
+        }
+    }
+    found
+}
+
+/// Text of the settings written by `kubie init`. Only the choices which differ from the defaults are set,
+/// the files outside of the default include paths being added to them.
+fn settings_text(extra_includes: &[PathBuf], integration: PromptIntegration) -> String {
+    let mut text = String::from(
+        "# Written by `kubie init`. Every setting is described in the README of kubie, and\n\
+         # `kubie edit-config` edits this file.\n",
+    );
+    if !extra_includes.is_empty() {
+        text.push_str("configs:\n    include:\n");
+        let extra = extra_includes.iter().map(|path| path.display().to_string());
+        for include in Configs::default().include.into_iter().chain(extra) {
+            text.push_str(&format!("        - {}\n", serde_json::Value::String(include)));
+        }
+    }
+    if integration == PromptIntegration::External {
+        text.push_str("prompt:\n    integration: external\n");
+    }
+    text
+}
+
+/// Detect the shell and the kubeconfig files, write the settings, and add the completions and the
+/// `kubie hook` function to the configuration file of the shell. Every question takes its default answer
+/// with `yes`, and an existing config file is replaced without asking with `force`.
+pub fn init(yes: bool, force: bool) -> Result<()> {
+    let shell = shell::detect().ok();
+    match shell {
+        Some(shell) => println!("Detected shell: {}", format!("{shell:?}").to_lowercase()),
+        None => println!("Could not detect the shell."),
+    }
+
+    let kubeconfigs = scan_kubeconfigs();
+    if kubeconfigs.is_empty() {
+        println!("No kubeconfig file was found in ~/.kube or KUBECONFIG.");
+    }
+    let defaults: Vec<glob::Pattern> = Configs::default()
+        .include
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+        .collect();
+    let mut extra_includes = vec![];
+    for (path, contexts) in kubeconfigs {
+        let included = defaults.iter().any(|pattern| pattern.matches_path(&path));
+        println!(
+            "Found {} with {contexts} context(s){}",
+            path.display(),
+            if included {
+                ""
+            } else {
+                ", outside of the default include paths"
+            }
+        );
+        if !included && ask(&format!("Include {}?", path.display()), true, yes)? {
+            extra_includes.push(path);
+        }
+    }
+
+    // Prompt frameworks such as starship show the context themselves.
+    let framework = env::var_os("STARSHIP_SHELL").is_some();
+    let integration = if ask(
+        "Does your prompt already show the Kubernetes context, e.g. with starship or powerlevel10k?",
+        framework,
+        yes,
+    )? {
+        PromptIntegration::External
+    } else {
+        PromptIntegration::Prepend
+    };
+
+    // The settings are written in YAML. An existing kubie.yml or kubie.toml is replaced by a kubie.yaml in the
+    // same directory, rather than being left behind it.
+    let existing = PathBuf::from(Settings::path());
+    let path = existing.with_extension("yaml");
+    if !existing.exists() || force || ask(&format!("{} exists, replace it?", existing.display()), false, yes)? {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, settings_text(&extra_includes, integration))
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+        if existing != path && existing.exists() {
+            fs::remove_file(&existing).with_context(|| format!("Could not remove {}", existing.display()))?;
+            println!("Removed {}", existing.display());
+        }
+    } else {
+        println!("Kept {}, `kubie init --force` replaces it", existing.display());
+    }
+
+    match shell.and_then(rc_file) {
+        Some(rc) => {
+            if ask(
+                "Enable the completion of kubie's commands, contexts and namespaces?",
+                true,
+                yes,
+            )? {
+                append_line(&rc.path, rc.completion)?;
+            }
+            if let Some(hook) = rc.hook {
+                if ask(
+                    "Switch contexts in the current shell, like kubectx, instead of spawning a shell?",
+                    false,
+                    yes,
+                )? {
+                    append_line(&rc.path, hook)?;
+                }
+            }
+        }
+        None => println!("See the Autocompletion section of kubie's README to enable the completions."),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_settings_text() {
+    let text = settings_text(&[], PromptIntegration::Prepend);
+    let settings: Settings = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(settings.configs.include, Configs::default().include);
+
+    let text = settings_text(
+        &[PathBuf::from("/work/kube configs/prod.conf")],
+        PromptIntegration::External,
+    );
+    let settings: Settings = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(settings.configs.include.last().unwrap(), "/work/kube configs/prod.conf");
+    assert_eq!(settings.configs.include.len(), Configs::default().include.len() + 1);
+    assert_eq!(settings.prompt.integration, PromptIntegration::External);
+}
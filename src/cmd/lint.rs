@@ -1,12 +1,91 @@
 use std::collections::HashSet;
-use std::path::Path;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::*;
+use serde::Serialize;
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use time::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
 
-use crate::kubeconfig::{self, Installed};
-use crate::settings::Settings;
+use crate::cmd::meta::LintOutput;
+use crate::cmd::{confirm, format_table};
+use crate::ioutil;
+use crate::kubeconfig::{self, Installed, KubeConfig, Sourced};
+use crate::kubectl;
+use crate::settings::{Conflict, Settings};
+use crate::vault;
 
-fn lint_clusters(installed: &Installed) {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// An issue found by the linter. The codes are stable so that CI pipelines can fail on some of them.
+#[derive(Debug, Serialize)]
+struct Issue {
+    code: &'static str,
+    severity: Severity,
+    message: String,
+    file: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+}
+
+impl Issue {
+    fn new(code: &'static str, severity: Severity, message: String, file: &Path) -> Issue {
+        Issue {
+            code,
+            severity,
+            message,
+            file: file.to_path_buf(),
+            context: None,
+        }
+    }
+
+    /// Whether the issue matches a `--fail-on` value, which is either a code or a minimum severity.
+    fn matches(&self, fail_on: &str) -> bool {
+        match fail_on {
+            "warning" => self.severity >= Severity::Warning,
+            "error" => self.severity >= Severity::Error,
+            code => self.code == code,
+        }
+    }
+}
+
+/// Report the files which were skipped because they could not be read or parsed. The files excluded on
+/// purpose are not reported.
+fn lint_skipped(installed: &Installed, issues: &mut Vec<Issue>) {
+    for file in installed.skipped.iter().filter(|file| file.invalid) {
+        issues.push(Issue::new(
+            "file-invalid",
+            Severity::Error,
+            format!("File {} {}", file.path.display(), file.reason),
+            &file.path,
+        ));
+    }
+}
+
+fn lint_clusters(installed: &Installed, issues: &mut Vec<Issue>) {
     let mut set: HashSet<(&str, &Path)> = HashSet::new();
 
     for cluster_src in &installed.clusters {
@@ -16,25 +95,35 @@ fn lint_clusters(installed: &Installed) {
             .find_contexts_by_cluster(&named.name, &cluster_src.source)
             .is_empty()
         {
-            println!(
-                "Cluster '{}' has no context referencing it in file {}",
-                named.name,
-                cluster_src.source.display(),
-            );
+            issues.push(Issue::new(
+                "cluster-unreferenced",
+                Severity::Warning,
+                format!(
+                    "Cluster '{}' has no context referencing it in file {}",
+                    named.name,
+                    cluster_src.source.display(),
+                ),
+                &cluster_src.source,
+            ));
         }
         if set.contains(&(&named.name, &cluster_src.source)) {
-            println!(
-                "A cluster named '{}' appears more than once in file {}",
-                named.name,
-                cluster_src.source.display(),
-            );
+            issues.push(Issue::new(
+                "cluster-duplicate",
+                Severity::Error,
+                format!(
+                    "A cluster named '{}' appears more than once in file {}",
+                    named.name,
+                    cluster_src.source.display(),
+                ),
+                &cluster_src.source,
+            ));
         } else {
             set.insert((&named.name, &cluster_src.source));
         }
     }
 }
 
-fn lint_users(installed: &Installed) {
+fn lint_users(installed: &Installed, issues: &mut Vec<Issue>) {
     let mut set: HashSet<(&str, &Path)> = HashSet::new();
 
     for user_src in &installed.users {
@@ -44,57 +133,81 @@ fn lint_users(installed: &Installed) {
             .find_contexts_by_user(&named.name, &user_src.source)
             .is_empty()
         {
-            println!(
-                "User '{}' has no context referencing it in file {}",
-                named.name,
-                user_src.source.display(),
-            );
+            issues.push(Issue::new(
+                "user-unreferenced",
+                Severity::Warning,
+                format!(
+                    "User '{}' has no context referencing it in file {}",
+                    named.name,
+                    user_src.source.display(),
+                ),
+                &user_src.source,
+            ));
         }
         if set.contains(&(&named.name, &user_src.source)) {
-            println!(
-                "A user named '{}' appears more than once in file {}",
-                named.name,
-                user_src.source.display(),
-            );
+            issues.push(Issue::new(
+                "user-duplicate",
+                Severity::Error,
+                format!(
+                    "A user named '{}' appears more than once in file {}",
+                    named.name,
+                    user_src.source.display(),
+                ),
+                &user_src.source,
+            ));
         } else {
             set.insert((&named.name, &user_src.source));
         }
     }
 }
 
-fn lint_contexts(installed: &Installed) {
+fn lint_contexts(installed: &Installed, issues: &mut Vec<Issue>) {
     let mut set = HashSet::new();
 
     for context_src in &installed.contexts {
         let named = &context_src.item;
+        let mut issue = |code, message| {
+            let mut issue = Issue::new(code, Severity::Error, message, &context_src.source);
+            issue.context = Some(named.name.clone());
+            issues.push(issue);
+        };
 
         if installed
             .find_cluster_by_name(&named.context.cluster, &context_src.source)
             .is_none()
         {
-            println!(
-                "Context '{}' references unknown cluster '{}' in file {}",
-                named.name,
-                named.context.cluster,
-                context_src.source.display(),
+            issue(
+                "context-unknown-cluster",
+                format!(
+                    "Context '{}' references unknown cluster '{}' in file {}",
+                    named.name,
+                    named.context.cluster,
+                    context_src.source.display(),
+                ),
             );
         }
         if installed
             .find_user_by_name(&named.context.user, &context_src.source)
             .is_none()
         {
-            println!(
-                "Context '{}' references unknown users '{}' in file {}",
-                named.name,
-                named.context.user,
-                context_src.source.display(),
+            issue(
+                "context-unknown-user",
+                format!(
+                    "Context '{}' references unknown users '{}' in file {}",
+                    named.name,
+                    named.context.user,
+                    context_src.source.display(),
+                ),
             );
         }
         if set.contains(&named.name) {
-            println!(
-                "A context name '{}' appears more than once in file {}",
-                named.name,
-                context_src.source.display()
+            issue(
+                "context-duplicate",
+                format!(
+                    "A context name '{}' appears more than once in file {}",
+                    named.name,
+                    context_src.source.display()
+                ),
             );
         } else {
             set.insert(&named.name);
@@ -102,10 +215,668 @@ fn lint_contexts(installed: &Installed) {
     }
 }
 
-pub fn lint(settings: &Settings) -> Result<()> {
-    let installed = kubeconfig::get_installed_contexts(settings)?;
-    lint_clusters(&installed);
-    lint_users(&installed);
-    lint_contexts(&installed);
+/// Files which define contexts, clusters or users, in the order in which they were loaded.
+fn sources(installed: &Installed) -> Vec<&Rc<PathBuf>> {
+    let mut sources: Vec<&Rc<PathBuf>> = vec![];
+    let all = installed
+        .contexts
+        .iter()
+        .map(|c| &c.source)
+        .chain(installed.clusters.iter().map(|c| &c.source))
+        .chain(installed.users.iter().map(|u| &u.source));
+    for source in all {
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+    sources
+}
+
+/// Whether a file can be read by other users than its owner. Kubeconfigs hold credentials.
+#[cfg(unix)]
+fn is_readable_by_others(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(fs::metadata(path)?.permissions().mode() & 0o077 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_readable_by_others(_path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+fn lint_permissions(installed: &Installed, issues: &mut Vec<Issue>) {
+    for source in sources(installed) {
+        if is_readable_by_others(source).unwrap_or(false) {
+            issues.push(Issue::new(
+                "file-permissions",
+                Severity::Warning,
+                format!(
+                    "File {} can be accessed by other users than its owner",
+                    source.display()
+                ),
+                source,
+            ));
+        }
+    }
+}
+
+/// Outcome of one of the online checks of a context, shown in a cell of the table.
+struct Check {
+    text: String,
+    /// Code and severity of the issue found by the check, if any.
+    issue: Option<(&'static str, Severity)>,
+}
+
+impl Check {
+    fn ok(text: impl Into<String>) -> Check {
+        Check {
+            text: text.into(),
+            issue: None,
+        }
+    }
+
+    fn issue(code: &'static str, severity: Severity, text: String) -> Check {
+        Check {
+            text,
+            issue: Some((code, severity)),
+        }
+    }
+}
+
+/// Number of contexts probed at the same time by `kubie lint --online`.
+const MAX_PROBES: usize = 8;
+
+/// Probe every context: whether its server answers, when its client certificate expires and whether its
+/// exec auth plugin is installed. The results are printed in a table when `table` is set.
+fn lint_online(
+    settings: &Settings,
+    installed: &Installed,
+    expiry_days: u32,
+    table: bool,
+    issues: &mut Vec<Issue>,
+) -> Result<()> {
+    let mut names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    let mut kubeconfigs = vec![];
+    for name in names {
+        let kubeconfig = installed
+            .make_kubeconfig_for_context(name, None::<&str>)
+            .and_then(|mut kubeconfig| {
+                vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+                Ok(kubeconfig)
+            });
+        kubeconfigs.push((name, kubeconfig));
+    }
+
+    let timeout = settings.behavior.namespace_lookup_timeout();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(kubeconfigs.iter().map(|_| None).collect::<Vec<_>>());
+    thread::scope(|s| {
+        for _ in 0..MAX_PROBES.min(kubeconfigs.len()) {
+            s.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some((name, kubeconfig)) = kubeconfigs.get(index) else {
+                    break;
+                };
+                let checks = probe(
+                    kubeconfig,
+                    settings.configured_kubectl_for(name).as_deref(),
+                    timeout,
+                    expiry_days,
+                );
+                results.lock().unwrap()[index] = Some(checks);
+            });
+        }
+    });
+    let results: Vec<[Check; 3]> = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|checks| checks.expect("every context is probed"))
+        .collect();
+
+    for ((name, _), checks) in kubeconfigs.iter().zip(&results) {
+        let source = &installed
+            .find_context_by_name(name)
+            .expect("context is installed")
+            .source;
+        for (label, check) in ["server", "client certificate", "exec plugin"].iter().zip(checks) {
+            if let Some((code, severity)) = check.issue {
+                let mut issue = Issue::new(
+                    code,
+                    severity,
+                    format!("Context '{name}' {label}: {}", check.text),
+                    source,
+                );
+                issue.context = Some(name.to_string());
+                issues.push(issue);
+            }
+        }
+    }
+    if !table {
+        return Ok(());
+    }
+
+    let header = ["CONTEXT", "SERVER", "CERTIFICATE", "EXEC PLUGIN"].map(String::from);
+    let rows: Vec<[String; 4]> = std::iter::once(header)
+        .chain(
+            kubeconfigs
+                .iter()
+                .zip(results)
+                .map(|((name, _), [server, certificate, exec])| {
+                    [name.to_string(), server.text, certificate.text, exec.text]
+                }),
+        )
+        .collect();
+    println!();
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+fn probe(kubeconfig: &Result<KubeConfig>, kubectl: Option<&str>, timeout: Duration, expiry_days: u32) -> [Check; 3] {
+    let kubeconfig = match kubeconfig {
+        Ok(kubeconfig) => kubeconfig,
+        Err(err) => {
+            return [
+                Check::issue("server-unreachable", Severity::Error, format!("error: {err}")),
+                Check::ok("-"),
+                Check::ok("-"),
+            ]
+        }
+    };
+    let user = &kubeconfig.users[0].user;
+    [
+        check_server(kubeconfig, kubectl, timeout),
+        check_certificate(user, expiry_days),
+        check_exec_plugin(user),
+    ]
+}
+
+fn check_server(kubeconfig: &KubeConfig, kubectl: Option<&str>, timeout: Duration) -> Check {
+    match kubectl::server_version(kubeconfig, kubectl, timeout) {
+        Ok(version) if version.is_empty() => Check::ok("ok"),
+        Ok(version) => Check::ok(format!("ok ({version})")),
+        Err(err) => Check::issue("server-unreachable", Severity::Error, short_error(&err)),
+    }
+}
+
+/// Shorten an error of the cluster to fit in a table.
+pub fn short_error(err: &anyhow::Error) -> String {
+    // The root cause is the most telling, the errors wrapping it repeat it.
+    let cause = err.root_cause().to_string();
+    let cause = cause.lines().next().unwrap_or_default();
+    match cause.char_indices().nth(80) {
+        Some((end, _)) => format!("error: {}...", &cause[..end]),
+        None => format!("error: {cause}"),
+    }
+}
+
+fn check_certificate(user: &Mapping, expiry_days: u32) -> Check {
+    let invalid = |text| Check::issue("certificate-invalid", Severity::Error, text);
+    let pem = if let Some(data) = user.get("client-certificate-data").and_then(Value::as_str) {
+        match BASE64_STANDARD.decode(data) {
+            Ok(pem) => pem,
+            Err(_) => return invalid("error: invalid client-certificate-data".into()),
+        }
+    } else if let Some(path) = user.get("client-certificate").and_then(Value::as_str) {
+        match fs::read(path) {
+            Ok(pem) => pem,
+            Err(err) => return invalid(format!("error: could not read {path}: {err}")),
+        }
+    } else {
+        return Check::ok("-");
+    };
+
+    match certificate_validity(&pem) {
+        Ok(left) if left.is_negative() => Check::issue(
+            "certificate-expired",
+            Severity::Error,
+            format!("expired {} days ago", -left.whole_days()),
+        ),
+        Ok(left) if left.whole_days() < i64::from(expiry_days) => Check::issue(
+            "certificate-expiring",
+            Severity::Warning,
+            format!("expires in {} days", left.whole_days()),
+        ),
+        Ok(left) => Check::ok(format!("ok ({} days left)", left.whole_days())),
+        Err(err) => invalid(format!("error: {err}")),
+    }
+}
+
+/// Time left until the certificate expires.
+fn certificate_validity(pem: &[u8]) -> Result<time::Duration> {
+    let end = certificate_not_after(&certificate_der(pem)?)?;
+    Ok(end - OffsetDateTime::now_utc())
+}
+
+/// DER encoding of the first certificate of a PEM file.
+fn certificate_der(pem: &[u8]) -> Result<Vec<u8>> {
+    let text = String::from_utf8_lossy(pem);
+    let (_, rest) = text
+        .split_once("-----BEGIN CERTIFICATE-----")
+        .context("The file does not contain a PEM certificate")?;
+    let (base64, _) = rest
+        .split_once("-----END CERTIFICATE-----")
+        .context("The PEM certificate is truncated")?;
+    let base64: String = base64.split_whitespace().collect();
+    BASE64_STANDARD
+        .decode(base64)
+        .context("The PEM certificate is not valid base64")
+}
+
+/// Split a DER element into its tag and contents, and the elements which follow it.
+fn der_element(input: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let invalid = || anyhow!("The certificate is not valid DER");
+    let [tag, length, rest @ ..] = input else {
+        return Err(invalid());
+    };
+    let (length, rest) = match *length {
+        length if length < 0x80 => (usize::from(length), rest),
+        length => {
+            let size = usize::from(length & 0x7f);
+            if size == 0 || size > 4 || rest.len() < size {
+                return Err(invalid());
+            }
+            let length = rest[..size].iter().fold(0, |length, &b| length << 8 | usize::from(b));
+            (length, &rest[size..])
+        }
+    };
+    if rest.len() < length {
+        return Err(invalid());
+    }
+    Ok((*tag, &rest[..length], &rest[length..]))
+}
+
+/// End of the validity of a DER certificate, the `notAfter` field of its TBSCertificate (RFC 5280).
+fn certificate_not_after(der: &[u8]) -> Result<OffsetDateTime> {
+    let (_, certificate, _) = der_element(der)?;
+    let (_, mut fields, _) = der_element(certificate)?;
+    // The version is explicitly tagged, and left out of v1 certificates.
+    if let (0xa0, _, rest) = der_element(fields)? {
+        fields = rest;
+    }
+    // The serial number, the signature algorithm and the issuer come before the validity.
+    for _ in 0..3 {
+        fields = der_element(fields)?.2;
+    }
+    let (_, validity, _) = der_element(fields)?;
+    let (_, _, not_after) = der_element(validity)?;
+    let (tag, time, _) = der_element(not_after)?;
+    parse_der_time(tag, &String::from_utf8_lossy(time))
+}
+
+/// Parse a UTCTime, whose two digit years are in 1950-2049, or a GeneralizedTime.
+fn parse_der_time(tag: u8, time: &str) -> Result<OffsetDateTime> {
+    let time = match tag {
+        0x17 if time.len() == 13 && time < "50" => format!("20{time}"),
+        0x17 if time.len() == 13 => format!("19{time}"),
+        0x18 => time.to_string(),
+        _ => bail!("Unexpected validity of the certificate: {}", time),
+    };
+    let format = format_description::parse("[year][month][day][hour][minute][second]Z")?;
+    Ok(PrimitiveDateTime::parse(&time, &format)?.assume_utc())
+}
+
+fn check_exec_plugin(user: &Mapping) -> Check {
+    let Some(command) = user
+        .get("exec")
+        .and_then(|exec| exec.get("command"))
+        .and_then(Value::as_str)
+    else {
+        return Check::ok("-");
+    };
+    match which::which(command) {
+        Ok(_) => Check::ok(format!("ok ({command})")),
+        Err(_) => Check::issue("exec-plugin-missing", Severity::Error, format!("missing: {command}")),
+    }
+}
+
+/// Print the issues as a SARIF log, which code scanning tools can import.
+fn print_sarif(issues: &[Issue]) -> Result<()> {
+    let mut rules: Vec<_> = issues.iter().map(|i| i.code).collect();
+    rules.sort();
+    rules.dedup();
+
+    let results: Vec<_> = issues
+        .iter()
+        .map(|issue| {
+            json!({
+                "ruleId": issue.code,
+                "level": issue.severity.as_str(),
+                "message": { "text": issue.message },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": issue.file.display().to_string() } },
+                }],
+            })
+        })
+        .collect();
+    let log = json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "kubie",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/kubie-org/kubie",
+                    "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+                },
+            },
+            "results": results,
+        }],
+    });
+    println!("{}", serde_json::to_string_pretty(&log)?);
+    Ok(())
+}
+
+/// Remove from a kubeconfig file what the linter reports and can be fixed: contexts hidden by a context of
+/// another file according to `configs.conflict`, contexts whose cluster or user is not defined anywhere,
+/// clusters and users defined twice, and clusters and users which no context references. Within a file, the
+/// first definition of a name is the one kept, as it is the one kubie uses.
+fn fix_kubeconfig(installed: &Installed, conflict: Conflict, source: &Path, kubeconfig: &mut KubeConfig) {
+    let mut seen = HashSet::new();
+    kubeconfig.contexts.retain(|c| {
+        !installed.is_hidden_context(&c.name, source, conflict)
+            && seen.insert(c.name.clone())
+            && installed.find_cluster_by_name(&c.context.cluster, source).is_some()
+            && installed.find_user_by_name(&c.context.user, source).is_some()
+    });
+
+    // Clusters and users are kept when a context of another file references them, as the contexts of a file
+    // can use the clusters and users of the other files.
+    let contexts = &kubeconfig.contexts;
+    let referenced = |f: fn(&kubeconfig::Context) -> &str, name: &str| {
+        contexts.iter().any(|c| f(&c.context) == name)
+            || installed
+                .contexts
+                .iter()
+                .any(|c| *c.source != source && f(&c.item.context) == name)
+    };
+    let mut seen = HashSet::new();
+    kubeconfig
+        .clusters
+        .retain(|c| seen.insert(c.name.clone()) && referenced(|c| &c.cluster, &c.name));
+    let mut seen = HashSet::new();
+    kubeconfig
+        .users
+        .retain(|u| seen.insert(u.name.clone()) && referenced(|c| &c.user, &u.name));
+}
+
+/// Diff two texts line by line, with two lines of context around the changes.
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<_> = old.lines().collect();
+    let new: Vec<_> = new.lines().collect();
+
+    // Length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let changed = |k: usize| {
+        lines[k.saturating_sub(2)..(k + 3).min(lines.len())]
+            .iter()
+            .any(|(c, _)| *c != ' ')
+    };
+    let mut output = vec![];
+    let mut skipped = false;
+    for (k, (c, line)) in lines.iter().enumerate() {
+        if changed(k) {
+            if skipped && !output.is_empty() {
+                output.push("...".into());
+            }
+            output.push(format!("{c} {line}"));
+            skipped = false;
+        } else {
+            skipped = true;
+        }
+    }
+    output
+}
+
+/// Whether to apply the fixes which were printed. Unless `yes` is given, the user is asked when stdin is a
+/// terminal.
+fn confirm_fix(yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        bail!("Use --yes to apply the fixes");
+    }
+
+    confirm("Apply these fixes?")
+}
+
+/// Fix the issues which can be fixed in the kubeconfig files, after printing a diff of the changes. Returns
+/// whether the files were changed.
+fn fix(installed: &Installed, conflict: Conflict, yes: bool) -> Result<bool> {
+    // The files are locked before they are read, for the fixes not to overwrite the changes of another process.
+    let _locks = kubeconfig::lock_files(sources(installed).into_iter().map(|source| source.as_path()))?;
+    let mut changes = vec![];
+    for source in sources(installed) {
+        let permissions = is_readable_by_others(source)?;
+        if kubeconfig::is_encrypted_file(source)? {
+            println!("Skipping {}, it is encrypted with sops.", source.display());
+            if permissions {
+                println!("chmod 600 {}", source.display());
+                changes.push((source, None, true));
+            }
+            continue;
+        }
+        if kubeconfig::is_multi_document_file(source)? {
+            println!("Skipping {}, it contains several YAML documents.", source.display());
+            if permissions {
+                println!("chmod 600 {}", source.display());
+                changes.push((source, None, true));
+            }
+            continue;
+        }
+
+        let original: KubeConfig = ioutil::read_yaml(source.as_ref())?;
+        let mut fixed = original.clone();
+        fix_kubeconfig(installed, conflict, source, &mut fixed);
+
+        let old = serde_yaml::to_string(&original)?;
+        let new = serde_yaml::to_string(&fixed)?;
+        let diff = diff_lines(&old, &new);
+        if !diff.is_empty() {
+            println!("--- {}", source.display());
+            println!("+++ {}", source.display());
+            for line in diff {
+                println!("{line}");
+            }
+        }
+        if permissions {
+            println!("chmod 600 {}", source.display());
+        }
+        if old != new || permissions {
+            changes.push((source, (old != new).then_some(fixed), permissions));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("Nothing to fix.");
+        return Ok(false);
+    }
+    if !confirm_fix(yes)? {
+        return Ok(false);
+    }
+
+    for (source, fixed, permissions) in changes {
+        if let Some(fixed) = fixed {
+            if fixed.contexts.is_empty() && fixed.clusters.is_empty() && fixed.users.is_empty() {
+                println!("Deleting kubeconfig {} because is it now empty.", source.display());
+                fs::remove_file(source.as_ref()).context("Could not delete empty kubeconfig file")?;
+                continue;
+            }
+            println!("Updating kubeconfig {}.", source.display());
+            ioutil::write_yaml(source.as_ref(), &fixed).context("Could not open kubeconfig file to rewrite it.")?;
+        }
+        if permissions {
+            ioutil::set_private_permissions(source)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Lint a single kubeconfig as if it were loaded from `source`, returning the messages of its issues. It
+/// checks a kubeconfig edited with `kubie edit` before it replaces the original.
+pub fn lint_kubeconfig(kubeconfig: &KubeConfig, source: &Path) -> Vec<String> {
+    let source = Rc::new(source.to_path_buf());
+    let kubeconfig = kubeconfig.clone();
+    let installed = Installed {
+        clusters: kubeconfig
+            .clusters
+            .into_iter()
+            .map(|x| Sourced::new(&source, x))
+            .collect(),
+        users: kubeconfig.users.into_iter().map(|x| Sourced::new(&source, x)).collect(),
+        contexts: kubeconfig
+            .contexts
+            .into_iter()
+            .map(|x| Sourced::new(&source, x))
+            .collect(),
+        skipped: vec![],
+    };
+
+    let mut issues = vec![];
+    lint_clusters(&installed, &mut issues);
+    lint_users(&installed, &mut issues);
+    lint_contexts(&installed, &mut issues);
+    issues.into_iter().map(|issue| issue.message).collect()
+}
+
+/// Issues of the kubeconfig files found without contacting the clusters.
+fn lint_offline(installed: &Installed) -> Vec<Issue> {
+    let mut issues = vec![];
+    lint_skipped(installed, &mut issues);
+    lint_clusters(installed, &mut issues);
+    lint_users(installed, &mut issues);
+    lint_contexts(installed, &mut issues);
+    lint_permissions(installed, &mut issues);
+    issues
+}
+
+pub fn lint(
+    settings: &Settings,
+    online: bool,
+    expiry_days: u32,
+    output: LintOutput,
+    fail_on: &[String],
+    fix_issues: bool,
+    yes: bool,
+) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts_with_conflicts(settings)?;
+    let mut issues = lint_offline(&installed);
+    let offline = issues.len();
+
+    match output {
+        LintOutput::Text => {
+            for issue in &issues {
+                println!("{}", issue.message);
+            }
+            if online {
+                lint_online(settings, &installed, expiry_days, true, &mut issues)?;
+            }
+        }
+        LintOutput::Json | LintOutput::Sarif => {
+            if online {
+                lint_online(settings, &installed, expiry_days, false, &mut issues)?;
+            }
+            if output == LintOutput::Json {
+                println!("{}", serde_json::to_string_pretty(&issues)?);
+            } else {
+                print_sarif(&issues)?;
+            }
+        }
+    }
+
+    // The issues which were just fixed do not fail the command, the files are linted again once fixed. The
+    // online checks are not affected by the fixes.
+    if fix_issues && fix(&installed, settings.configs.conflict, yes)? {
+        let online = issues.split_off(offline);
+        let installed = kubeconfig::get_installed_contexts_with_conflicts(settings)?;
+        issues = lint_offline(&installed);
+        issues.extend(online);
+    }
+
+    if issues.iter().any(|issue| fail_on.iter().any(|f| issue.matches(f))) {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+#[test]
+fn test_certificate_not_after() {
+    let pem = "-----BEGIN CERTIFICATE-----
+MIIBdjCCARugAwIBAgIUaAsnlJC4jT3f6NjhAi/ilW/u5e4wCgYIKoZIzj0EAwIw
+EDEOMAwGA1UEAwwFa3ViaWUwHhcNMjUwMTAxMDAwMDAwWhcNMzAwNjE1MTIzMDAw
+WjAQMQ4wDAYDVQQDDAVrdWJpZTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABFCH
+CbX3ZR/f6RXW5VYz07XidkegcAy/61Ml+E/IyOiPQiSbg/eivcdFJK+NtHLWBicu
+6Ra6Ra0sdfh0wKbaF16jUzBRMB0GA1UdDgQWBBRzJUdmTdoPaqbAkVI0SWh81Lqw
+JjAfBgNVHSMEGDAWgBRzJUdmTdoPaqbAkVI0SWh81LqwJjAPBgNVHRMBAf8EBTAD
+AQH/MAoGCCqGSM49BAMCA0kAMEYCIQC4cWnpWUfeXMozn1aDU5vTCvwdBYo/Ztkb
+lBYx+1iupgIhANgBohR89WmVCdtRuEmFJbx7XbMwb9YIbCa5XsFx6CPE
+-----END CERTIFICATE-----
+";
+    let date = certificate_not_after(&certificate_der(pem.as_bytes()).unwrap()).unwrap();
+    assert_eq!(date.to_string(), "2030-06-15 12:30:00.0 +00:00:00");
+    assert!(certificate_der(&pem.as_bytes()[..100]).is_err());
+    assert!(certificate_not_after(&[0x30, 0x05, 0x30]).is_err());
+
+    let date = parse_der_time(0x18, "20550102030405Z").unwrap();
+    assert_eq!(date.to_string(), "2055-01-02 3:04:05.0 +00:00:00");
+    assert_eq!(parse_der_time(0x17, "990102030405Z").unwrap().year(), 1999);
+}
+
+#[test]
+fn test_issue_matches() {
+    let issue = Issue::new(
+        "cluster-unreferenced",
+        Severity::Warning,
+        String::new(),
+        Path::new("config"),
+    );
+    assert!(issue.matches("warning"));
+    assert!(!issue.matches("error"));
+    assert!(issue.matches("cluster-unreferenced"));
+    assert!(!issue.matches("user-unreferenced"));
+}
+
+#[test]
+fn test_diff_lines() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+    let new = "a\nx\nc\nd\ne\nf\ng\nh\ni\n";
+    assert_eq!(
+        diff_lines(old, new),
+        vec!["  a", "- b", "+ x", "  c", "  d", "...", "  h", "  i", "- j"],
+    );
+    assert!(diff_lines(old, old).is_empty());
+}
@@ -1,31 +1,52 @@
-use clap::{CommandFactory, Parser};
-use clap_complete::{generate, Shell};
+use std::env;
+use std::path::{Path, PathBuf};
 
-use crate::settings::ContextHeaderBehavior;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{generate, Generator, Shell};
+use clap_complete_nushell::Nushell;
+
+#[cfg(feature = "oidc")]
+use crate::oidc::Provider;
+use crate::settings::{ContextHeaderBehavior, PromptColor};
+use crate::shell::EvalShell;
 
 #[derive(Debug, Parser)]
-#[clap(version)]
+#[clap(name = "kubie", version)]
+pub struct Cli {
+    /// Do not color the output, as when the NO_COLOR variable is set.
+    #[clap(long = "no-color", global = true)]
+    pub no_color: bool,
+
+    /// Do not print banners, headers, and the messages of kubie and of its hooks. `kubie up` does nothing
+    /// when no .kubie.yaml file is found.
+    #[clap(short = 'q', long = "quiet", global = true)]
+    pub quiet: bool,
+
+    #[clap(subcommand)]
+    pub command: Kubie,
+}
+
+#[derive(Debug, Subcommand)]
 pub enum Kubie {
     /// Spawn a shell in the given context. The shell is isolated from other shells.
     /// Kubie shells can be spawned recursively without any issue.
     #[clap(name = "ctx")]
-    Context {
-        /// Specify in which namespace of the context the shell is spawned.
-        #[clap(short = 'n', long = "namespace")]
-        namespace_name: Option<String>,
-
-        /// Specify files from which to load contexts instead of using the installed ones.
-        #[clap(short = 'f', long = "kubeconfig")]
-        kubeconfigs: Vec<String>,
-
-        /// Enter the context by spawning a new recursive shell.
-        #[clap(short = 'r', long = "recursive")]
-        recursive: bool,
+    Context(ContextCommand),
 
-        /// Name of the context to enter. Use '-' to switch back to the previous context.
-        context_name: Option<String>,
+    /// Spawn a shell in the context and namespace used last by any kubie shell, e.g. after opening a new
+    /// terminal. Nothing is asked, except the confirmation of protected contexts.
+    #[clap(name = "shell")]
+    Shell {
+        /// Do not ask for confirmation when entering a protected context.
+        #[clap(short = 'y', long = "yes")]
+        yes: bool,
     },
 
+    /// Browse the contexts and their namespaces in a full-screen interface, and spawn a shell in
+    /// the selected one.
+    #[clap(name = "ui")]
+    Ui,
+
     /// Change the namespace in which the current shell operates. The namespace change does
     /// not affect other shells.
     #[clap(name = "ns")]
@@ -38,6 +59,31 @@ pub enum Kubie {
         #[clap(short = 'u', long = "unset")]
         unset: bool,
 
+        /// Create the namespace without asking when it does not exist.
+        #[clap(long = "create")]
+        create: bool,
+
+        /// List the namespaces used with the current context in this shell, the most recent first.
+        #[clap(long = "history", conflicts_with_all = ["namespace_name", "recursive", "unset", "create"])]
+        history: bool,
+
+        /// Print the names of the namespaces of the current context, or of the context given with --context.
+        #[clap(long = "list", conflicts_with_all = ["namespace_name", "recursive", "unset", "create", "history"])]
+        list: bool,
+
+        /// Context whose namespaces are printed with --list.
+        #[clap(long = "context", requires = "list")]
+        context_name: Option<String>,
+
+        /// Print the namespaces of every context matching the wildcard, each prefixed with its context. With a
+        /// namespace name, only the namespaces matching it are printed, which may be a wildcard as well.
+        #[clap(
+            long = "all-contexts",
+            value_name = "PATTERN",
+            conflicts_with_all = ["recursive", "unset", "create", "history", "list"]
+        )]
+        all_contexts: Option<String>,
+
         /// Name of the namespace to enter. Use '-' to switch back to the previous namespace.
         namespace_name: Option<String>,
     },
@@ -49,33 +95,57 @@ pub enum Kubie {
 
     /// Execute a command inside of the given context and namespace.
     #[clap(name = "exec", trailing_var_arg = true)]
-    Exec {
-        /// Name of the context in which to run the command.
-        context_name: String,
-        /// Namespace in which to run the command. This is mandatory to avoid potential errors.
-        namespace_name: String,
-        /// Exit early if a command fails when using a wildcard context.
-        #[clap(short = 'e', long = "exit-early")]
-        exit_early: bool,
-        /// Overrides behavior.print_context_in_exec in Kubie settings file.
-        #[clap(value_enum, long = "context-headers")]
-        context_headers_flag: Option<ContextHeaderBehavior>,
-        /// Command to run as well as its arguments.
-        args: Vec<String>,
-    },
+    Exec(ExecCommand),
 
     /// Prints the path to an isolated configuration file for a context and namespace.
     #[clap(name = "export")]
-    Export {
-        /// Name of the context to export.
-        context_name: String,
-        /// Name of the namespace in the context. This is mandatory to avoid potential errors.
-        namespace_name: String,
+    Export(ExportCommand),
+
+    /// Write a standalone kubeconfig file with only the given context, its cluster and its user. The
+    /// certificate and key files it references are embedded in the file.
+    #[clap(name = "copy")]
+    Copy {
+        /// Name of the context to copy. Defaults to the context and namespace of the current kubie shell.
+        context_name: Option<String>,
+        /// File to write the kubeconfig to. It is printed when the file is omitted or '-'.
+        #[clap(short = 'o', long = "output", conflicts_with = "clipboard")]
+        output: Option<PathBuf>,
+        /// Copy the kubeconfig to the system clipboard instead, e.g. to share it when pairing.
+        #[clap(short = 'c', long = "clipboard")]
+        clipboard: bool,
     },
 
     /// Check the Kubernetes config files for issues.
     #[clap(name = "lint")]
-    Lint,
+    Lint {
+        /// Also probe every context: whether its server answers, when its client certificate expires and
+        /// whether its exec auth plugin is installed.
+        #[clap(long = "online")]
+        online: bool,
+
+        /// Report client certificates which expire within this number of days with --online.
+        #[clap(long = "expiry-days", value_name = "DAYS", default_value_t = 30, requires = "online")]
+        expiry_days: u32,
+
+        /// Print the issues as text, as a JSON array, or as a SARIF log for code scanning tools.
+        #[clap(value_enum, short = 'o', long = "output", default_value_t = LintOutput::Text)]
+        output: LintOutput,
+
+        /// Exit with the code 1 when an issue with this code is found. 'warning' and 'error' match every
+        /// issue of at least this severity. Can be given several times.
+        #[clap(long = "fail-on", value_name = "CODE|SEVERITY")]
+        fail_on: Vec<String>,
+
+        /// Fix the issues which can be fixed in the kubeconfig files: duplicated and unreferenced entries,
+        /// contexts whose cluster or user is not defined and files readable by other users. The changes are
+        /// printed and applied after confirmation.
+        #[clap(long = "fix", conflicts_with = "output")]
+        fix: bool,
+
+        /// Apply the fixes without asking for confirmation.
+        #[clap(short = 'y', long = "yes", requires = "fix")]
+        yes: bool,
+    },
 
     /// Edit the given context.
     #[clap(name = "edit")]
@@ -88,18 +158,220 @@ pub enum Kubie {
     #[clap(name = "edit-config")]
     EditConfig,
 
+    /// Set kubie up for the first time: detect the shell and the kubeconfig files, write kubie's config file,
+    /// and enable the completions and `kubie hook` in the configuration file of the shell.
+    #[clap(name = "init")]
+    Init {
+        /// Take the default answer to every question.
+        #[clap(short = 'y', long = "yes")]
+        yes: bool,
+
+        /// Replace kubie's config file when it already exists, without asking.
+        #[clap(short = 'f', long = "force")]
+        force: bool,
+    },
+
     /// Check for a Kubie update and replace Kubie's binary if needed.
     /// This function can ask for sudo-mode.
     #[clap(name = "update")]
     #[cfg(feature = "update")]
-    Update,
+    Update {
+        /// Only check whether an update is available, printing the current and latest versions as JSON.
+        /// Exit with the code 1 when an update is available.
+        #[clap(long = "check")]
+        check: bool,
+    },
+
+    /// Validate kubie's config files strictly, reporting unknown keys, values of the wrong type, invalid
+    /// glob patterns and include paths which match no file, with their line and column.
+    #[clap(name = "check-config")]
+    CheckConfig {
+        /// Config file to check instead of the system-wide and user config files.
+        file: Option<PathBuf>,
+    },
+
+    /// Print the credentials of an exec plugin from the cache of kubie, running the plugin when they expired.
+    /// Used by the kubeconfig of kubie shells when credential_cache is enabled.
+    #[clap(name = "credential-helper", hide = true)]
+    CredentialHelper {
+        /// Key of the credentials in the cache.
+        #[clap(long = "key")]
+        key: String,
+
+        /// Command and arguments of the exec plugin.
+        #[clap(required = true, last = true)]
+        command: Vec<String>,
+    },
+
+    /// Log in to the OIDC provider of the user of a context, such as Dex or Keycloak, with the device
+    /// authorization flow. The token is used instead of kubelogin for the contexts enabled in the oidc settings.
+    #[clap(name = "login")]
+    #[cfg(feature = "oidc")]
+    Login {
+        /// Name of the context whose user runs kubelogin or uses the oidc auth provider.
+        context_name: String,
+    },
 
-    /// Delete a context. Automatic garbage collection will be performed.
+    /// Print the ID token of an OIDC provider as the credentials of an exec plugin, refreshing it or logging
+    /// in when it expired. Used by the kubeconfig of kubie shells when oidc is enabled.
+    #[clap(name = "oidc-token", hide = true)]
+    #[cfg(feature = "oidc")]
+    OidcToken {
+        #[clap(flatten)]
+        provider: Provider,
+    },
+
+    /// Clone or update the git repositories listed in configs.git_sources, making their
+    /// kubeconfig files available to kubie, and download the remote kubeconfigs of configs.include.
+    #[clap(name = "sync")]
+    Sync,
+
+    /// Enter the context and namespace declared by the .kubie.yaml file of the current directory,
+    /// or of its closest parent having one. A file must be trusted before it is used, and again
+    /// after it is modified.
+    #[clap(name = "up")]
+    Up {
+        /// Enter the context by spawning a new recursive shell.
+        #[clap(short = 'r', long = "recursive")]
+        recursive: bool,
+
+        /// Trust the file without asking for confirmation.
+        #[clap(long = "trust")]
+        trust: bool,
+
+        /// Instead of spawning a shell, print commands which make the current shell use the context.
+        #[clap(
+            value_enum,
+            long = "eval",
+            value_name = "SHELL",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "bash",
+            conflicts_with = "recursive"
+        )]
+        eval: Option<EvalShell>,
+    },
+
+    /// Check the clusters of every context, or of the contexts matching the wildcard, in parallel: whether
+    /// the server answers, its version, the latency of the request and whether it accepts the credentials.
+    /// Exit with the code 1 when a server does not answer or refuses the credentials.
+    #[clap(name = "status")]
+    Status {
+        /// Wildcard of the contexts to check.
+        context_name: Option<String>,
+
+        /// Print the status of every context as a JSON array.
+        #[clap(value_enum, short = 'o', long = "output", default_value_t = StatusOutput::Text)]
+        output: StatusOutput,
+    },
+
+    /// List the running kubie shells with their current context and namespace, or terminate one of them.
+    #[clap(name = "sessions")]
+    Sessions(KubieSessions),
+
+    /// Show the recent context and namespace switches of every kubie shell, or which contexts are used the
+    /// most.
+    #[clap(name = "history")]
+    History {
+        /// Print the number of switches to each context and when it was last used, including the contexts
+        /// which were never used.
+        #[clap(long = "stats")]
+        stats: bool,
+
+        /// Number of switches to print.
+        #[clap(short = 'n', long = "limit", default_value_t = 20, conflicts_with = "stats")]
+        limit: usize,
+    },
+
+    /// Show how the contexts and namespaces are used on this machine: the switches to them and the estimated
+    /// time spent in them, along with the commands run with `kubie exec` when the audit log is enabled. The
+    /// statistics are computed from the local history, nothing is sent anywhere.
+    #[clap(name = "stats")]
+    Stats {
+        /// Only count the last period, such as 7d or 12h.
+        #[clap(long = "since")]
+        since: Option<String>,
+
+        /// Metadata key of the contexts by which their usage is grouped, such as environment.
+        #[clap(long = "group-by", default_value = "environment")]
+        group_by: String,
+
+        /// Print the statistics as JSON.
+        #[clap(value_enum, short = 'o', long = "output", default_value_t = StatsOutput::Text)]
+        output: StatsOutput,
+    },
+
+    /// Remove the temporary kubeconfig and session files left behind by kubie shells which crashed, and
+    /// forget the trusted project files which no longer exist. Temporary files are also pruned by the
+    /// commands which spawn shells or write temporary files.
+    #[clap(name = "prune")]
+    Prune {
+        /// Print what would be removed, without removing anything.
+        #[clap(long = "dry-run")]
+        dry_run: bool,
+
+        /// Also delete the kubeconfig files which expired, see configs.expire and the ttl of the
+        /// discovery backends, after confirmation.
+        #[clap(long = "expired")]
+        expired: bool,
+
+        /// Do not ask for confirmation before deleting the expired kubeconfig files.
+        #[clap(short = 'y', long = "yes")]
+        yes: bool,
+    },
+
+    /// Save and restore the contexts and namespaces used in a kubie shell.
+    #[clap(name = "session")]
+    Session(KubieSession),
+
+    /// Delete contexts. Automatic garbage collection will be performed.
     /// Dangling users and clusters will be removed.
     #[clap(name = "delete")]
     Delete {
-        /// Name of the context to edit.
+        /// Also delete the clusters and users of the modified files which are not used by any context anymore.
+        #[clap(long = "prune-orphans")]
+        prune_orphans: bool,
+
+        /// Print which files would be modified and what would be deleted from them, without deleting anything.
+        #[clap(long = "dry-run")]
+        dry_run: bool,
+
+        /// Names of the contexts to delete. A menu in which several contexts can be selected with tab is
+        /// shown when omitted.
+        context_names: Vec<String>,
+    },
+
+    /// Rename a context in the kubeconfig file defining it. Its cluster and user are renamed as well
+    /// when they have the same name and no other context uses them.
+    #[clap(name = "rename")]
+    Rename {
+        /// Name of the context to rename.
         context_name: Option<String>,
+        /// New name of the context.
+        new_name: Option<String>,
+    },
+
+    /// Print the contexts matching a name or a wildcard with their kubeconfig file, resolving them as kubie
+    /// does, `-` being the previous context. Exit with the code 1 when no context matches, and with the code 2
+    /// when several do.
+    #[clap(name = "which")]
+    Which {
+        /// Name or wildcard of the context.
+        pattern: String,
+
+        /// Accept several matching contexts.
+        #[clap(short = 'a', long = "all")]
+        all: bool,
+    },
+
+    /// Print a shell function which wraps kubie so that `kubie ctx` and `kubie up` change the context of
+    /// the current shell instead of spawning a new one. Enable it using
+    /// `eval "$(kubie hook bash)"` in your shell's configuration file.
+    #[clap(name = "hook")]
+    Hook {
+        /// The shell to generate the function for.
+        #[clap(value_enum)]
+        shell: EvalShell,
     },
 
     /// Generate a completion script. Enable completion using
@@ -107,12 +379,261 @@ pub enum Kubie {
     /// configuration file to enable completion automatically.
     #[clap(name = "generate-completion")]
     GenerateCompletion(GenerateCompletionCommand),
+
+    /// Any other subcommand runs the `kubie-<name>` executable found in PATH.
+    #[clap(external_subcommand)]
+    Plugin(Vec<String>),
+}
+
+#[derive(Debug, Default, Parser)]
+pub struct ContextCommand {
+    /// Specify in which namespace of the context the shell is spawned.
+    #[clap(short = 'n', long = "namespace")]
+    pub namespace_name: Option<String>,
+
+    /// Specify files from which to load contexts instead of using the installed ones.
+    #[clap(short = 'f', long = "kubeconfig")]
+    pub kubeconfigs: Vec<String>,
+
+    /// Print the kubeconfig files which were skipped, because they match configs.exclude_patterns or could
+    /// not be parsed, and why.
+    #[clap(short = 'v', long = "verbose")]
+    pub verbose: bool,
+
+    /// Enter the context by spawning a new recursive shell.
+    #[clap(short = 'r', long = "recursive")]
+    pub recursive: bool,
+
+    /// Do not ask for confirmation when entering a protected context.
+    #[clap(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// Impersonate behavior.read_only_user so that the cluster cannot be modified.
+    #[clap(long = "read-only")]
+    pub read_only: bool,
+
+    /// Create the namespace given with --namespace without asking when it does not exist.
+    #[clap(long = "create", requires = "namespace_name")]
+    pub create: bool,
+
+    /// Instead of spawning a shell, print commands which make the current shell use the context.
+    /// Use with `eval "$(kubie ctx <name> --eval)"` or the wrapper printed by `kubie hook`.
+    #[clap(
+        value_enum,
+        long = "eval",
+        value_name = "SHELL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "bash",
+        conflicts_with = "recursive"
+    )]
+    pub eval: Option<EvalShell>,
+
+    /// Do not spawn a shell. Write the isolated kubeconfig of the context to the --output file,
+    /// or print it when no file is given.
+    #[clap(long = "no-shell", conflicts_with_all = ["recursive", "eval"])]
+    pub no_shell: bool,
+
+    /// Print the names of the contexts instead of entering one.
+    #[clap(long = "list", conflicts_with_all = ["context_name", "namespace_name", "recursive", "eval", "no_shell"])]
+    pub list: bool,
+
+    /// Print the contexts in a table with the server, user and file of each of them instead of entering one.
+    #[clap(short = 'w', long = "wide", conflicts_with_all = ["context_name", "namespace_name", "recursive", "eval", "no_shell"])]
+    pub wide: bool,
+
+    /// File to write the kubeconfig to with --no-shell.
+    #[clap(short = 'o', long = "output", requires = "no_shell")]
+    pub output: Option<PathBuf>,
+
+    /// Name of the context to enter. Use '-' to switch back to the previous context.
+    pub context_name: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ExportCommand {
+    /// Name of the context to export. A wildcard exports every context matching it.
+    pub context_name: String,
+    /// Name of the namespace in the context. This is mandatory to avoid potential errors.
+    pub namespace_name: String,
+    /// File to write the kubeconfig to, or '-' to print it, instead of a temporary file whose path is
+    /// printed. Requires a single context, or --merge.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+    /// Combine the exported contexts into a single kubeconfig, along with the contexts of the given
+    /// kubeconfig files, such as previous exports. The exported context is the current one.
+    #[clap(long = "merge", num_args = 0..)]
+    pub merge: Option<Vec<PathBuf>>,
+    /// Set the namespace of every context of the kubeconfig, including the contexts merged from files.
+    #[clap(long = "set-namespace")]
+    pub set_namespace: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ExecCommand {
+    /// Name of the context in which to run the command. It is omitted with --contexts-from.
+    pub context_name: String,
+    /// Namespace in which to run the command. This is mandatory to avoid potential errors. A wildcard
+    /// runs the command in every namespace of the contexts matching it.
+    pub namespace_name: String,
+    /// Print the contexts matching the wildcard, along with the namespace, the environment and the command
+    /// line which would be used in each of them, without running anything.
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+    /// Pick the contexts in which to run the command from a menu of the contexts matching the wildcard.
+    /// Use tab to select several contexts.
+    #[clap(long = "pick")]
+    pub pick: bool,
+    /// Exit early if a command fails when using a wildcard context, with the exit code of the command.
+    #[clap(short = 'e', long = "exit-early", alias = "fail-fast")]
+    pub exit_early: bool,
+    /// Run the command in every context even if it fails in some, then exit with the exit code of the
+    /// first context in which it failed.
+    #[clap(long = "keep-going", conflicts_with = "exit_early")]
+    pub keep_going: bool,
+    /// Print a table of the exit code of the command in every context to stderr at the end. Implies
+    /// --keep-going unless --exit-early is given.
+    #[clap(long = "summary")]
+    pub summary: bool,
+    /// Show a desktop notification with the number of contexts in which the command succeeded and failed
+    /// once it finished in every context.
+    #[clap(long = "notify")]
+    pub notify: bool,
+    /// Overrides behavior.print_context_in_exec in Kubie settings file.
+    #[clap(value_enum, long = "context-headers")]
+    pub context_headers_flag: Option<ContextHeaderBehavior>,
+    /// Do not ask for confirmation when running in protected contexts.
+    #[clap(short = 'y', long = "yes")]
+    pub yes: bool,
+    /// Print the exit code and the output of the command in every context as JSON objects, one per
+    /// line as the commands finish, or as an array at the end.
+    #[clap(value_enum, short = 'o', long = "output", default_value_t = ExecOutput::Text)]
+    pub output: ExecOutput,
+    /// Run the command in up to N contexts at the same time. Every line of output is prefixed
+    /// with the context and namespace it comes from.
+    #[clap(
+        short = 'p',
+        long = "parallel",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub parallel: usize,
+    /// Run a command which fails again in the same context, up to N times.
+    #[clap(long = "retries", value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Number of seconds to wait before running a failed command again.
+    #[clap(long = "retry-delay", value_name = "SECONDS", default_value_t = 1)]
+    pub retry_delay: u64,
+    /// Kill the command when it runs longer than the given number of seconds in a context. It then
+    /// exits with the code 124.
+    #[clap(long = "timeout", value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+    /// Attach the terminal to the command, which must match a single context and namespace. No context
+    /// header is printed.
+    #[clap(short = 'i', long = "interactive", conflicts_with_all = ["parallel", "output", "stdin_file"])]
+    pub interactive: bool,
+    /// Give the contents of the file to the command as its stdin in every context, or the stdin of kubie
+    /// with `-`. By default, the commands share the stdin of kubie, which only the first one reads.
+    #[clap(long = "stdin-file", value_name = "FILE")]
+    pub stdin_file: Option<PathBuf>,
+    /// Run the command in the contexts listed in the file, one per line, or read from the stdin of kubie
+    /// with `-`, instead of the contexts matching a wildcard. Empty lines and lines starting with # are
+    /// ignored. Put `--` before the command when its arguments start with a dash.
+    #[clap(long = "contexts-from", value_name = "FILE")]
+    pub contexts_from: Option<PathBuf>,
+    /// Command to run as well as its arguments.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExecOutput {
+    Text,
+    Json,
+    JsonArray,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatusOutput {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatsOutput {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LintOutput {
+    Text,
+    Json,
+    Sarif,
 }
 
 #[derive(Debug, Parser)]
+pub struct KubieSession {
+    #[clap(subcommand)]
+    pub kind: KubieSessionKind,
+}
+
+#[derive(Debug, Parser)]
+pub enum KubieSessionKind {
+    /// Save the contexts and namespaces used in the current shell under the given name.
+    #[clap(name = "save")]
+    Save {
+        /// Name of the session.
+        name: String,
+        /// Environment variables of the current shell to save with the session.
+        #[clap(short = 'e', long = "env")]
+        env: Vec<String>,
+    },
+    /// Enter one of the contexts of a saved session, using a selectable menu if it has several.
+    #[clap(name = "load")]
+    Load {
+        /// Name of the session.
+        name: String,
+        /// Context of the session to enter.
+        context_name: Option<String>,
+    },
+    /// List the saved sessions.
+    #[clap(name = "list")]
+    List,
+    /// Delete a saved session.
+    #[clap(name = "delete")]
+    Delete {
+        /// Name of the session.
+        name: String,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub struct KubieSessions {
+    #[clap(subcommand)]
+    pub kind: Option<KubieSessionsKind>,
+}
+
+#[derive(Debug, Parser)]
+pub enum KubieSessionsKind {
+    /// Terminate a kubie shell, as if its terminal was closed.
+    #[clap(name = "kill")]
+    Kill {
+        /// PID of the shell, as listed by `kubie sessions`.
+        pid: u32,
+    },
+}
+
+#[derive(Debug, Parser)]
+#[clap(arg_required_else_help = true)]
 pub struct KubieInfo {
+    /// Copy the info to the system clipboard instead of printing it. Without a kind of info, the context
+    /// and the namespace of the current shell are copied, e.g. for incident notes.
+    #[clap(long = "copy", global = true)]
+    pub copy: bool,
+
     #[clap(subcommand)]
-    pub kind: KubieInfoKind,
+    pub kind: Option<KubieInfoKind>,
 }
 
 /// Type of info the user is requesting.
@@ -120,37 +641,281 @@ pub struct KubieInfo {
 pub enum KubieInfoKind {
     /// Get the current shell's context name.
     #[clap(name = "ctx")]
-    Context,
+    Context {
+        /// Decorate the name for kubie's prompt.
+        #[clap(long = "prompt", hide = true)]
+        prompt: bool,
+
+        /// Print the ANSI code of the color of the context in kubie's prompt instead of its name.
+        #[clap(long = "color", hide = true)]
+        color: bool,
+
+        /// Color printed with --color when no entry of prompt.colors matches the context.
+        #[clap(value_enum, long = "default-color", hide = true, requires = "color")]
+        default_color: Option<PromptColor>,
+
+        /// Print the name of the color with --color instead of its code.
+        #[clap(long = "color-name", hide = true, requires = "color")]
+        color_name: bool,
+    },
     /// Get the current shell's namespace name.
     #[clap(name = "ns")]
     Namespace,
     /// Get the current depth of contexts.
     #[clap(name = "depth")]
     Depth,
+    /// Get a field of the metadata of the current shell's context, or an empty line when it has none.
+    #[clap(name = "meta")]
+    Metadata {
+        /// Key of the field, such as owner.
+        key: String,
+    },
+    /// Print kubie's prompt, rendered from the current settings every time the shell shows it.
+    #[clap(name = "prompt", hide = true)]
+    Prompt,
 }
 
 #[derive(Debug, Parser)]
 pub struct GenerateCompletionCommand {
     /// The shell to generate the completion script for. Determined automatically if omitted.
     #[clap(value_enum)]
-    pub shell: Option<Shell>,
+    pub shell: Option<CompletionShell>,
+}
+
+/// Shells supported by clap_complete, plus nushell which lives in its own crate.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Elvish,
+    Fish,
+    #[clap(name = "powershell")]
+    PowerShell,
+    Zsh,
+    Nu,
+}
+
+impl CompletionShell {
+    fn from_env() -> Option<CompletionShell> {
+        let shell = env::var_os("SHELL")?;
+        let name = Path::new(&shell).file_stem()?.to_str()?;
+        if name == "nu" {
+            return Some(CompletionShell::Nu);
+        }
+        Some(match Shell::from_env()? {
+            Shell::Bash => CompletionShell::Bash,
+            Shell::Elvish => CompletionShell::Elvish,
+            Shell::Fish => CompletionShell::Fish,
+            Shell::PowerShell => CompletionShell::PowerShell,
+            Shell::Zsh => CompletionShell::Zsh,
+            _ => return None,
+        })
+    }
 }
 
 /// Generate a completion script.
 pub fn generate_completion(command: GenerateCompletionCommand) {
-    let mut app = Kubie::command();
-    let bin_name = env!("CARGO_BIN_NAME");
     let shell = determine_shell(command);
-    generate(shell, &mut app, bin_name, &mut std::io::stdout());
+    match shell {
+        CompletionShell::Bash => generate_dynamic(Shell::Bash),
+        CompletionShell::Elvish => generate_for(Shell::Elvish),
+        CompletionShell::Fish => generate_dynamic(Shell::Fish),
+        CompletionShell::PowerShell => generate_for(Shell::PowerShell),
+        CompletionShell::Zsh => generate_dynamic(Shell::Zsh),
+        CompletionShell::Nu => generate_for(Nushell),
+    }
+}
+
+fn generate_for(generator: impl Generator) {
+    let mut app = Cli::command();
+    let bin_name = env!("CARGO_BIN_NAME");
+    generate(generator, &mut app, bin_name, &mut std::io::stdout());
+}
+
+/// Generate a completion script which also completes the names of the contexts and namespaces, using
+/// `kubie ctx --list` and `kubie ns --list`.
+fn generate_dynamic(shell: Shell) {
+    let mut app = Cli::command();
+    let mut script = vec![];
+    generate(shell, &mut app, env!("CARGO_BIN_NAME"), &mut script);
+    let script = String::from_utf8_lossy(&script);
+    let script = match shell {
+        Shell::Bash => format!(
+            "{}{BASH_DYNAMIC}",
+            script.replace("complete -F _kubie ", "complete -F _kubie_dynamic ")
+        ),
+        Shell::Zsh => complete_dynamically_zsh(&script),
+        Shell::Fish => format!("{script}{FISH_DYNAMIC}"),
+        _ => script.into_owned(),
+    };
+    print!("{script}");
+}
+
+/// Replace the default completion of the context and namespace arguments in the zsh script generated by
+/// clap with functions listing them.
+fn complete_dynamically_zsh(script: &str) -> String {
+    let mut out = String::new();
+    let mut subcommand = "";
+    for line in script.lines() {
+        if let Some(name) = line.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+            subcommand = name;
+        }
+        if line.starts_with("if [ \"$funcstack[1]\" = \"_kubie\" ]") {
+            out.push_str(ZSH_DYNAMIC);
+        }
+
+        let completer = if line.contains(":context_name -- ") || line.contains(":CONTEXT_NAME:") {
+            Some("_kubie_contexts")
+        } else if subcommand == "ns" && line.contains(":namespace_name -- ") {
+            Some("_kubie_namespaces")
+        } else if line.contains(":namespace_name -- ") || line.contains(":NAMESPACE_NAME:") {
+            Some("_kubie_context_namespaces")
+        } else {
+            None
+        };
+        match completer {
+            Some(completer) => out.push_str(&line.replacen(":_default'", &format!(":{completer}'"), 1)),
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
 }
 
-fn determine_shell(command: GenerateCompletionCommand) -> Shell {
+const BASH_DYNAMIC: &str = r#"
+_kubie_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" positional=() list=() i
+
+    # Arguments of the subcommand, without the options and their values.
+    for ((i = 2; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -n|--namespace|-f|--kubeconfig|-o|--output|-p|--parallel|--retries|--retry-delay|--timeout|--stdin-file|--contexts-from|--context|--context-headers|--eval)
+                ((i++)) ;;
+            -*) ;;
+            *) positional+=("${COMP_WORDS[i]}") ;;
+        esac
+    done
+
+    case "${COMP_WORDS[1]}:$prev" in
+        ctx:-n|ctx:--namespace)
+            list=(ns --list)
+            [ ${#positional[@]} -gt 0 ] && list+=(--context "${positional[0]}") ;;
+        ns:--context)
+            list=(ctx --list) ;;
+        *:-f|*:--kubeconfig|*:-o|*:--output|*:-p|*:--parallel|*:--retries|*:--retry-delay|*:--timeout|*:--stdin-file|*:--contexts-from|*:--context-headers|*:--eval)
+            ;;
+        ctx:*|edit:*|delete:*|rename:*|copy:*|login:*|status:*)
+            [ ${#positional[@]} -eq 0 ] && list=(ctx --list) ;;
+        ns:*)
+            [ ${#positional[@]} -eq 0 ] && list=(ns --list) ;;
+        exec:*|export:*)
+            case ${#positional[@]} in
+                0) list=(ctx --list) ;;
+                1) list=(ns --list --context "${positional[0]}") ;;
+            esac ;;
+    esac
+
+    if [[ "$cur" != -* && ${#list[@]} -gt 0 ]]; then
+        local name
+        while IFS= read -r name; do
+            [[ "$name" == "$cur"* ]] && COMPREPLY+=("$(printf '%q' "$name")")
+        done < <(command kubie "${list[@]}" 2>/dev/null)
+        return 0
+    fi
+    _kubie "$@"
+}
+"#;
+
+const ZSH_DYNAMIC: &str = r#"_kubie_contexts() {
+    local -a contexts
+    contexts=("${(@f)$(command kubie ctx --list 2>/dev/null)}")
+    compadd -a contexts
+}
+
+_kubie_namespaces() {
+    local -a namespaces
+    namespaces=("${(@f)$(command kubie ns --list 2>/dev/null)}")
+    compadd -a namespaces
+}
+
+# Namespaces of the context given as the first argument, or of the current context.
+_kubie_context_namespaces() {
+    local -a args namespaces
+    args=(ns --list)
+    [[ -n "${line[1]}" ]] && args+=(--context "${line[1]}")
+    namespaces=("${(@f)$(command kubie $args 2>/dev/null)}")
+    compadd -a namespaces
+}
+
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+# Whether the subcommand has been given the number of arguments, not counting the options.
+function __fish_kubie_argument_count
+    set -l args (commandline -opc | string match -v -- '-*')
+    test (math (count $args) - 2) -eq $argv[1]
+end
+
+# Namespaces of the context given as the first argument, or of the current context.
+function __fish_kubie_context_namespaces
+    set -l args (commandline -opc | string match -v -- '-*')
+    if set -q args[3]
+        command kubie ns --list --context $args[3] 2>/dev/null
+    else
+        command kubie ns --list 2>/dev/null
+    end
+end
+
+complete -c kubie -n "__fish_kubie_using_subcommand ctx edit delete rename copy login exec export; and __fish_kubie_argument_count 0" -f -a "(command kubie ctx --list 2>/dev/null)"
+complete -c kubie -n "__fish_kubie_using_subcommand exec export; and __fish_kubie_argument_count 1" -f -a "(__fish_kubie_context_namespaces)"
+complete -c kubie -n "__fish_kubie_using_subcommand ctx" -s n -l namespace -r -f -a "(__fish_kubie_context_namespaces)"
+complete -c kubie -n "__fish_kubie_using_subcommand ns; and __fish_kubie_argument_count 0" -f -a "(command kubie ns --list 2>/dev/null)"
+complete -c kubie -n "__fish_kubie_using_subcommand ns" -l context -r -f -a "(command kubie ctx --list 2>/dev/null)"
+"#;
+
+fn determine_shell(command: GenerateCompletionCommand) -> CompletionShell {
     if let Some(shell) = command.shell {
         shell
-    } else if let Some(shell) = Shell::from_env() {
+    } else if let Some(shell) = CompletionShell::from_env() {
         shell
     } else {
         eprintln!("Could not determine shell from environment. Please specify the shell.");
         std::process::exit(1);
     }
 }
+
+#[test]
+fn test_complete_dynamically_zsh() {
+    let script = "(ctx)
+'--namespace=[Namespace]:NAMESPACE_NAME:_default' \\
+'::context_name -- Name of the context:_default' \\
+(ns)
+'::namespace_name -- Name of the namespace:_default' \\
+'-o+[Output]:OUTPUT:_default' \\
+";
+    assert_eq!(
+        complete_dynamically_zsh(script),
+        "(ctx)
+'--namespace=[Namespace]:NAMESPACE_NAME:_kubie_context_namespaces' \\
+'::context_name -- Name of the context:_kubie_contexts' \\
+(ns)
+'::namespace_name -- Name of the namespace:_kubie_namespaces' \\
+'-o+[Output]:OUTPUT:_default' \\
+"
+    );
+}
+
+#[test]
+fn test_global_flags() {
+    let cli = Cli::try_parse_from(["kubie", "up", "-q"]).unwrap();
+    assert!(cli.quiet && !cli.no_color);
+    assert!(matches!(cli.command, Kubie::Up { .. }));
+
+    let cli = Cli::try_parse_from(["kubie", "--no-color", "ns", "-q"]).unwrap();
+    assert!(cli.quiet && cli.no_color);
+}
+
+#[test]
+fn test_exec_parallel() {
+    assert!(Cli::try_parse_from(["kubie", "exec", "-p", "4", "dev", "default", "true"]).is_ok());
+    assert!(Cli::try_parse_from(["kubie", "exec", "-p", "0", "dev", "default", "true"]).is_err());
+}
@@ -1,22 +1,39 @@
-use std::io::{self, IsTerminal};
+use std::io::{self, BufRead, IsTerminal, Write};
 
 use anyhow::{bail, Context, Result};
 
-use crate::kubeconfig::Installed;
+use crate::kubeconfig::{self, Installed, KubeConfig};
 use crate::kubectl;
-use crate::settings::Fzf;
+use crate::openshift;
+use crate::settings::Settings;
+use crate::state::State;
 
+pub mod check_config;
 pub mod context;
+pub mod copy;
 pub mod delete;
 pub mod edit;
 pub mod exec;
 pub mod export;
+pub mod history;
 pub mod info;
+pub mod init;
 pub mod lint;
 pub mod meta;
 pub mod namespace;
+pub mod plugin;
+pub mod prune;
+pub mod rename;
+pub mod session;
+pub mod sessions;
+pub mod stats;
+pub mod status;
+pub mod sync;
+pub mod ui;
+pub mod up;
 #[cfg(feature = "update")]
 pub mod update;
+pub mod which;
 
 pub enum SelectResult {
     Cancelled,
@@ -24,9 +41,37 @@ pub enum SelectResult {
     Selected(String),
 }
 
-pub fn select_or_list_context(fzf: &Fzf, installed: &mut Installed) -> Result<SelectResult> {
+pub fn select_or_list_context(settings: &Settings, installed: &mut Installed) -> Result<SelectResult> {
     installed.contexts.sort_by(|a, b| a.item.name.cmp(&b.item.name));
-    let mut context_names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.clone()).collect();
+    let context_names: Vec<_> = installed.contexts.iter().map(|c| c.item.name.clone()).collect();
+    select_or_list_context_names(settings, context_names, Some(installed))
+}
+
+/// Marker of the favorite contexts in the context menu.
+const FAVORITE_MARKER: char = '★';
+
+/// Select a context name with a menu, or list them when stdout is not a terminal. The menu shows the names
+/// rewritten with `prompt.context_rewrite`, along with the project and location of the GKE clusters. The details
+/// of the contexts found in `installed` are shown in the preview window, and in columns with `fzf.columns`.
+/// The contexts of `favorites` are shown first, with a marker.
+pub fn select_or_list_context_names(
+    settings: &Settings,
+    context_names: Vec<String>,
+    installed: Option<&Installed>,
+) -> Result<SelectResult> {
+    select_context_names(settings, context_names, installed, io::stdout().is_terminal())
+}
+
+/// Like `select_or_list_context_names`, the menu being shown when `menu` is set. The menu is drawn on stderr,
+/// so that `--eval` can show it while the shell captures the output of kubie.
+pub fn select_context_names(
+    settings: &Settings,
+    mut context_names: Vec<String>,
+    installed: Option<&Installed>,
+    menu: bool,
+) -> Result<SelectResult> {
+    context_names.sort();
+    context_names.dedup();
 
     if context_names.is_empty() {
         bail!("No contexts found");
@@ -35,10 +80,77 @@ pub fn select_or_list_context(fzf: &Fzf, installed: &mut Installed) -> Result<Se
         return Ok(SelectResult::Selected(context_names[0].clone()));
     }
 
-    if io::stdout().is_terminal() {
+    if menu {
+        // Favorite contexts come first, in the order of `favorites`, and the others stay sorted by name.
+        context_names.sort_by_key(|name| settings.favorite_rank(name).unwrap_or(usize::MAX));
         // NOTE: skim shows the list of context names in reverse order
         context_names.reverse();
-        match crate::skim::select(fzf, context_names)? {
+        let fzf = &settings.fzf.for_contexts();
+        // The project and location of GKE clusters are shown so that typing them finds their contexts.
+        let gke_cluster = |name: &str| installed.and_then(|installed| installed.gke_cluster(name));
+        let gke = |name: &str| {
+            gke_cluster(name)
+                .map(|gke| [gke.project, gke.location])
+                .unwrap_or_default()
+        };
+        let has_gke = context_names.iter().any(|name| gke_cluster(name).is_some());
+        let texts = match installed {
+            Some(installed) if fzf.columns => {
+                let rows: Vec<_> = context_names
+                    .iter()
+                    .map(|name| {
+                        let [server, user, source] = installed.context_columns(name).unwrap_or_default();
+                        let [project, location] = gke(name);
+                        [
+                            settings.prompt.display_name(name),
+                            project,
+                            location,
+                            server,
+                            user,
+                            source,
+                        ]
+                    })
+                    .collect();
+                format_table(&rows)
+            }
+            Some(_) if has_gke => {
+                let rows: Vec<_> = context_names
+                    .iter()
+                    .map(|name| {
+                        let [project, location] = gke(name);
+                        [settings.prompt.display_name(name), project, location]
+                    })
+                    .collect();
+                format_table(&rows)
+            }
+            _ => context_names
+                .iter()
+                .map(|name| settings.prompt.display_name(name))
+                .collect(),
+        };
+        let has_favorites = context_names.iter().any(|name| settings.favorite_rank(name).is_some());
+        let texts: Vec<_> = context_names
+            .iter()
+            .zip(texts)
+            .map(|(name, text)| match settings.favorite_rank(name) {
+                Some(_) => format!("{FAVORITE_MARKER} {text}"),
+                None if has_favorites => format!("  {text}"),
+                None => text,
+            })
+            .collect();
+        let items = context_names
+            .into_iter()
+            .zip(texts)
+            .map(|(name, text)| crate::skim::MenuItem {
+                preview: installed
+                    .filter(|_| fzf.preview)
+                    .map(|installed| describe_context(settings, installed, &name)),
+                value: name,
+                text,
+            })
+            .collect();
+        let selected = crate::skim::select_items(fzf, items)?;
+        match selected {
             Some(name) => Ok(SelectResult::Selected(name)),
             None => Ok(SelectResult::Cancelled),
         }
@@ -50,10 +162,136 @@ pub fn select_or_list_context(fzf: &Fzf, installed: &mut Installed) -> Result<Se
     }
 }
 
-pub fn select_or_list_namespace(fzf: &Fzf, namespaces: Option<Vec<String>>) -> Result<SelectResult> {
+/// Details of a context for the preview of the context menu, followed by its metadata.
+fn describe_context(settings: &Settings, installed: &Installed, name: &str) -> String {
+    let mut text = installed.describe_context(name).unwrap_or_default();
+    for (key, value) in settings.metadata_for(name) {
+        text.push_str(&format!("{:<11}{}\n", format!("{key}:"), value));
+    }
+    text
+}
+
+/// Metadata of a context on a single line, for the wide listings.
+pub fn metadata_column(settings: &Settings, name: &str) -> String {
+    let fields: Vec<_> = settings
+        .metadata_for(name)
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    fields.join(", ")
+}
+
+/// Align the cells of the rows in columns separated by two spaces.
+pub fn format_table<const N: usize>(rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// List the namespaces of the given kubeconfig, or of the current kubie shell, and remember them in the
+/// namespace cache.
+pub fn fetch_namespaces(settings: &Settings, kubeconfig: Option<&KubeConfig>) -> Result<Vec<String>> {
+    let (context_name, projects) = match kubeconfig {
+        Some(kubeconfig) => (
+            kubeconfig.contexts[0].name.clone(),
+            openshift::is_openshift(settings, kubeconfig),
+        ),
+        None => {
+            let config = kubeconfig::get_current_config()?;
+            (
+                config.contexts[0].name.clone(),
+                openshift::is_openshift(settings, &config),
+            )
+        }
+    };
+
+    let namespaces = kubectl::get_namespaces(
+        kubeconfig,
+        settings.configured_kubectl_for(&context_name).as_deref(),
+        projects,
+        settings.behavior.namespace_lookup_timeout(),
+    )?;
+    let cached = namespaces.clone();
+    State::modify(move |s| {
+        s.namespace_cache.insert(context_name, cached);
+        Ok(())
+    })?;
+    Ok(namespaces)
+}
+
+/// Like `fetch_namespaces`, but falls back to the namespaces the context had the last time they were listed
+/// when the cluster cannot be reached. The namespaces which the settings do not allow in the context are left
+/// out.
+pub fn namespaces_or_cached(settings: &Settings, kubeconfig: Option<&KubeConfig>) -> Result<Vec<String>> {
+    let context_name = match kubeconfig {
+        Some(kubeconfig) => kubeconfig.contexts[0].name.clone(),
+        None => kubeconfig::get_current_config()?.contexts[0].name.clone(),
+    };
+    let namespaces = match fetch_namespaces(settings, kubeconfig) {
+        Ok(namespaces) => namespaces,
+        Err(err) => match State::load()?.namespace_cache.remove(&context_name) {
+            Some(namespaces) => {
+                eprintln!("Warning: could not list namespaces, using the cached list: {err}");
+                namespaces
+            }
+            None => return Err(err),
+        },
+    };
+    Ok(namespaces
+        .into_iter()
+        .filter(|ns| settings.is_namespace_allowed(&context_name, ns))
+        .collect())
+}
+
+/// Fail for a namespace which the settings do not allow in the context.
+pub fn check_namespace_allowed(settings: &Settings, context_name: &str, namespace_name: &str) -> Result<()> {
+    if !settings.is_namespace_allowed(context_name, namespace_name) {
+        bail!(
+            "Namespace {} is not allowed in context {} by the namespaces settings",
+            namespace_name,
+            context_name
+        );
+    }
+    Ok(())
+}
+
+/// Impersonate `behavior.read_only_user` in the kubeconfig when its context is read-only, or when
+/// `read_only` is set.
+pub fn impersonate_if_read_only(settings: &Settings, kubeconfig: &mut KubeConfig, read_only: bool) -> Result<()> {
+    if read_only || settings.behavior.is_read_only_context(&kubeconfig.contexts[0].name) {
+        let user = settings
+            .behavior
+            .read_only_user
+            .as_deref()
+            .context("behavior.read_only_user must be set to enter a context in read-only mode")?;
+        kubeconfig.impersonate(user, &settings.behavior.read_only_groups);
+    }
+    Ok(())
+}
+
+pub fn select_or_list_namespace(settings: &Settings, namespaces: Option<Vec<String>>) -> Result<SelectResult> {
+    select_namespace(settings, namespaces, io::stdout().is_terminal())
+}
+
+/// Like `select_or_list_namespace`, the menu being shown when `menu` is set.
+pub fn select_namespace(settings: &Settings, namespaces: Option<Vec<String>>, menu: bool) -> Result<SelectResult> {
     let mut namespaces = match namespaces {
         Some(ns) => ns,
-        None => kubectl::get_namespaces(None).context("Could not get namespaces")?,
+        None => namespaces_or_cached(settings, None).context("Could not get namespaces")?,
     };
 
     namespaces.sort();
@@ -62,10 +300,10 @@ pub fn select_or_list_namespace(fzf: &Fzf, namespaces: Option<Vec<String>>) -> R
         bail!("No namespaces found");
     }
 
-    if io::stdout().is_terminal() {
+    if menu {
         // NOTE: skim shows the list of namespaces in reverse order
         namespaces.reverse();
-        match crate::skim::select(fzf, namespaces)? {
+        match crate::skim::select(&settings.fzf.for_namespaces(), namespaces)? {
             Some(name) => Ok(SelectResult::Selected(name)),
             None => Ok(SelectResult::Cancelled),
         }
@@ -76,3 +314,66 @@ pub fn select_or_list_namespace(fzf: &Fzf, namespaces: Option<Vec<String>>) -> R
         Ok(SelectResult::Listed)
     }
 }
+
+/// Print a question on stderr and read the answer of the user, without its surrounding whitespace.
+pub fn read_answer(question: &str) -> Result<String> {
+    eprint!("{question} ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Ask a yes or no question, whose answer is no unless the user types yes.
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = read_answer(&format!("{question} [y/N]"))?;
+    Ok(matches!(answer.as_str(), "y" | "Y" | "yes"))
+}
+
+/// Whether to create a namespace which does not exist. Unless `create` is given, the user is asked when
+/// stdin is a terminal.
+pub fn confirm_create_namespace(namespace_name: &str, create: bool) -> Result<bool> {
+    if create {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    confirm(&format!("Namespace {namespace_name} not found, create it?"))
+}
+
+/// Make the user type the name of a protected context before it is used, unless `yes` is given.
+pub fn confirm_protected_context(settings: &Settings, context_name: &str, yes: bool) -> Result<()> {
+    if yes || !settings.behavior.is_protected_context(context_name) {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        bail!("Context {} is protected, use --yes to confirm", context_name);
+    }
+
+    let answer = read_answer(&format!(
+        "Context {context_name} is protected. Type the context name to confirm:"
+    ))?;
+    if answer != context_name {
+        bail!("Confirmation failed for protected context {}", context_name);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_format_table() {
+    let rows = [
+        ["NAME", "SERVER", "SOURCE"].map(String::from),
+        ["minikube", "", "/kube/config"].map(String::from),
+        ["prod", "https://prod", ""].map(String::from),
+    ];
+    assert_eq!(
+        format_table(&rows),
+        [
+            "NAME      SERVER        SOURCE",
+            "minikube                /kube/config",
+            "prod      https://prod",
+        ],
+    );
+}
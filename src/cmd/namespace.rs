@@ -1,19 +1,32 @@
-use anyhow::{anyhow, Context, Result};
+use std::thread;
 
-use crate::cmd::{select_or_list_namespace, SelectResult};
+use anyhow::{bail, Context, Result};
+use wildmatch::WildMatch;
+
+use crate::audit;
+use crate::cmd::{
+    check_namespace_allowed, confirm_create_namespace, context, format_table, namespaces_or_cached,
+    select_or_list_namespace, SelectResult,
+};
+use crate::hooks::{self, HookEnv};
 use crate::kubeconfig;
 use crate::kubectl;
+use crate::openshift;
+use crate::output::note;
 use crate::session::Session;
-use crate::settings::{Settings, ValidateNamespacesBehavior};
-use crate::shell::spawn_shell;
+use crate::settings::{AmbiguousNamespaceAction, Settings, ValidateNamespacesBehavior};
+use crate::shell::{prompt, spawn_shell, update};
 use crate::state::State;
+use crate::tmux;
 use crate::vars;
+use crate::vault;
 
 pub fn namespace(
     settings: &Settings,
     namespace_name: Option<String>,
     recursive: bool,
     unset: bool,
+    create: bool,
 ) -> Result<()> {
     vars::ensure_kubie_active()?;
 
@@ -30,34 +43,44 @@ pub fn namespace(
                 .context("There is not previous namespace to switch to")?
                 .to_string(),
         ),
-        Some(s) => match settings.behavior.validate_namespaces {
-            ValidateNamespacesBehavior::False => Some(s),
-            ValidateNamespacesBehavior::True => {
-                let namespaces = kubectl::get_namespaces(None)?;
-                if !namespaces.contains(&s) {
-                    return Err(anyhow!("'{}' is not a valid namespace for the context", s));
+        // Wildcards are matched against the namespaces even when they are not validated.
+        Some(s) if !settings.behavior.validate_namespaces.can_list_namespaces() && !is_pattern(&s) => Some(s),
+        Some(s) => {
+            let Some(namespaces) = namespaces_to_validate(settings) else {
+                if is_pattern(&s) {
+                    bail!("Could not list the namespaces to match {}", s);
                 }
+                return enter_namespace(settings, &mut session, recursive, Some(s));
+            };
+            if namespaces.contains(&s) {
                 Some(s)
-            }
-            ValidateNamespacesBehavior::Partial => {
-                let namespaces = kubectl::get_namespaces(None)?;
-                if namespaces.contains(&s) {
-                    Some(s)
-                } else {
-                    let ns_partial_matches: Vec<String> =
-                        namespaces.iter().filter(|&ns| ns.contains(&s)).cloned().collect();
-                    match ns_partial_matches.len() {
-                        0 => return Err(anyhow!("'{}' is not a valid namespace for the context", s)),
-                        1 => Some(ns_partial_matches[0].clone()),
-                        _ => match select_or_list_namespace(&settings.fzf, Some(ns_partial_matches))? {
+            } else {
+                let partial = matches!(
+                    settings.behavior.validate_namespaces,
+                    ValidateNamespacesBehavior::Partial
+                );
+                let mut matches = matching_namespaces(&namespaces, &s, partial);
+                match matches.len() {
+                    0 if is_pattern(&s) => bail!("No namespace matching {}", s),
+                    0 => {
+                        create_missing_namespace(settings, &s, create)?;
+                        Some(s)
+                    }
+                    1 => matches.pop(),
+                    _ => match settings.behavior.ambiguous_namespace {
+                        AmbiguousNamespaceAction::Error => {
+                            matches.sort();
+                            bail!("{} matches several namespaces: {}", s, matches.join(", "))
+                        }
+                        AmbiguousNamespaceAction::Select => match select_or_list_namespace(settings, Some(matches))? {
                             SelectResult::Selected(s) => Some(s),
                             _ => return Ok(()),
                         },
-                    }
+                    },
                 }
             }
-        },
-        None => match select_or_list_namespace(&settings.fzf, None)? {
+        }
+        None => match select_or_list_namespace(settings, None)? {
             SelectResult::Selected(s) => Some(s),
             _ => return Ok(()),
         },
@@ -66,16 +89,162 @@ pub fn namespace(
     enter_namespace(settings, &mut session, recursive, namespace_name)
 }
 
+/// Whether the namespace given to `kubie ns` is a wildcard, which namespace names cannot contain.
+fn is_pattern(namespace_name: &str) -> bool {
+    namespace_name.contains(['*', '?'])
+}
+
+/// The namespaces matching the given name: the ones matching it as a wildcard, or else the ones starting
+/// with it, or containing it with `partial`.
+fn matching_namespaces(namespaces: &[String], namespace_name: &str, partial: bool) -> Vec<String> {
+    let pattern = WildMatch::new(namespace_name);
+    namespaces
+        .iter()
+        .filter(|ns| {
+            if is_pattern(namespace_name) {
+                pattern.matches(ns)
+            } else if partial {
+                ns.contains(namespace_name)
+            } else {
+                ns.starts_with(namespace_name)
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Print the namespaces used with the current context in this shell, the most recent first.
+pub fn history() -> Result<()> {
+    vars::ensure_kubie_active()?;
+
+    let session = Session::load().context("Could not load session file")?;
+    let config = kubeconfig::get_current_config()?;
+    for namespace in session.recent_namespaces(&config.contexts[0].name) {
+        println!("{}", namespace.unwrap_or("default"));
+    }
+    Ok(())
+}
+
+/// Print the namespaces of the given context, or of the current one when no context is given.
+pub fn list(settings: &Settings, context_name: Option<String>) -> Result<()> {
+    let mut namespaces = match context_name {
+        Some(context_name) => {
+            let installed = kubeconfig::get_installed_contexts(settings)?;
+            context::namespaces_of(settings, &installed, &context_name)?
+        }
+        None => {
+            vars::ensure_kubie_active()?;
+            namespaces_or_cached(settings, None)?
+        }
+    };
+    namespaces.sort();
+    for namespace in namespaces {
+        println!("{namespace}");
+    }
+    Ok(())
+}
+
+/// Print the namespaces of every context matching the pattern, listed in parallel, along with their context.
+/// The contexts whose namespaces cannot be listed are reported on stderr.
+pub fn list_all_contexts(settings: &Settings, pattern: &str, namespace_pattern: Option<&str>) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let mut names: Vec<_> = installed
+        .get_contexts_matching(pattern, settings.behavior.allow_multiple_context_patterns)
+        .into_iter()
+        .map(|c| c.item.name.as_str())
+        .collect();
+    names.sort();
+    names.dedup();
+    if names.is_empty() {
+        bail!("No context matching {}", pattern);
+    }
+
+    let mut kubeconfigs = vec![];
+    for name in &names {
+        let mut kubeconfig = installed.make_kubeconfig_for_context(name, None::<&str>)?;
+        vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+        kubeconfigs.push(kubeconfig);
+    }
+
+    let results: Vec<Result<Vec<String>>> = thread::scope(|s| {
+        let handles: Vec<_> = kubeconfigs
+            .iter()
+            .map(|kubeconfig| s.spawn(move || namespaces_or_cached(settings, Some(kubeconfig))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("listing panicked"))
+            .collect()
+    });
+
+    let namespace_pattern = namespace_pattern.map(WildMatch::new);
+    let mut rows = vec![];
+    for (name, result) in names.iter().zip(results) {
+        match result {
+            Ok(mut namespaces) => {
+                namespaces.sort();
+                rows.extend(
+                    namespaces
+                        .into_iter()
+                        .filter(|ns| namespace_pattern.as_ref().is_none_or(|p| p.matches(ns)))
+                        .map(|ns| [name.to_string(), ns]),
+                );
+            }
+            Err(err) => eprintln!("Warning: could not list the namespaces of context {name}: {err:#}"),
+        }
+    }
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// The namespaces to validate the requested one against, or `None` to skip the validation when the cluster
+/// cannot be reached and its namespaces were never cached.
+fn namespaces_to_validate(settings: &Settings) -> Option<Vec<String>> {
+    match namespaces_or_cached(settings, None) {
+        Ok(namespaces) => Some(namespaces),
+        Err(err) => {
+            eprintln!("Warning: could not validate the namespace: {err}");
+            None
+        }
+    }
+}
+
+/// Create a namespace which does not exist if the user wants to, fail otherwise.
+fn create_missing_namespace(settings: &Settings, namespace_name: &str, create: bool) -> Result<()> {
+    if !confirm_create_namespace(namespace_name, create)? {
+        bail!("'{}' is not a valid namespace for the context", namespace_name);
+    }
+    let config = kubeconfig::get_current_config()?;
+    kubectl::create_namespace(
+        None,
+        settings.configured_kubectl_for(&config.contexts[0].name).as_deref(),
+        namespace_name,
+        openshift::is_openshift(settings, &config),
+        settings.behavior.namespace_lookup_timeout(),
+    )?;
+    note!("Created namespace {namespace_name}");
+    Ok(())
+}
+
 fn enter_namespace(
     settings: &Settings,
     session: &mut Session,
     recursive: bool,
     namespace_name: Option<String>,
 ) -> Result<()> {
+    let recursive = recursive && !settings.behavior.reuse_session;
     let mut config = kubeconfig::get_current_config()?;
+    let old_namespace = config.contexts[0].context.namespace.take();
     config.contexts[0].context.namespace = namespace_name.clone();
 
     let context_name = &config.contexts[0].name;
+    if let Some(namespace_name) = &namespace_name {
+        check_namespace_allowed(settings, context_name, namespace_name)?;
+    }
+
+    audit::record(&settings.audit, "ns", context_name, namespace_name.as_deref(), None)?;
 
     // Update the state, set the last namespace used for the context.
     // We take out a file lock here to avoid concurrent kubie processes
@@ -84,19 +253,56 @@ fn enter_namespace(
         state
             .namespace_history
             .insert(context_name.into(), namespace_name.clone());
+        state.record_switch(context_name, namespace_name.as_deref());
         Ok(())
     })?;
 
     // Update the history, add the context and namespace to it.
-    session.add_history_entry(context_name, namespace_name);
+    session.add_history_entry(context_name, namespace_name.as_deref());
 
-    if recursive {
-        spawn_shell(settings, config, session)?;
-    } else {
+    if !recursive {
         let config_file = kubeconfig::get_kubeconfig_path()?;
         config.write_to_file(config_file.as_path())?;
+        session.set_prompt(prompt::text(settings, &config, vars::get_depth()));
         session.save(None)?;
+        if let Err(err) = update::write(settings, &config) {
+            eprintln!("Warning: could not update the variables of the shell: {err:#}");
+        }
+        tmux::rename_window(settings, context_name, namespace_name.as_deref());
+    }
+
+    if !settings.hooks.change_ns.is_empty() {
+        let status = hooks::run(
+            &settings.hooks.change_ns,
+            &HookEnv::current(&config),
+            &[
+                ("KUBIE_OLD_NAMESPACE", old_namespace.as_deref().unwrap_or("")),
+                ("KUBIE_NEW_NAMESPACE", namespace_name.as_deref().unwrap_or("")),
+            ],
+        )?;
+        if !status.success() {
+            eprintln!("Warning: change_ns hook exited with {status}");
+        }
+    }
+
+    if recursive {
+        spawn_shell(settings, config, session)?;
     }
 
     Ok(())
 }
+
+#[test]
+fn test_matching_namespaces() {
+    let namespaces: Vec<String> = ["team-a", "team-b", "my-team", "kube-system"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(matching_namespaces(&namespaces, "team-", false), ["team-a", "team-b"]);
+    assert_eq!(
+        matching_namespaces(&namespaces, "team", true),
+        ["team-a", "team-b", "my-team"]
+    );
+    assert_eq!(matching_namespaces(&namespaces, "*-system", false), ["kube-system"]);
+    assert_eq!(matching_namespaces(&namespaces, "team-?", true), ["team-a", "team-b"]);
+    assert!(matching_namespaces(&namespaces, "payments", true).is_empty());
+}
@@ -0,0 +1,34 @@
+use std::io;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::hooks::HookEnv;
+use crate::kubeconfig;
+use crate::vars;
+
+/// Run the `kubie-<name>` executable for an unknown subcommand, like git and kubectl do for their plugins.
+/// Inside of a kubie shell, the plugin receives the context, namespace and kubeconfig of the shell.
+pub fn plugin(args: Vec<String>) -> Result<()> {
+    let (name, args) = args.split_first().expect("external subcommand should have a name");
+    let executable = format!("kubie-{name}");
+
+    let mut cmd = Command::new(&executable);
+    cmd.args(args);
+    if vars::is_kubie_active() {
+        let config = kubeconfig::get_current_config()?;
+        cmd.envs(HookEnv::current(&config).vars());
+    }
+
+    let status = match cmd.status() {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            bail!(
+                "Unknown command {}, no executable named {} was found in PATH",
+                name,
+                executable
+            )
+        }
+        status => status.with_context(|| format!("Could not run {executable}"))?,
+    };
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::cmd::confirm;
+use crate::ephemeral;
+use crate::settings::Settings;
+use crate::state::State;
+
+/// Delete the kubeconfig files which expired, after asking unless `yes` is given. Return how many were found.
+fn prune_expired(settings: &Settings, dry_run: bool, yes: bool) -> Result<usize> {
+    let (_, skipped) = settings.get_kube_configs_paths_and_skipped()?;
+    let expired: Vec<_> = skipped.into_iter().filter(|file| file.expired).collect();
+    if expired.is_empty() {
+        return Ok(0);
+    }
+    if dry_run {
+        for file in &expired {
+            println!("Would delete {}: {}", file.path.display(), file.reason);
+        }
+        return Ok(expired.len());
+    }
+
+    for file in &expired {
+        println!("{}: {}", file.path.display(), file.reason);
+    }
+    if !yes {
+        if !io::stdin().is_terminal() {
+            bail!("Use --yes to delete the expired kubeconfig files");
+        }
+        if !confirm(&format!("Delete these {} kubeconfig files?", expired.len()))? {
+            return Ok(expired.len());
+        }
+    }
+    for file in &expired {
+        fs::remove_file(&file.path).with_context(|| format!("Could not delete {}", file.path.display()))?;
+        println!("Deleted {}", file.path.display());
+    }
+    Ok(expired.len())
+}
+
+pub fn prune(settings: &Settings, dry_run: bool, expired: bool, yes: bool) -> Result<()> {
+    let action = if dry_run { "Would remove" } else { "Removed" };
+    let files = ephemeral::prune(dry_run);
+    for path in &files {
+        println!("{} {}", action, path.display());
+    }
+
+    let projects: Vec<String> = State::load()?
+        .trusted_projects
+        .into_keys()
+        .filter(|path| !Path::new(path).exists())
+        .collect();
+    let action = if dry_run { "Would forget" } else { "Forgot" };
+    for path in &projects {
+        println!("{action} trusted project {path}");
+    }
+    if !dry_run && !projects.is_empty() {
+        State::modify(|state| {
+            state.trusted_projects.retain(|path, _| Path::new(path).exists());
+            Ok(())
+        })?;
+    }
+
+    let kubeconfigs = if expired {
+        prune_expired(settings, dry_run, yes)?
+    } else {
+        0
+    };
+
+    if files.is_empty() && projects.is_empty() && kubeconfigs == 0 {
+        println!("Nothing to prune");
+    }
+    Ok(())
+}
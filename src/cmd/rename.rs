@@ -0,0 +1,52 @@
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Result};
+
+use crate::cmd::{read_answer, select_or_list_context, SelectResult};
+use crate::kubeconfig;
+use crate::settings::Settings;
+use crate::state::State;
+
+pub fn rename_context(settings: &Settings, context_name: Option<String>, new_name: Option<String>) -> Result<()> {
+    let mut installed = kubeconfig::get_installed_contexts(settings)?;
+
+    let context_name = match context_name {
+        Some(context_name) => context_name,
+        None => match select_or_list_context(settings, &mut installed)? {
+            SelectResult::Selected(x) => x,
+            _ => return Ok(()),
+        },
+    };
+
+    let new_name = match new_name {
+        Some(new_name) => new_name,
+        None => ask_new_name(&context_name)?,
+    };
+    if new_name.is_empty() || new_name == context_name {
+        return Ok(());
+    }
+
+    installed.rename_context(&context_name, &new_name)?;
+
+    // Keep the namespace remembered for the context.
+    State::modify(|state| {
+        if state.last_context.as_deref() == Some(context_name.as_str()) {
+            state.last_context = Some(new_name.clone());
+        }
+        if let Some(namespace) = state.namespace_history.remove(&context_name) {
+            state.namespace_history.insert(new_name.clone(), namespace);
+        }
+        if let Some(namespaces) = state.namespace_cache.remove(&context_name) {
+            state.namespace_cache.insert(new_name.clone(), namespaces);
+        }
+        Ok(())
+    })
+}
+
+fn ask_new_name(context_name: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        bail!("A new name is required to rename context {}", context_name);
+    }
+
+    read_answer(&format!("New name for context {context_name}:"))
+}
@@ -0,0 +1,111 @@
+use std::env;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::cmd::meta::{ContextCommand, KubieSession, KubieSessionKind};
+use crate::cmd::{context, select_or_list_context_names, SelectResult};
+use crate::kubeconfig;
+use crate::session::{HistoryEntry, Session};
+use crate::settings::Settings;
+use crate::state::{SavedSession, State};
+use crate::vars;
+
+fn save(name: String, env_names: Vec<String>) -> Result<()> {
+    vars::ensure_kubie_active()?;
+    let config = kubeconfig::get_current_config()?;
+    let session = Session::load()?;
+
+    // The namespace of the current context comes from its kubeconfig, it may have been changed since
+    // the context was entered.
+    let current = HistoryEntry {
+        context: config.contexts[0].name.clone(),
+        namespace: config.contexts[0].context.namespace.clone(),
+    };
+    let mut contexts: Vec<_> = session
+        .recent_contexts()
+        .into_iter()
+        .filter(|e| e.context != current.context)
+        .collect();
+    contexts.insert(0, current);
+
+    let mut saved = SavedSession {
+        contexts,
+        ..Default::default()
+    };
+    for env_name in env_names {
+        let value = env::var(&env_name).with_context(|| format!("{env_name} variable is not set"))?;
+        saved.env.insert(env_name, value);
+    }
+
+    let count = saved.contexts.len();
+    State::modify(move |s| {
+        s.saved_sessions.insert(name, saved);
+        Ok(())
+    })?;
+    eprintln!("Saved {count} context(s).");
+    Ok(())
+}
+
+fn load(settings: &Settings, name: &str, context_name: Option<String>) -> Result<()> {
+    let state = State::load()?;
+    let saved = state
+        .saved_sessions
+        .get(name)
+        .ok_or_else(|| anyhow!("No saved session named {}", name))?;
+
+    let context_name = match context_name {
+        Some(context_name) => context_name,
+        None => {
+            let names = saved.contexts.iter().map(|e| e.context.clone()).collect();
+            match select_or_list_context_names(settings, names, None)? {
+                SelectResult::Selected(x) => x,
+                _ => return Ok(()),
+            }
+        }
+    };
+    let entry = saved
+        .contexts
+        .iter()
+        .find(|e| e.context == context_name)
+        .ok_or_else(|| anyhow!("Session {} has no context named {}", name, context_name))?;
+
+    if vars::is_kubie_active() && !saved.env.is_empty() {
+        eprintln!("Warning: the environment of session {name} only applies to new shells, use -r to spawn one.");
+    }
+    // Shells spawned by kubie inherit its environment.
+    for (key, value) in &saved.env {
+        env::set_var(key, value);
+    }
+
+    context::context(
+        settings,
+        ContextCommand {
+            context_name: Some(entry.context.clone()),
+            namespace_name: entry.namespace.clone(),
+            ..Default::default()
+        },
+    )
+}
+
+pub fn session(settings: &Settings, session: KubieSession) -> Result<()> {
+    match session.kind {
+        KubieSessionKind::Save { name, env } => save(name, env)?,
+        KubieSessionKind::Load { name, context_name } => load(settings, &name, context_name)?,
+        KubieSessionKind::List => {
+            let state = State::load()?;
+            for (name, saved) in &state.saved_sessions {
+                let contexts: Vec<_> = saved.contexts.iter().map(|e| e.context.as_str()).collect();
+                println!("{}\t{}", name, contexts.join(", "));
+            }
+        }
+        KubieSessionKind::Delete { name } => {
+            State::modify(move |s| {
+                if s.saved_sessions.remove(&name).is_none() {
+                    bail!("No saved session named {}", name);
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, Result};
+
+use crate::cmd::format_table;
+use crate::cmd::meta::{KubieSessions, KubieSessionsKind};
+use crate::registry;
+use crate::settings::Settings;
+
+fn list(settings: &Settings) {
+    let entries = registry::list();
+    if entries.is_empty() {
+        eprintln!("No kubie shells are running.");
+        return;
+    }
+
+    let mut rows = vec![["PID", "CONTEXT", "NAMESPACE", "DEPTH", "STARTED", "TTY"].map(String::from)];
+    for entry in entries {
+        let (context, namespace) = entry.current();
+        rows.push([
+            entry.pid.to_string(),
+            settings.prompt.display_name(&context),
+            namespace.unwrap_or_default(),
+            entry.depth.to_string(),
+            entry.started,
+            entry.tty.unwrap_or_default(),
+        ]);
+    }
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) -> Result<()> {
+    let entry = registry::get(pid).ok_or_else(|| anyhow!("No kubie shell with the PID {}", pid))?;
+    if !registry::is_live(&entry) {
+        // The PID may have been given to another process since the shell exited.
+        registry::unregister(pid);
+        return Err(anyhow!("The kubie shell {} is no longer running", pid));
+    }
+    // Shells exit on SIGHUP, which they receive when their terminal is closed. Interactive shells ignore SIGTERM.
+    let result = unsafe { libc::kill(pid as libc::pid_t, libc::SIGHUP) };
+    if result != 0 {
+        return Err(anyhow!(
+            "Could not terminate the shell {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+    registry::unregister(pid);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill(_pid: u32) -> Result<()> {
+    Err(anyhow!("Terminating kubie shells is only supported on Unix"))
+}
+
+pub fn sessions(settings: &Settings, sessions: KubieSessions) -> Result<()> {
+    match sessions.kind {
+        None => list(settings),
+        Some(KubieSessionsKind::Kill { pid }) => kill(pid)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::audit::{self, LoggedEntry};
+use crate::cmd::format_table;
+use crate::cmd::meta::StatsOutput;
+use crate::settings::{parse_duration, Settings};
+use crate::state::{State, Switch};
+
+/// Longest time counted for a switch. The rest of the time until the next switch is assumed to be idle, such
+/// as a night with a shell left open.
+const MAX_SWITCH_TIME: Duration = Duration::minutes(30);
+
+/// Usage of a context, or of a group of contexts.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Usage {
+    switches: usize,
+    /// Estimated time spent in the contexts, in seconds.
+    seconds: i64,
+    /// Commands run with `kubie exec`, counted from the audit log.
+    execs: usize,
+}
+
+impl Usage {
+    fn add(&mut self, switches: usize, seconds: i64, execs: usize) {
+        self.switches += switches;
+        self.seconds += seconds;
+        self.execs += execs;
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct NamespaceUsage {
+    context: String,
+    namespace: String,
+    switches: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Stats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last: Option<String>,
+    switches: usize,
+    switches_per_day: f64,
+    contexts: BTreeMap<String, Usage>,
+    namespaces: Vec<NamespaceUsage>,
+    /// Usage of the contexts by the value of their metadata key given with --group-by.
+    groups: BTreeMap<String, Usage>,
+}
+
+fn parse_time(timestamp: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(timestamp, &Rfc3339).ok()
+}
+
+/// Aggregate the switches and the commands of the audit log since the given time. The time spent in a context
+/// is estimated from the time until the next switch of any shell, up to MAX_SWITCH_TIME.
+fn aggregate(
+    switches: &[Switch],
+    logged: &[LoggedEntry],
+    since: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+    group: impl Fn(&str) -> String,
+) -> Stats {
+    let recent = |timestamp: &str| parse_time(timestamp).filter(|time| since.is_none_or(|since| *time >= since));
+    let switches: Vec<(OffsetDateTime, &Switch)> = switches
+        .iter()
+        .filter_map(|switch| Some((recent(&switch.timestamp)?, switch)))
+        .collect();
+
+    let mut stats = Stats::default();
+    let mut namespaces: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for (index, (time, switch)) in switches.iter().enumerate() {
+        let end = switches.get(index + 1).map_or(now, |(next, _)| *next);
+        let seconds = (end - *time).clamp(Duration::ZERO, MAX_SWITCH_TIME).whole_seconds();
+        stats
+            .contexts
+            .entry(switch.context.clone())
+            .or_default()
+            .add(1, seconds, 0);
+        stats
+            .groups
+            .entry(group(&switch.context))
+            .or_default()
+            .add(1, seconds, 0);
+        *namespaces
+            .entry((&switch.context, switch.namespace.as_deref().unwrap_or("default")))
+            .or_default() += 1;
+    }
+    for entry in logged.iter().filter(|entry| entry.command == "exec") {
+        if recent(&entry.timestamp).is_some() {
+            stats.contexts.entry(entry.context.clone()).or_default().add(0, 0, 1);
+            stats.groups.entry(group(&entry.context)).or_default().add(0, 0, 1);
+        }
+    }
+
+    stats.switches = switches.len();
+    if let (Some((first, _)), Some((last, _))) = (switches.first(), switches.last()) {
+        let days = (*last - *first).as_seconds_f64() / Duration::DAY.as_seconds_f64();
+        stats.switches_per_day = switches.len() as f64 / days.max(1.0);
+        stats.first = first.format(&Rfc3339).ok();
+        stats.last = last.format(&Rfc3339).ok();
+    }
+    stats.namespaces = namespaces
+        .into_iter()
+        .map(|((context, namespace), switches)| NamespaceUsage {
+            context: context.into(),
+            namespace: namespace.into(),
+            switches,
+        })
+        .collect();
+    stats
+}
+
+/// A duration such as `2h05m`.
+fn format_seconds(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h{:02}m", minutes % 60),
+    }
+}
+
+/// Rows of a usage table, the most used first.
+fn usage_rows(header: &str, usages: &BTreeMap<String, Usage>, display: impl Fn(&str) -> String) -> Vec<[String; 5]> {
+    let total: i64 = usages.values().map(|usage| usage.seconds).sum();
+    let mut usages: Vec<_> = usages.iter().collect();
+    usages.sort_by(|a, b| b.1.seconds.cmp(&a.1.seconds).then(b.1.switches.cmp(&a.1.switches)));
+
+    let mut rows = vec![[header, "SWITCHES", "TIME", "SHARE", "EXECS"].map(String::from)];
+    for (name, usage) in usages {
+        let share = match total {
+            0 => "-".to_string(),
+            total => format!("{}%", usage.seconds * 100 / total),
+        };
+        rows.push([
+            display(name),
+            usage.switches.to_string(),
+            format_seconds(usage.seconds),
+            share,
+            usage.execs.to_string(),
+        ]);
+    }
+    rows
+}
+
+pub fn stats(settings: &Settings, since: Option<String>, group_by: String, output: StatsOutput) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    let since = match since {
+        Some(since) => {
+            let period = parse_duration(&since).with_context(|| format!("Invalid duration: {since}"))?;
+            Some(now - period)
+        }
+        None => None,
+    };
+
+    let state = State::load()?;
+    let logged = audit::read(&settings.audit)?;
+    let group = |context: &str| {
+        settings
+            .metadata_for(context)
+            .get(group_by.as_str())
+            .map_or("-".to_string(), |value| value.to_string())
+    };
+    let stats = aggregate(&state.switches, &logged, since, now, group);
+
+    if output == StatsOutput::Json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    if stats.contexts.is_empty() {
+        eprintln!("No context or namespace switches were recorded yet.");
+        return Ok(());
+    }
+
+    if let (Some(first), Some(last)) = (&stats.first, &stats.last) {
+        println!(
+            "{} switches from {first} to {last}, {:.1} per day",
+            stats.switches, stats.switches_per_day
+        );
+        println!();
+    }
+    let rows = usage_rows("CONTEXT", &stats.contexts, |name| settings.prompt.display_name(name));
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+
+    println!();
+    let mut rows = vec![["CONTEXT", "NAMESPACE", "SWITCHES"].map(String::from)];
+    let mut namespaces: Vec<_> = stats.namespaces.iter().collect();
+    namespaces.sort_by_key(|usage| Reverse(usage.switches));
+    for usage in namespaces {
+        rows.push([
+            settings.prompt.display_name(&usage.context),
+            usage.namespace.clone(),
+            usage.switches.to_string(),
+        ]);
+    }
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+
+    println!();
+    for line in format_table(&usage_rows(&group_by.to_uppercase(), &stats.groups, str::to_string)) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_aggregate() {
+    let switch = |timestamp: &str, context: &str, namespace: Option<&str>| Switch {
+        timestamp: timestamp.into(),
+        context: context.into(),
+        namespace: namespace.map(Into::into),
+    };
+    let switches = [
+        switch("2024-05-01T09:00:00Z", "dev", None),
+        switch("2024-05-01T10:00:00Z", "prod", Some("web")),
+        switch("2024-05-01T10:10:00Z", "dev", Some("web")),
+        switch("2024-05-01T10:15:00Z", "prod", Some("web")),
+    ];
+    let logged = [LoggedEntry {
+        timestamp: "2024-05-01T10:12:00Z".into(),
+        command: "exec".into(),
+        context: "prod".into(),
+    }];
+    let now = parse_time("2024-05-01T10:20:00Z").unwrap();
+    let group = |context: &str| if context == "prod" { "production" } else { "-" }.to_string();
+
+    let stats = aggregate(&switches, &logged, None, now, group);
+    assert_eq!(stats.switches, 4);
+    assert_eq!(
+        stats.contexts["dev"],
+        Usage {
+            switches: 2,
+            seconds: 35 * 60,
+            execs: 0
+        }
+    );
+    assert_eq!(stats.groups["production"].seconds, 15 * 60);
+    assert_eq!(stats.groups["production"].execs, 1);
+    assert_eq!(stats.namespaces.len(), 3);
+
+    let since = parse_time("2024-05-01T10:12:00Z");
+    let stats = aggregate(&switches, &logged, since, now, group);
+    assert_eq!(stats.switches, 1);
+    assert_eq!(stats.contexts["prod"].execs, 1);
+}
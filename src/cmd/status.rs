@@ -0,0 +1,191 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::cmd::format_table;
+use crate::cmd::lint::short_error;
+use crate::cmd::meta::StatusOutput;
+use crate::kubeconfig::{self, KubeConfig};
+use crate::kubectl;
+use crate::openshift;
+use crate::settings::Settings;
+use crate::vault;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Auth {
+    Ok,
+    Failed,
+    Unknown,
+}
+
+/// Status of the cluster of a context.
+#[derive(Debug, Serialize)]
+struct Status {
+    context: String,
+    reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u128>,
+    auth: Auth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Status {
+    fn is_healthy(&self) -> bool {
+        self.reachable && self.auth != Auth::Failed
+    }
+}
+
+/// Whether an error of the cluster tells that it refused the credentials, or that it accepted them but does
+/// not allow the request. The native client and kubectl both name the HTTP status.
+fn auth_from_error(message: &str) -> Option<Auth> {
+    if message.contains("Unauthorized") {
+        Some(Auth::Failed)
+    } else if message.contains("Forbidden") || message.contains("forbidden") {
+        Some(Auth::Ok)
+    } else {
+        None
+    }
+}
+
+/// Ask the server for its version, then list the namespaces to check the credentials, since the version is
+/// usually public.
+fn check(
+    name: &str,
+    kubeconfig: &Result<KubeConfig>,
+    kubectl: Option<&str>,
+    projects: bool,
+    timeout: Duration,
+) -> Status {
+    let mut status = Status {
+        context: name.to_string(),
+        reachable: false,
+        version: None,
+        latency_ms: None,
+        auth: Auth::Unknown,
+        error: None,
+    };
+    let kubeconfig = match kubeconfig {
+        Ok(kubeconfig) => kubeconfig,
+        Err(err) => {
+            status.error = Some(short_error(err));
+            return status;
+        }
+    };
+
+    let start = Instant::now();
+    match kubectl::server_version(kubeconfig, kubectl, timeout) {
+        Ok(version) => {
+            status.reachable = true;
+            status.latency_ms = Some(start.elapsed().as_millis());
+            status.version = Some(version).filter(|v| !v.is_empty());
+        }
+        Err(err) => match auth_from_error(&format!("{err:#}")) {
+            // The server answered, but it does not show its version without credentials.
+            Some(auth) => {
+                status.reachable = true;
+                status.latency_ms = Some(start.elapsed().as_millis());
+                status.auth = auth;
+            }
+            None => {
+                status.error = Some(short_error(&err));
+                return status;
+            }
+        },
+    }
+
+    if status.auth == Auth::Unknown {
+        match kubectl::get_namespaces(kubeconfig, kubectl, projects, timeout) {
+            Ok(_) => status.auth = Auth::Ok,
+            Err(err) => match auth_from_error(&format!("{err:#}")) {
+                Some(auth) => status.auth = auth,
+                None => status.error = Some(short_error(&err)),
+            },
+        }
+    }
+    status
+}
+
+pub fn status(settings: &Settings, context_name: Option<String>, output: StatusOutput) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let pattern = context_name.as_deref().unwrap_or("*");
+    let matching = installed.get_contexts_matching(pattern, settings.behavior.allow_multiple_context_patterns);
+    if matching.is_empty() {
+        bail!("No context matching {}", pattern);
+    }
+
+    let mut kubeconfigs = vec![];
+    for context_src in matching {
+        let name = context_src.item.name.as_str();
+        let kubeconfig = installed
+            .make_kubeconfig_for_context(name, None::<&str>)
+            .and_then(|mut kubeconfig| {
+                vault::inject_credentials(&settings.vault, &mut kubeconfig)?;
+                Ok(kubeconfig)
+            });
+        kubeconfigs.push((name, kubeconfig));
+    }
+
+    let timeout = settings.behavior.namespace_lookup_timeout();
+    let statuses: Vec<Status> = thread::scope(|s| {
+        let handles: Vec<_> = kubeconfigs
+            .iter()
+            .map(|(name, kubeconfig)| {
+                let kubectl = settings.configured_kubectl_for(name);
+                let projects = kubeconfig
+                    .as_ref()
+                    .is_ok_and(|kubeconfig| openshift::is_openshift(settings, kubeconfig));
+                s.spawn(move || check(name, kubeconfig, kubectl.as_deref(), projects, timeout))
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("check panicked")).collect()
+    });
+
+    match output {
+        StatusOutput::Text => {
+            let mut rows = vec![["CONTEXT", "REACHABLE", "VERSION", "LATENCY", "AUTH", "ERROR"].map(String::from)];
+            for status in &statuses {
+                rows.push([
+                    status.context.clone(),
+                    if status.reachable { "yes" } else { "no" }.into(),
+                    status.version.clone().unwrap_or_else(|| "-".into()),
+                    status.latency_ms.map_or("-".into(), |ms| format!("{ms}ms")),
+                    match status.auth {
+                        Auth::Ok => "ok",
+                        Auth::Failed => "failed",
+                        Auth::Unknown => "-",
+                    }
+                    .into(),
+                    status.error.clone().unwrap_or_default(),
+                ]);
+            }
+            for line in format_table(&rows) {
+                println!("{line}");
+            }
+        }
+        StatusOutput::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+    }
+
+    if !statuses.iter().all(Status::is_healthy) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_auth_from_error() {
+    assert_eq!(
+        auth_from_error("Error calling kubectl:\nerror: You must be logged in to the server (Unauthorized)"),
+        Some(Auth::Failed)
+    );
+    assert_eq!(
+        auth_from_error("Could not list namespaces: ApiError: namespaces is forbidden: User \"dev\" cannot list"),
+        Some(Auth::Ok)
+    );
+    assert_eq!(auth_from_error("The cluster did not answer within 5s"), None);
+}
@@ -0,0 +1,36 @@
+use anyhow::{bail, Result};
+
+use crate::settings::Settings;
+use crate::sources::{git, remote};
+
+pub fn sync(settings: &Settings) -> Result<()> {
+    let includes = &settings.configs.include;
+    let urls: Vec<&String> = includes.iter().filter(|include| remote::is_remote(include)).collect();
+    if settings.configs.git_sources.is_empty() && urls.is_empty() {
+        bail!(
+            "No git sources or remote kubeconfigs configured, see configs.git_sources and configs.include in \
+             kubie's config file"
+        );
+    }
+
+    let mut failed = 0;
+    for source in &settings.configs.git_sources {
+        println!("Syncing {}", source.url);
+        if let Err(err) = git::sync(source) {
+            eprintln!("Could not sync {}: {:#}", source.url, err);
+            failed += 1;
+        }
+    }
+    for url in urls {
+        println!("Downloading {url}");
+        if let Err(err) = remote::refresh(&settings.configs.remote, url) {
+            eprintln!("Could not download {url}: {err:#}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} source(s) could not be synced", failed);
+    }
+    Ok(())
+}
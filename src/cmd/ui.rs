@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::cmd::context::{self, fetch_context_namespaces};
+use crate::cmd::meta::ContextCommand;
+use crate::kubeconfig::{self, Installed};
+use crate::settings::Settings;
+use crate::state::State;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Contexts,
+    Namespaces,
+}
+
+enum Namespaces {
+    Loaded(Vec<String>),
+    /// The cluster could not be reached, the namespaces come from the namespace cache.
+    Cached(Vec<String>),
+    Failed(String),
+}
+
+impl Namespaces {
+    fn names(&self) -> &[String] {
+        match self {
+            Namespaces::Loaded(names) | Namespaces::Cached(names) => names,
+            Namespaces::Failed(_) => &[],
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    tags: Vec<String>,
+}
+
+struct App<'a> {
+    settings: &'a Settings,
+    installed: &'a Installed,
+    contexts: Vec<Entry>,
+    namespaces: HashMap<String, Namespaces>,
+    focus: Pane,
+    context_query: String,
+    namespace_query: String,
+    context_list: ListState,
+    namespace_list: ListState,
+    loading: bool,
+}
+
+/// What to do once the interface is closed.
+enum Outcome {
+    Quit,
+    Enter(String, Option<String>),
+}
+
+/// Whether a context matches the search. Words starting with `#` must be tags of the context, the
+/// other words must be found in its name.
+fn matches(name: &str, tags: &[String], query: &str) -> bool {
+    let name = name.to_lowercase();
+    query.split_whitespace().all(|word| match word.strip_prefix('#') {
+        Some(tag) => tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+        None => name.contains(&word.to_lowercase()),
+    })
+}
+
+impl App<'_> {
+    fn filtered_contexts(&self) -> Vec<&Entry> {
+        self.contexts
+            .iter()
+            .filter(|e| matches(&e.name, &e.tags, &self.context_query))
+            .collect()
+    }
+
+    fn filtered_namespaces(&self, context_name: &str) -> Vec<&str> {
+        match self.namespaces.get(context_name) {
+            Some(namespaces) => namespaces
+                .names()
+                .iter()
+                .filter(|n| matches(n, &[], &self.namespace_query))
+                .map(|n| n.as_str())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn selected_context(&self) -> Option<String> {
+        let contexts = self.filtered_contexts();
+        self.context_list
+            .selected()
+            .and_then(|i| contexts.get(i))
+            .map(|e| e.name.clone())
+    }
+
+    fn selected_namespace(&self) -> Option<String> {
+        let context_name = self.selected_context()?;
+        let namespaces = self.filtered_namespaces(&context_name);
+        self.namespace_list
+            .selected()
+            .and_then(|i| namespaces.get(i))
+            .map(|n| n.to_string())
+    }
+
+    fn load_namespaces(&mut self, context_name: &str) {
+        if self.namespaces.contains_key(context_name) {
+            return;
+        }
+        let namespaces = match fetch_context_namespaces(self.settings, self.installed, context_name) {
+            Ok(names) => Namespaces::Loaded(names),
+            Err(err) => match State::load()
+                .ok()
+                .and_then(|s| s.namespace_cache.get(context_name).cloned())
+            {
+                Some(mut names) => {
+                    names.retain(|ns| self.settings.is_namespace_allowed(context_name, ns));
+                    Namespaces::Cached(names)
+                }
+                None => Namespaces::Failed(err.to_string()),
+            },
+        };
+        self.namespaces.insert(context_name.to_string(), namespaces);
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (len, list) = match self.focus {
+            Pane::Contexts => (self.filtered_contexts().len(), &mut self.context_list),
+            Pane::Namespaces => {
+                let len = match self.selected_context() {
+                    Some(name) => self.filtered_namespaces(&name).len(),
+                    None => 0,
+                };
+                (len, &mut self.namespace_list)
+            }
+        };
+        if len == 0 {
+            list.select(None);
+            return;
+        }
+        let current = list.selected().unwrap_or(0) as isize;
+        list.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+        if self.focus == Pane::Contexts {
+            self.namespace_query.clear();
+            self.namespace_list.select(Some(0));
+        }
+    }
+
+    fn query_changed(&mut self) {
+        match self.focus {
+            Pane::Contexts => {
+                self.context_list.select(Some(0));
+                self.namespace_query.clear();
+            }
+            Pane::Namespaces => {}
+        }
+        self.namespace_list.select(Some(0));
+    }
+
+    fn query_mut(&mut self) -> &mut String {
+        match self.focus {
+            Pane::Contexts => &mut self.context_query,
+            Pane::Namespaces => &mut self.namespace_query,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Outcome> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Some(Outcome::Quit),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::Esc if !self.query_mut().is_empty() => {
+                self.query_mut().clear();
+                self.query_changed();
+            }
+            KeyCode::Esc | KeyCode::Left if self.focus == Pane::Namespaces => self.focus = Pane::Contexts,
+            KeyCode::Esc => return Some(Outcome::Quit),
+            KeyCode::Right | KeyCode::Tab if self.focus == Pane::Contexts && self.selected_context().is_some() => {
+                self.focus = Pane::Namespaces;
+                self.loading = true;
+            }
+            KeyCode::BackTab | KeyCode::Tab => self.focus = Pane::Contexts,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Backspace => {
+                self.query_mut().pop();
+                self.query_changed();
+            }
+            KeyCode::Char(c) => {
+                self.query_mut().push(c);
+                self.query_changed();
+            }
+            KeyCode::Enter => {
+                let context_name = self.selected_context()?;
+                return match self.focus {
+                    Pane::Contexts => Some(Outcome::Enter(context_name, None)),
+                    Pane::Namespaces => Some(Outcome::Enter(context_name, Some(self.selected_namespace()?))),
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+        let focused = Style::default().fg(Color::Cyan);
+
+        let items: Vec<ListItem> = self
+            .filtered_contexts()
+            .into_iter()
+            .map(|e| {
+                let mut spans = vec![Span::raw(self.settings.prompt.display_name(&e.name))];
+                for tag in &e.tags {
+                    spans.push(Span::styled(format!(" #{tag}"), Style::default().fg(Color::Yellow)));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title(pane_title("Contexts", &self.context_query));
+        if self.focus == Pane::Contexts {
+            block = block.border_style(focused);
+        }
+        let list = List::new(items).block(block).highlight_style(highlight);
+        frame.render_stateful_widget(list, left, &mut self.context_list);
+
+        let mut title = "Namespaces";
+        let mut block = Block::default().borders(Borders::ALL);
+        if self.focus == Pane::Namespaces {
+            block = block.border_style(focused);
+        }
+        match self.selected_context() {
+            Some(_) if self.loading => {
+                let block = block.title(pane_title(title, &self.namespace_query));
+                frame.render_widget(Paragraph::new("Loading namespaces...").block(block), right);
+            }
+            Some(context_name) => match self.namespaces.get(&context_name) {
+                Some(Namespaces::Failed(err)) => {
+                    let block = block.title(pane_title(title, &self.namespace_query));
+                    frame.render_widget(
+                        Paragraph::new(err.as_str()).wrap(Wrap { trim: false }).block(block),
+                        right,
+                    );
+                }
+                Some(namespaces) => {
+                    if let Namespaces::Cached(_) = namespaces {
+                        title = "Namespaces (cached)";
+                    }
+                    let items: Vec<ListItem> = self
+                        .filtered_namespaces(&context_name)
+                        .into_iter()
+                        .map(|n| ListItem::new(n.to_string()))
+                        .collect();
+                    let block = block.title(pane_title(title, &self.namespace_query));
+                    let mut list = List::new(items).block(block);
+                    if self.focus == Pane::Namespaces {
+                        list = list.highlight_style(highlight);
+                    }
+                    frame.render_stateful_widget(list, right, &mut self.namespace_list);
+                }
+                None => {
+                    let block = block.title(pane_title(title, &self.namespace_query));
+                    let text = kubeconfig_details(self.installed, &context_name);
+                    frame.render_widget(Paragraph::new(text).block(block), right);
+                }
+            },
+            None => frame.render_widget(Paragraph::new("").block(block.title(title)), right),
+        }
+
+        let keys = "type to search, #tag to filter by tag | ↑↓ move | →/tab namespaces | enter open | esc back/quit";
+        frame.render_widget(Paragraph::new(keys).style(Style::default().fg(Color::DarkGray)), help);
+    }
+}
+
+fn pane_title(title: &str, query: &str) -> String {
+    if query.is_empty() {
+        format!(" {title} ")
+    } else {
+        format!(" {title} / {query} ")
+    }
+}
+
+fn kubeconfig_details(installed: &Installed, context_name: &str) -> String {
+    let details = installed.describe_context(context_name).unwrap_or_default();
+    format!("{details}\n\nPress → to list the namespaces.")
+}
+
+fn run(terminal: &mut DefaultTerminal, app: &mut App) -> Result<Outcome> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if app.loading {
+            if let Some(context_name) = app.selected_context() {
+                app.load_namespaces(&context_name);
+            }
+            app.loading = false;
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(outcome) = app.handle_key(key) {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+pub fn ui(settings: &Settings) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        bail!("kubie ui requires a terminal");
+    }
+
+    let mut installed = kubeconfig::get_installed_contexts(settings)?;
+    installed.contexts.sort_by(|a, b| a.item.name.cmp(&b.item.name));
+    let mut contexts: Vec<Entry> = installed
+        .contexts
+        .iter()
+        .map(|c| Entry {
+            name: c.item.name.clone(),
+            tags: settings.tags_for(&c.item.name).into_iter().map(String::from).collect(),
+        })
+        .collect();
+    contexts.dedup_by(|a, b| a.name == b.name);
+    if contexts.is_empty() {
+        bail!("No contexts found");
+    }
+
+    let mut app = App {
+        settings,
+        installed: &installed,
+        contexts,
+        namespaces: HashMap::new(),
+        focus: Pane::Contexts,
+        context_query: String::new(),
+        namespace_query: String::new(),
+        context_list: ListState::default().with_selected(Some(0)),
+        namespace_list: ListState::default().with_selected(Some(0)),
+        loading: false,
+    };
+
+    let mut terminal = ratatui::init();
+    let outcome = run(&mut terminal, &mut app);
+    ratatui::restore();
+
+    match outcome? {
+        Outcome::Quit => Ok(()),
+        Outcome::Enter(context_name, namespace_name) => context::context(
+            settings,
+            ContextCommand {
+                context_name: Some(context_name),
+                namespace_name,
+                ..Default::default()
+            },
+        ),
+    }
+}
+
+#[test]
+fn test_matches() {
+    let tags = vec!["prod".to_string(), "eu".to_string()];
+    assert!(matches("eu-prod-1", &tags, ""));
+    assert!(matches("eu-prod-1", &tags, "PROD #eu"));
+    assert!(!matches("eu-prod-1", &tags, "#us"));
+    assert!(!matches("eu-prod-1", &tags, "staging"));
+}
@@ -0,0 +1,70 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Result};
+
+use crate::cmd::meta::ContextCommand;
+use crate::cmd::{confirm, context};
+use crate::project::{self, ProjectFile};
+use crate::settings::Settings;
+use crate::shell::EvalShell;
+use crate::state::State;
+
+/// Make sure the user trusts the project file, showing its contents when it is unknown or was modified.
+fn confirm_trust(file: &ProjectFile, trust: bool) -> Result<()> {
+    let key = file.key();
+    let digest = file.digest();
+    let state = State::load()?;
+    if file.is_trusted(&state) {
+        return Ok(());
+    }
+
+    if !trust {
+        if !io::stdin().is_terminal() {
+            bail!("{} is not trusted, run `kubie up` in a terminal to review it", key);
+        }
+        if state.trusted_projects.contains_key(&key) {
+            eprintln!("{key} was modified since it was trusted:");
+        } else {
+            eprintln!("{key} is not trusted yet:");
+        }
+        for line in file.contents.lines() {
+            eprintln!("    {line}");
+        }
+        if !confirm("Trust it?")? {
+            bail!("{} was not trusted", key);
+        }
+    }
+
+    State::modify(move |state| {
+        state.trusted_projects.insert(key, digest);
+        Ok(())
+    })
+}
+
+pub fn up(settings: &Settings, recursive: bool, trust: bool, quiet: bool, eval: Option<EvalShell>) -> Result<()> {
+    let dir = env::current_dir()?;
+    let path = match project::find(&dir) {
+        Some(path) => path,
+        None if quiet => return Ok(()),
+        None => bail!(
+            "No {} file found in {} or its parents",
+            project::FILE_NAME,
+            dir.display()
+        ),
+    };
+
+    let file = ProjectFile::load(&path)?;
+    confirm_trust(&file, trust)?;
+
+    context::context(
+        settings,
+        ContextCommand {
+            context_name: Some(file.project.context),
+            namespace_name: file.project.namespace,
+            recursive,
+            eval,
+            ..Default::default()
+        },
+    )
+}
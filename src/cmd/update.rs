@@ -1,26 +1,46 @@
+use std::cmp::Ordering;
 use std::env;
 use std::fs;
-use std::fs::Permissions;
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use cfg_if::cfg_if;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::settings::{Settings, UpdateChannel};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/sbstp/kubie/releases/latest";
+const RELEASES_URL: &str = "https://api.github.com/repos/sbstp/kubie/releases";
+
+/// Assets listing the SHA-256 checksums of every binary of a release, besides `<binary>.sha256` assets.
+const CHECKSUM_ASSETS: &[&str] = &["checksums.txt", "SHA256SUMS"];
 
 #[derive(Debug, Deserialize)]
 pub struct Release {
     tag_name: String,
+    #[serde(default)]
+    draft: bool,
     assets: Vec<Asset>,
 }
 
 impl Release {
-    pub fn get_latest() -> Result<Release> {
-        let latest_release = attohttpc::get(LATEST_RELEASE_URL).send()?.json()?;
-        Ok(latest_release)
+    pub fn get_latest(channel: UpdateChannel) -> Result<Release> {
+        match channel {
+            UpdateChannel::Stable => {
+                let latest_release = attohttpc::get(LATEST_RELEASE_URL).send()?.json()?;
+                Ok(latest_release)
+            }
+            UpdateChannel::Prerelease => {
+                // Releases are listed from the most recent one, pre-releases included.
+                let releases: Vec<Release> = attohttpc::get(RELEASES_URL).send()?.json()?;
+                releases
+                    .into_iter()
+                    .find(|release| !release.draft)
+                    .context("Could not find any release")
+            }
+        }
     }
 
     // Get the right binary name based on which OS and architecture kubie was built-on.
@@ -47,22 +67,123 @@ impl Release {
         let binary_name = Self::get_binary_name()?;
 
         for asset in self.assets.iter() {
-            if asset.browser_download_url.contains(binary_name) {
+            if asset.name == binary_name {
                 return Some(&asset.browser_download_url);
             }
         }
         None
     }
+
+    /// Expected SHA-256 checksum of the binary, read from the checksum assets of the release.
+    fn get_checksum(&self, binary_name: &str) -> Result<Option<String>> {
+        let checksum_name = format!("{binary_name}.sha256");
+        for asset in self.assets.iter() {
+            if asset.name == checksum_name || CHECKSUM_ASSETS.contains(&asset.name.as_str()) {
+                let text = attohttpc::get(&asset.browser_download_url)
+                    .send()?
+                    .error_for_status()?
+                    .text()?;
+                if let Some(checksum) = find_checksum(&text, binary_name, asset.name == checksum_name) {
+                    return Ok(Some(checksum));
+                }
+            }
+        }
+        Ok(None)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Asset {
+    name: String,
     browser_download_url: String,
 }
 
-pub fn update() -> Result<()> {
-    let latest_release = Release::get_latest()?;
-    if latest_release.tag_name == format!("v{VERSION}") {
+/// Result of `kubie update --check`.
+#[derive(Debug, Serialize)]
+struct Check<'a> {
+    current: String,
+    latest: &'a str,
+    channel: UpdateChannel,
+    update_available: bool,
+}
+
+/// Find the checksum of the binary in a file in the `sha256sum` format. A checksum without a file name is
+/// only accepted from the `<binary>.sha256` asset of the binary, with `own_asset`, since the checksum of
+/// another binary could be taken from a list.
+fn find_checksum(text: &str, binary_name: &str, own_asset: bool) -> Option<String> {
+    text.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let checksum = parts.next()?;
+        match parts.next() {
+            None if own_asset => Some(checksum.to_lowercase()),
+            None => None,
+            Some(name) if name.trim_start_matches('*') == binary_name => Some(checksum.to_lowercase()),
+            Some(_) => None,
+        }
+    })
+}
+
+/// Numbers of a version such as `v0.28.0`, and the identifiers of its pre-release part, with numeric
+/// identifiers ordered before the other ones.
+type Version<'a> = (Vec<u64>, Option<Vec<(u8, u64, &'a str)>>);
+
+fn parse_version(version: &str) -> Option<Version<'_>> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let numbers = numbers
+        .split('.')
+        .map(|n| n.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let pre = pre.map(|pre| {
+        pre.split('.')
+            .map(|id| match id.parse() {
+                Ok(n) => (0, n, ""),
+                Err(_) => (1, 0, id),
+            })
+            .collect()
+    });
+    Some((numbers, pre))
+}
+
+/// Whether the `latest` version is newer than the `current` one. A release is newer than its pre-releases.
+fn is_newer(latest: &str, current: &str) -> bool {
+    let (Some((latest_numbers, latest_pre)), Some((current_numbers, current_pre))) =
+        (parse_version(latest), parse_version(current))
+    else {
+        return latest.trim_start_matches('v') != current.trim_start_matches('v');
+    };
+    match latest_numbers.cmp(&current_numbers) {
+        Ordering::Equal => match (latest_pre, current_pre) {
+            (None, Some(_)) => true,
+            (Some(latest_pre), Some(current_pre)) => latest_pre > current_pre,
+            _ => false,
+        },
+        ordering => ordering == Ordering::Greater,
+    }
+}
+
+pub fn update(settings: &Settings, check: bool) -> Result<()> {
+    let latest_release = Release::get_latest(settings.update.channel)?;
+    let update_available = is_newer(&latest_release.tag_name, VERSION);
+
+    if check {
+        let result = Check {
+            current: format!("v{VERSION}"),
+            latest: &latest_release.tag_name,
+            channel: settings.update.channel,
+            update_available,
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        if update_available {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if !update_available {
         println!("Kubie is up-to-date : v{VERSION}");
     } else {
         println!(
@@ -71,12 +192,34 @@ pub fn update() -> Result<()> {
         );
 
         let download_url = latest_release.get_binary_url().context("Sorry, this release has no build for your OS, please create an issue : https://github.com/sbstp/kubie/issues")?;
+        let binary_name = Release::get_binary_name().expect("release has a binary for this platform");
         println!("Download url is: {download_url}");
 
         let resp = attohttpc::get(download_url).send()?;
         if resp.is_success() {
+            let binary = resp.bytes()?;
+            let checksum: String = Sha256::digest(&binary).iter().map(|b| format!("{b:02x}")).collect();
+            match latest_release.get_checksum(binary_name)? {
+                Some(expected) if expected == checksum => println!("Checksum verified: {checksum}"),
+                Some(expected) => bail!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    download_url,
+                    expected,
+                    checksum
+                ),
+                None if settings.update.require_checksum => bail!(
+                    "Release {} has no checksum for {}, it was not installed because update.require_checksum is set",
+                    latest_release.tag_name,
+                    binary_name
+                ),
+                None => eprintln!(
+                    "Warning: release {} has no checksum, the binary could not be verified",
+                    latest_release.tag_name
+                ),
+            }
+
             let temp_file = tempfile::Builder::new().prefix("kubie").tempfile()?;
-            resp.write_to(&temp_file)?;
+            fs::write(temp_file.path(), &binary)?;
 
             let old_file = env::current_exe().expect("Could not get own binary path");
             replace_file(&old_file, temp_file.path()).context("Update failed. Consider using sudo?")?;
@@ -88,7 +231,13 @@ pub fn update() -> Result<()> {
 }
 
 pub fn replace_file(old_file: &Path, new_file: &Path) -> std::io::Result<()> {
-    fs::set_permissions(new_file, Permissions::from_mode(0o755))?;
+    #[cfg(unix)]
+    {
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(new_file, Permissions::from_mode(0o755))?;
+    }
     fs::remove_file(old_file)?;
     fs::copy(new_file, old_file)?;
     Ok(())
@@ -111,3 +260,26 @@ fn test_binary_name() {
 fn test_binary_name() {
     assert_eq!(Release::get_binary_name(), Some("kubie-darwin-arm64"))
 }
+
+#[test]
+fn test_is_newer() {
+    assert!(is_newer("v0.29.0", "0.28.0"));
+    assert!(is_newer("v0.28.10", "0.28.9"));
+    assert!(!is_newer("v0.28.0", "0.28.0"));
+    assert!(!is_newer("v0.27.5", "0.28.0"));
+    assert!(is_newer("v0.29.0-rc.10", "0.29.0-rc.9"));
+    assert!(is_newer("v0.29.0", "0.29.0-rc.1"));
+    assert!(!is_newer("v0.29.0-rc.1", "0.29.0"));
+}
+
+#[test]
+fn test_find_checksum() {
+    let sums = "0123abcd  kubie-darwin-arm64\nABCDEF01 *kubie-linux-amd64\n";
+    assert_eq!(find_checksum(sums, "kubie-linux-amd64", false), Some("abcdef01".into()));
+    assert_eq!(find_checksum(sums, "kubie-linux-arm64", false), None);
+    assert_eq!(
+        find_checksum("0123abcd\n", "kubie-linux-amd64", true),
+        Some("0123abcd".into())
+    );
+    assert_eq!(find_checksum("0123abcd\n", "kubie-linux-amd64", false), None);
+}
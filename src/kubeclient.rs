@@ -0,0 +1,98 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::api::core::v1::Namespace;
+use kube::api::{ApiResource, DynamicObject, ListParams, ObjectMeta, PostParams};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{Api, Client, Config};
+
+/// Run a request against the cluster of a kubeconfig file, without requiring kubectl. The request fails
+/// when the cluster does not answer within the timeout.
+fn block_on<T>(timeout: Duration, future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            match tokio::time::timeout(timeout, future).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!("The cluster did not answer within {}s", timeout.as_secs_f32())),
+            }
+        })
+}
+
+async fn client(path: &Path) -> Result<Client> {
+    let kubeconfig = Kubeconfig::read_from(path).context("Could not read kubeconfig")?;
+    let config = Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default()).await?;
+    Ok(Client::try_from(config)?)
+}
+
+pub fn get_namespaces(path: &Path, timeout: Duration) -> Result<Vec<String>> {
+    block_on(timeout, async {
+        let api: Api<Namespace> = Api::all(client(path).await?);
+        let list = api
+            .list_metadata(&ListParams::default())
+            .await
+            .context("Could not list namespaces")?;
+        Ok(list.items.into_iter().filter_map(|n| n.metadata.name).collect())
+    })
+}
+
+pub fn create_namespace(path: &Path, namespace_name: &str, timeout: Duration) -> Result<()> {
+    block_on(timeout, async {
+        let api: Api<Namespace> = Api::all(client(path).await?);
+        let namespace = Namespace {
+            metadata: ObjectMeta {
+                name: Some(namespace_name.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        api.create(&PostParams::default(), &namespace)
+            .await
+            .context("Could not create namespace")?;
+        Ok(())
+    })
+}
+
+/// A resource of the `project.openshift.io` group of OpenShift clusters.
+fn openshift_resource(kind: &str, plural: &str) -> ApiResource {
+    ApiResource {
+        group: "project.openshift.io".into(),
+        version: "v1".into(),
+        api_version: "project.openshift.io/v1".into(),
+        kind: kind.into(),
+        plural: plural.into(),
+    }
+}
+
+/// List the projects of an OpenShift cluster, the namespaces which the user can access.
+pub fn get_projects(path: &Path, timeout: Duration) -> Result<Vec<String>> {
+    block_on(timeout, async {
+        let api: Api<DynamicObject> = Api::all_with(client(path).await?, &openshift_resource("Project", "projects"));
+        let list = api
+            .list(&ListParams::default())
+            .await
+            .context("Could not list projects")?;
+        Ok(list.items.into_iter().filter_map(|p| p.metadata.name).collect())
+    })
+}
+
+/// Create a project in an OpenShift cluster, whose users request projects instead of creating namespaces.
+pub fn create_project(path: &Path, project_name: &str, timeout: Duration) -> Result<()> {
+    block_on(timeout, async {
+        let resource = openshift_resource("ProjectRequest", "projectrequests");
+        let api: Api<DynamicObject> = Api::all_with(client(path).await?, &resource);
+        api.create(&PostParams::default(), &DynamicObject::new(project_name, &resource))
+            .await
+            .context("Could not create project")?;
+        Ok(())
+    })
+}
+
+pub fn server_version(path: &Path, timeout: Duration) -> Result<String> {
+    block_on(timeout, async {
+        let version = client(path).await?.apiserver_version().await?;
+        Ok(version.git_version)
+    })
+}
@@ -1,31 +1,172 @@
 use std::env;
-use std::process::Command;
-use std::str;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::{anyhow, Context};
+use anyhow::Context;
+use cfg_if::cfg_if;
+use tempfile::TempDir;
 
+use crate::ephemeral;
 use crate::kubeconfig::KubeConfig;
+use crate::settings::Settings;
+use crate::shell;
 
-pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyhow::Result<Vec<String>> {
-    let mut cmd = Command::new("kubectl");
-    cmd.arg("get");
-    cmd.arg("namespaces");
+/// Call `f` with the path of a file containing the given kubeconfig, or of the current kubie shell's
+/// kubeconfig.
+fn with_kubeconfig_path<'a, T>(
+    kubeconfig: impl Into<Option<&'a KubeConfig>>,
+    f: impl FnOnce(&Path) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    match kubeconfig.into() {
+        Some(kubeconfig) => {
+            let temp_config_file = ephemeral::tempfile("config", ".yaml")?;
+            kubeconfig.write_to_file(temp_config_file.path())?;
+            f(temp_config_file.path())
+        }
+        None => f(&PathBuf::from(
+            env::var("KUBIE_KUBECONFIG").context("KUBIE_KUBECONFIG variable is not set")?,
+        )),
+    }
+}
 
-    let temp_config_file;
+/// List the namespaces of the given kubeconfig, or of the current kubie shell. The cluster is queried with
+/// the configured kubectl executable, if any, and otherwise with the native client when kubie is built with
+/// it or with `kubectl`. With `projects`, the OpenShift projects are listed instead, since the users of
+/// OpenShift clusters usually cannot list the namespaces.
+pub fn get_namespaces<'a>(
+    kubeconfig: impl Into<Option<&'a KubeConfig>>,
+    kubectl: Option<&str>,
+    projects: bool,
+    timeout: Duration,
+) -> anyhow::Result<Vec<String>> {
+    with_kubeconfig_path(kubeconfig, |path| {
+        if let Some(kubectl) = kubectl {
+            return get_namespaces_with_kubectl(path, kubectl, projects, timeout);
+        }
+        cfg_if! {
+            if #[cfg(feature = "native-client")] {
+                if projects {
+                    crate::kubeclient::get_projects(path, timeout)
+                } else {
+                    crate::kubeclient::get_namespaces(path, timeout)
+                }
+            } else {
+                get_namespaces_with_kubectl(path, "kubectl", projects, timeout)
+            }
+        }
+    })
+}
 
-    if let Some(kubeconfig) = kubeconfig.into() {
-        temp_config_file = tempfile::Builder::new()
-            .prefix("kubie-config")
-            .suffix(".yaml")
-            .tempfile()?;
-        kubeconfig.write_to_file(temp_config_file.path())?;
-        cmd.env("KUBECONFIG", temp_config_file.path());
-    } else {
-        cmd.env(
-            "KUBECONFIG",
-            env::var("KUBIE_KUBECONFIG").context("KUBIE_KUBECONFIG variable is not set")?,
-        );
+/// Create a namespace in the cluster of the given kubeconfig, or of the current kubie shell, like
+/// `get_namespaces` queries it. With `projects`, an OpenShift project is requested instead.
+pub fn create_namespace<'a>(
+    kubeconfig: impl Into<Option<&'a KubeConfig>>,
+    kubectl: Option<&str>,
+    namespace_name: &str,
+    projects: bool,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    with_kubeconfig_path(kubeconfig, |path| {
+        if let Some(kubectl) = kubectl {
+            return create_namespace_with_kubectl(path, kubectl, namespace_name, projects, timeout);
+        }
+        cfg_if! {
+            if #[cfg(feature = "native-client")] {
+                if projects {
+                    crate::kubeclient::create_project(path, namespace_name, timeout)
+                } else {
+                    crate::kubeclient::create_namespace(path, namespace_name, timeout)
+                }
+            } else {
+                create_namespace_with_kubectl(path, "kubectl", namespace_name, projects, timeout)
+            }
+        }
+    })
+}
+
+/// Ask the server of the given kubeconfig for its version, like `get_namespaces` queries it.
+pub fn server_version(kubeconfig: &KubeConfig, kubectl: Option<&str>, timeout: Duration) -> anyhow::Result<String> {
+    with_kubeconfig_path(kubeconfig, |path| {
+        if let Some(kubectl) = kubectl {
+            return server_version_with_kubectl(path, kubectl, timeout);
+        }
+        cfg_if! {
+            if #[cfg(feature = "native-client")] {
+                crate::kubeclient::server_version(path, timeout)
+            } else {
+                server_version_with_kubectl(path, "kubectl", timeout)
+            }
+        }
+    })
+}
+
+/// Variable holding the directory of the kubectl shim of a kubie shell, which runs the kubectl executable
+/// configured for its context.
+pub const SHIM_DIR_VAR: &str = "KUBIE_KUBECTL_SHIM";
+
+/// Create the kubectl shim of a new kubie shell, when kubectl executables are configured. Its directory is
+/// meant to be put first in the `PATH` of the shell.
+#[cfg(unix)]
+pub fn create_shim(settings: &Settings, context_name: &str) -> anyhow::Result<Option<TempDir>> {
+    if !settings.has_kubectl_paths() {
+        return Ok(None);
     }
+    let dir = ephemeral::tempdir("kubectl")?;
+    write_shim(dir.path(), &settings.kubectl_for(context_name))?;
+    Ok(Some(dir))
+}
+
+#[cfg(not(unix))]
+pub fn create_shim(_settings: &Settings, _context_name: &str) -> anyhow::Result<Option<TempDir>> {
+    Ok(None)
+}
+
+/// Write the `kubectl` script of the shim, running the given executable. The executable is resolved without
+/// the shim directory, in which `kubectl` would be the shim itself.
+#[cfg(unix)]
+pub fn write_shim(dir: &Path, kubectl: &str) -> anyhow::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let paths = env::var_os("PATH").unwrap_or_default();
+    let paths = env::join_paths(env::split_paths(&paths).filter(|p| p != dir))?;
+    let executable = which::which_in(kubectl, Some(paths), env::current_dir()?)
+        .with_context(|| format!("Could not find kubectl executable {kubectl}"))?;
+
+    let path = dir.join("kubectl");
+    let script = format!(
+        "#!/bin/sh\nexec {} \"$@\"\n",
+        shell::quote(&executable.to_string_lossy())
+    );
+    fs::write(&path, script)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn write_shim(_dir: &Path, _kubectl: &str) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn get_namespaces_with_kubectl(
+    path: &Path,
+    kubectl: &str,
+    projects: bool,
+    timeout: Duration,
+) -> anyhow::Result<Vec<String>> {
+    use anyhow::anyhow;
+    use std::process::Command;
+    use std::str;
+
+    let resource = match projects {
+        true => "projects.project.openshift.io",
+        false => "namespaces",
+    };
+    let mut cmd = Command::new(kubectl);
+    cmd.arg("get");
+    cmd.arg(resource);
+    cmd.arg(format!("--request-timeout={}s", timeout.as_secs()));
+    cmd.env("KUBECONFIG", path);
 
     let result = cmd.output()?;
     if !result.status.success() {
@@ -42,3 +183,68 @@ pub fn get_namespaces<'a>(kubeconfig: impl Into<Option<&'a KubeConfig>>) -> anyh
 
     Ok(namespaces)
 }
+
+fn create_namespace_with_kubectl(
+    path: &Path,
+    kubectl: &str,
+    namespace_name: &str,
+    projects: bool,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    use anyhow::anyhow;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::str;
+
+    let mut cmd = Command::new(kubectl);
+    cmd.arg("create");
+    if projects {
+        // `oc new-project` requests the project the same way.
+        cmd.arg("--filename=-").stdin(Stdio::piped());
+    } else {
+        cmd.arg("namespace").arg(namespace_name);
+    }
+    let mut child = cmd
+        .arg(format!("--request-timeout={}s", timeout.as_secs()))
+        .env("KUBECONFIG", path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let request = serde_json::json!({
+            "apiVersion": "project.openshift.io/v1",
+            "kind": "ProjectRequest",
+            "metadata": { "name": namespace_name },
+        });
+        stdin.write_all(request.to_string().as_bytes())?;
+    }
+    let result = child.wait_with_output()?;
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of kubectl as utf-8");
+        return Err(anyhow!("Error calling kubectl:\n{}", stderr));
+    }
+    Ok(())
+}
+
+fn server_version_with_kubectl(path: &Path, kubectl: &str, timeout: Duration) -> anyhow::Result<String> {
+    use anyhow::anyhow;
+    use std::process::Command;
+    use std::str;
+
+    let result = Command::new(kubectl)
+        .arg("version")
+        .arg("--output=json")
+        .arg(format!("--request-timeout={}s", timeout.as_secs()))
+        .env("KUBECONFIG", path)
+        .output()?;
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of kubectl as utf-8");
+        return Err(anyhow!("Error calling kubectl:\n{}", stderr));
+    }
+
+    let version: serde_json::Value = serde_json::from_slice(&result.stdout)?;
+    version["serverVersion"]["gitVersion"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("kubectl did not print the server version"))
+}
@@ -0,0 +1,119 @@
+//! Pre-flight check of the exec credential plugin of a context, such as `aws eks get-token`, running the login
+//! command configured for the context when its credentials have expired.
+
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::hooks::{self, HookEnv};
+use crate::kubeconfig::KubeConfig;
+use crate::output::note;
+use crate::settings::Settings;
+use crate::vars;
+
+/// How long the exec plugin may take to print credentials before they are considered expired.
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Whether the output of an exec plugin is missing credentials or has expired ones.
+fn is_expired(output: &[u8], now: OffsetDateTime) -> bool {
+    let Ok(credential) = serde_json::from_slice::<serde_json::Value>(output) else {
+        return true;
+    };
+    match credential["status"]["expirationTimestamp"].as_str() {
+        Some(timestamp) => OffsetDateTime::parse(timestamp, &Rfc3339).map_or(true, |expiration| expiration <= now),
+        None => credential["status"].is_null(),
+    }
+}
+
+/// Run the exec plugin of the user non-interactively, and tell whether it printed valid credentials.
+fn has_valid_credentials(exec: &Mapping) -> bool {
+    let Some(command) = exec.get("command").and_then(Value::as_str) else {
+        return true;
+    };
+    let mut cmd = Command::new(command);
+    if let Some(args) = exec.get("args").and_then(Value::as_sequence) {
+        cmd.args(args.iter().filter_map(Value::as_str));
+    }
+    for var in exec.get("env").and_then(Value::as_sequence).into_iter().flatten() {
+        if let (Some(name), Some(value)) = (var["name"].as_str(), var["value"].as_str()) {
+            cmd.env(name, value);
+        }
+    }
+    let exec_info = json!({
+        "apiVersion": exec.get("apiVersion").and_then(Value::as_str).unwrap_or_default(),
+        "kind": "ExecCredential",
+        "spec": { "interactive": false },
+    });
+    cmd.env("KUBERNETES_EXEC_INFO", exec_info.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    let Ok(mut child) = cmd.spawn() else {
+        // A missing plugin is not fixed by logging in, and is reported by kubectl.
+        return true;
+    };
+    let deadline = Instant::now() + PLUGIN_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+    match child.wait_with_output() {
+        Ok(output) => output.status.success() && !is_expired(&output.stdout, OffsetDateTime::now_utc()),
+        Err(_) => false,
+    }
+}
+
+/// Run the login command of the context when the credentials of its exec plugin have expired. Contexts
+/// without a login command are not checked, since checking runs the plugin.
+pub fn ensure_credentials(settings: &Settings, kubeconfig: &KubeConfig) -> Result<()> {
+    let context_name = &kubeconfig.contexts[0].name;
+    let Some(login) = settings.login_for(context_name) else {
+        return Ok(());
+    };
+    let Some(exec) = kubeconfig.users[0].user.get("exec").and_then(Value::as_mapping) else {
+        return Ok(());
+    };
+    if has_valid_credentials(exec) {
+        return Ok(());
+    }
+
+    note!("Credentials of context {context_name} have expired, logging in.");
+    let env = HookEnv {
+        context: context_name.clone(),
+        namespace: kubeconfig.contexts[0].context.namespace.clone(),
+        depth: vars::get_depth(),
+        ..Default::default()
+    };
+    let status = hooks::run(login, &env, &[])?;
+    if !status.success() {
+        bail!("Login command of context {} exited with {}", context_name, status);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_is_expired() {
+    let now = OffsetDateTime::parse("2024-05-01T12:00:00Z", &Rfc3339).unwrap();
+    let credential = |expiration: &str| {
+        format!(r#"{{"kind":"ExecCredential","status":{{"token":"t","expirationTimestamp":"{expiration}"}}}}"#)
+    };
+    assert!(!is_expired(credential("2024-05-01T12:15:00Z").as_bytes(), now));
+    assert!(is_expired(credential("2024-05-01T11:59:00Z").as_bytes(), now));
+    assert!(!is_expired(br#"{"kind":"ExecCredential","status":{"token":"t"}}"#, now));
+    assert!(is_expired(br#"{"kind":"ExecCredential"}"#, now));
+    assert!(is_expired(b"Error: the SSO session has expired", now));
+}
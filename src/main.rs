@@ -1,91 +1,205 @@
 use anyhow::Result;
 use clap::Parser;
 
-use cmd::meta::Kubie;
-use settings::Settings;
+use cmd::meta::{Cli, Kubie};
+use kubie_lib::settings::Settings;
+// The modules of the library are used through `crate::` paths like the modules of the binary.
+use kubie_lib::{discovery, ioutil, kubeconfig, project, session, settings, sources, state, vars};
 
+mod audit;
+mod clipboard;
 mod cmd;
-mod ioutil;
-mod kubeconfig;
+mod credentials;
+mod ephemeral;
+mod hooks;
+#[cfg(feature = "native-client")]
+mod kubeclient;
 mod kubectl;
-mod session;
-mod settings;
+mod login;
+mod notify;
+#[cfg(feature = "oidc")]
+mod oidc;
+mod openshift;
+mod output;
+mod registry;
 mod shell;
 mod skim;
-mod state;
-mod vars;
+mod static_menu;
+mod tmux;
+mod vault;
 
 fn main() -> Result<()> {
+    let cli = Cli::parse();
+    output::init(cli.no_color, cli.quiet);
+    let kubie = cli.command;
+
+    // Loading the settings fails on some of the problems reported by check-config.
+    if let Kubie::CheckConfig { file } = &kubie {
+        return cmd::check_config::check_config(file.as_deref());
+    }
+    if let Kubie::EditConfig = &kubie {
+        return cmd::edit::edit_config();
+    }
+    if let Kubie::Init { yes, force } = &kubie {
+        return cmd::init::init(*yes, *force);
+    }
+
+    // Run by kubectl for every request, it must start quickly.
+    if let Kubie::CredentialHelper { key, command } = &kubie {
+        return credentials::helper(key, command);
+    }
+    #[cfg(feature = "oidc")]
+    if let Kubie::OidcToken { provider } = &kubie {
+        return oidc::token(provider);
+    }
+
     let settings = Settings::load()?;
+    ephemeral::init(&settings);
 
-    let kubie = Kubie::parse();
+    // Best-effort cleanup of the temporary files of crashed kubie processes, by the commands which create
+    // such files. The others, such as `kubie info prompt`, may run on every prompt of the shell.
+    if matches!(
+        kubie,
+        Kubie::Context(_)
+            | Kubie::Shell { .. }
+            | Kubie::Ui
+            | Kubie::Namespace { .. }
+            | Kubie::Exec(_)
+            | Kubie::Up { .. }
+            | Kubie::Export(_)
+    ) {
+        ephemeral::prune(false);
+    }
 
     match kubie {
-        Kubie::Context {
+        Kubie::Context(cmd) => {
+            cmd::context::context(&settings, cmd)?;
+        }
+        Kubie::Shell { yes } => {
+            cmd::context::last_context(&settings, yes)?;
+        }
+        Kubie::Ui => {
+            cmd::ui::ui(&settings)?;
+        }
+        Kubie::Namespace { history: true, .. } => {
+            cmd::namespace::history()?;
+        }
+        Kubie::Namespace {
+            all_contexts: Some(pattern),
             namespace_name,
+            ..
+        } => {
+            cmd::namespace::list_all_contexts(&settings, &pattern, namespace_name.as_deref())?;
+        }
+        Kubie::Namespace {
+            list: true,
             context_name,
-            kubeconfigs,
-            recursive,
+            ..
         } => {
-            cmd::context::context(
-                &settings,
-                context_name,
-                namespace_name,
-                kubeconfigs,
-                recursive,
-            )?;
+            cmd::namespace::list(&settings, context_name)?;
         }
         Kubie::Namespace {
             namespace_name,
             recursive,
             unset,
+            create,
+            ..
         } => {
-            cmd::namespace::namespace(&settings, namespace_name, recursive, unset)?;
+            cmd::namespace::namespace(&settings, namespace_name, recursive, unset, create)?;
         }
         Kubie::Info(info) => {
-            cmd::info::info(info)?;
+            cmd::info::info(&settings, info)?;
         }
-        Kubie::Exec {
-            context_name,
-            namespace_name,
-            exit_early,
-            context_headers_flag,
-            args,
+        Kubie::Exec(cmd) => {
+            cmd::exec::exec(&settings, cmd)?;
+        }
+        Kubie::Lint {
+            online,
+            expiry_days,
+            output,
+            fail_on,
+            fix,
+            yes,
         } => {
-            cmd::exec::exec(
-                &settings,
-                context_name,
-                namespace_name,
-                exit_early,
-                context_headers_flag,
-                args,
-            )?;
-        }
-        Kubie::Lint => {
-            cmd::lint::lint(&settings)?;
+            cmd::lint::lint(&settings, online, expiry_days, output, &fail_on, fix, yes)?;
         }
         Kubie::Edit { context_name } => {
             cmd::edit::edit_context(&settings, context_name)?;
         }
-        Kubie::EditConfig => {
-            cmd::edit::edit_config(&settings)?;
+        Kubie::CheckConfig { .. } => unreachable!("the config is checked before the settings are loaded"),
+        Kubie::EditConfig => unreachable!("the config is edited before the settings are loaded"),
+        Kubie::Init { .. } => unreachable!("the config is written before the settings are loaded"),
+        Kubie::CredentialHelper { .. } => unreachable!("the credential helper runs before the settings are loaded"),
+        #[cfg(feature = "oidc")]
+        Kubie::Login { context_name } => {
+            oidc::login(&settings, &context_name)?;
         }
+        #[cfg(feature = "oidc")]
+        Kubie::OidcToken { .. } => unreachable!("the token is printed before the settings are loaded"),
         #[cfg(feature = "update")]
-        Kubie::Update => {
-            cmd::update::update()?;
+        Kubie::Update { check } => {
+            cmd::update::update(&settings, check)?;
+        }
+        Kubie::Sync => {
+            cmd::sync::sync(&settings)?;
+        }
+        Kubie::Up { recursive, trust, eval } => {
+            cmd::up::up(&settings, recursive, trust, cli.quiet, eval)?;
+        }
+        Kubie::Status { context_name, output } => {
+            cmd::status::status(&settings, context_name, output)?;
+        }
+        Kubie::Sessions(sessions) => {
+            cmd::sessions::sessions(&settings, sessions)?;
+        }
+        Kubie::History { stats, limit } => {
+            cmd::history::history(&settings, stats, limit)?;
+        }
+        Kubie::Stats {
+            since,
+            group_by,
+            output,
+        } => {
+            cmd::stats::stats(&settings, since, group_by, output)?;
         }
-        Kubie::Delete { context_name } => {
-            cmd::delete::delete_context(&settings, context_name)?;
+        Kubie::Prune { dry_run, expired, yes } => {
+            cmd::prune::prune(&settings, dry_run, expired, yes)?;
         }
-        Kubie::Export {
+        Kubie::Session(session) => {
+            cmd::session::session(&settings, session)?;
+        }
+        Kubie::Delete {
+            prune_orphans,
+            dry_run,
+            context_names,
+        } => {
+            cmd::delete::delete_contexts(&settings, context_names, prune_orphans, dry_run)?;
+        }
+        Kubie::Copy {
             context_name,
-            namespace_name,
+            output,
+            clipboard,
         } => {
-            cmd::export::export(&settings, context_name, namespace_name)?;
+            cmd::copy::copy(&settings, context_name, output, clipboard)?;
+        }
+        Kubie::Rename { context_name, new_name } => {
+            cmd::rename::rename_context(&settings, context_name, new_name)?;
+        }
+        Kubie::Which { pattern, all } => {
+            cmd::which::which(&settings, pattern, all)?;
+        }
+        Kubie::Export(cmd) => {
+            cmd::export::export(&settings, cmd)?;
+        }
+        Kubie::Hook { shell } => {
+            shell::eval::print_hook(shell);
         }
         Kubie::GenerateCompletion(cmd) => {
             cmd::meta::generate_completion(cmd);
         }
+        Kubie::Plugin(args) => {
+            cmd::plugin::plugin(args)?;
+        }
     }
 
     Ok(())
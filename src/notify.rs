@@ -0,0 +1,26 @@
+//! Desktop notifications, sent with notify-rust to the notification server of Linux and the BSDs, the
+//! Notification Center of macOS, and the action center of Windows.
+
+use anyhow::Result;
+
+#[cfg(feature = "notify")]
+fn try_send(title: &str, body: &str) -> Result<()> {
+    notify_rust::Notification::new()
+        .appname("kubie")
+        .summary(title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "notify"))]
+fn try_send(_title: &str, _body: &str) -> Result<()> {
+    anyhow::bail!("kubie was built without the notify feature")
+}
+
+/// Show a desktop notification, printing a warning when it cannot be shown.
+pub fn send(title: &str, body: &str) {
+    if let Err(err) = try_send(title, body) {
+        eprintln!("Warning: could not send the notification: {err:#}");
+    }
+}
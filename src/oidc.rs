@@ -0,0 +1,427 @@
+//! Login to clusters authenticating their users with OIDC, such as with Dex or Keycloak, using the device
+//! authorization flow. The tokens are kept in the data directory of kubie. The kubeconfig of the contexts
+//! enabled in the `oidc` settings runs `kubie oidc-token` to print them instead of kubelogin, refreshing
+//! them when they expire.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+
+use anyhow::{bail, Context, Result};
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use sha2::{Digest, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::ioutil;
+use crate::kubeconfig::{self, KubeConfig};
+use crate::settings::{Oidc, Settings};
+use crate::state;
+
+/// Tokens expiring sooner than this are refreshed, so that they do not expire during a command.
+const EXPIRY_MARGIN: Duration = Duration::seconds(60);
+
+/// How long to wait for each request to the OIDC provider.
+const REQUEST_TIMEOUT: StdDuration = StdDuration::from_secs(15);
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// An OIDC provider, along with the client as which kubie logs in.
+#[derive(Debug, Clone, PartialEq, Eq, clap::Args)]
+pub struct Provider {
+    /// URL of the issuer, whose configuration is at `/.well-known/openid-configuration`.
+    #[clap(long = "issuer-url")]
+    pub issuer_url: String,
+    #[clap(long = "client-id")]
+    pub client_id: String,
+    #[clap(long = "client-secret")]
+    pub client_secret: Option<String>,
+    /// Scopes requested along with `openid` and `offline_access`.
+    #[clap(long = "scope")]
+    pub scopes: Vec<String>,
+}
+
+/// Values of an option of a command line, given either as `--name=value` or as `--name value`.
+fn option_values<'a>(args: &[&'a str], name: &str) -> Vec<&'a str> {
+    let mut values = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix(name) {
+            Some("") => values.extend(args.next()),
+            Some(value) if value.starts_with('=') => values.push(&value[1..]),
+            _ => {}
+        }
+    }
+    values
+}
+
+impl Provider {
+    /// The provider of a kubeconfig user running kubelogin, or using the `oidc` auth provider.
+    pub fn from_user(user: &Mapping) -> Option<Provider> {
+        if let Some(auth_provider) = user.get("auth-provider") {
+            if auth_provider["name"].as_str() != Some("oidc") {
+                return None;
+            }
+            let config = &auth_provider["config"];
+            return Some(Provider {
+                issuer_url: config["idp-issuer-url"].as_str()?.into(),
+                client_id: config["client-id"].as_str()?.into(),
+                client_secret: config["client-secret"].as_str().map(Into::into),
+                scopes: config["extra-scopes"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|scope| !scope.is_empty())
+                    .map(Into::into)
+                    .collect(),
+            });
+        }
+
+        let args: Vec<&str> = user
+            .get("exec")?
+            .get("args")?
+            .as_sequence()?
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        Some(Provider {
+            issuer_url: option_values(&args, "--oidc-issuer-url").first()?.to_string(),
+            client_id: option_values(&args, "--oidc-client-id").first()?.to_string(),
+            client_secret: option_values(&args, "--oidc-client-secret")
+                .first()
+                .map(|s| s.to_string()),
+            scopes: option_values(&args, "--oidc-extra-scope")
+                .into_iter()
+                .flat_map(|scopes| scopes.split(','))
+                .map(Into::into)
+                .collect(),
+        })
+    }
+
+    /// Arguments of `kubie oidc-token` for this provider.
+    fn args(&self) -> Vec<Value> {
+        let mut args: Vec<Value> = vec!["oidc-token".into()];
+        args.extend(["--issuer-url".into(), self.issuer_url.clone().into()]);
+        args.extend(["--client-id".into(), self.client_id.clone().into()]);
+        if let Some(secret) = &self.client_secret {
+            args.extend(["--client-secret".into(), secret.clone().into()]);
+        }
+        for scope in &self.scopes {
+            args.extend(["--scope".into(), scope.clone().into()]);
+        }
+        args
+    }
+
+    /// Path of the tokens of the provider, named after a digest of the issuer, the client and the scopes.
+    fn tokens_path(&self) -> PathBuf {
+        let key: String = Sha256::digest(format!(
+            "{}\n{}\n{}",
+            self.issuer_url,
+            self.client_id,
+            self.scopes.join(",")
+        ))
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+        state::paths::data_dir().join("oidc").join(format!("{key}.json"))
+    }
+
+    fn scope(&self) -> String {
+        let mut scopes = vec!["openid", "offline_access"];
+        scopes.extend(self.scopes.iter().map(String::as_str));
+        scopes.join(" ")
+    }
+}
+
+/// Replace the user of the kubeconfig with `kubie oidc-token`, when it uses OIDC and the tokens of kubie
+/// are enabled for the context.
+pub fn wrap(oidc: &Oidc, kubeconfig: &mut KubeConfig) {
+    if !oidc.enabled_for(&kubeconfig.contexts[0].name) {
+        return;
+    }
+    let user = &mut kubeconfig.users[0].user;
+    let Some(provider) = Provider::from_user(user) else {
+        return;
+    };
+    let Ok(kubie) = env::current_exe() else {
+        return;
+    };
+
+    let mut exec = Mapping::new();
+    exec.insert("apiVersion".into(), "client.authentication.k8s.io/v1beta1".into());
+    exec.insert("command".into(), kubie.to_string_lossy().into_owned().into());
+    exec.insert("args".into(), Value::Sequence(provider.args()));
+    exec.insert("interactiveMode".into(), "IfAvailable".into());
+    user.remove("auth-provider");
+    user.insert("exec".into(), Value::Mapping(exec));
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Tokens {
+    id_token: String,
+    refresh_token: Option<String>,
+}
+
+impl Tokens {
+    /// Read the tokens from a response of the token endpoint. Providers may not return a new refresh token
+    /// when refreshing, the previous one is then kept.
+    fn from_response(response: &serde_json::Value, refresh_token: Option<&str>) -> Result<Tokens> {
+        Ok(Tokens {
+            id_token: response["id_token"]
+                .as_str()
+                .context("The OIDC provider did not return an ID token")?
+                .into(),
+            refresh_token: response["refresh_token"].as_str().or(refresh_token).map(Into::into),
+        })
+    }
+
+    /// Expiration of the ID token, found in its claims.
+    fn expiration(&self) -> Option<OffsetDateTime> {
+        let claims = self.id_token.split('.').nth(1)?;
+        let claims: serde_json::Value = serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(claims).ok()?).ok()?;
+        OffsetDateTime::from_unix_timestamp(claims["exp"].as_i64()?).ok()
+    }
+
+    fn is_fresh(&self, now: OffsetDateTime) -> bool {
+        self.expiration()
+            .is_some_and(|expiration| expiration - EXPIRY_MARGIN > now)
+    }
+}
+
+/// Endpoints of the provider, from its configuration.
+#[derive(Debug, Deserialize)]
+struct Endpoints {
+    device_authorization_endpoint: Option<String>,
+    token_endpoint: String,
+}
+
+fn endpoints(provider: &Provider) -> Result<Endpoints> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        provider.issuer_url.trim_end_matches('/')
+    );
+    let response = attohttpc::get(&url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .with_context(|| format!("Could not get the OIDC configuration from {url}"))?;
+    if !response.is_success() {
+        bail!(
+            "Could not get the OIDC configuration from {}: {}",
+            url,
+            response.status()
+        );
+    }
+    Ok(response.json()?)
+}
+
+/// Post a form to an endpoint of the provider, along with the client's credentials. Errors are returned
+/// as JSON objects as well, so the body is returned along with whether the request succeeded.
+fn post(provider: &Provider, url: &str, params: &[(&str, &str)]) -> Result<(bool, serde_json::Value)> {
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    form.extend_pairs(params).append_pair("client_id", &provider.client_id);
+    if let Some(secret) = &provider.client_secret {
+        form.append_pair("client_secret", secret);
+    }
+    let response = attohttpc::post(url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .bytes(form.finish())
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .with_context(|| format!("Could not reach the OIDC provider at {url}"))?;
+    let success = response.is_success();
+    let body = response
+        .json()
+        .with_context(|| format!("The OIDC provider returned an invalid response from {url}"))?;
+    Ok((success, body))
+}
+
+fn error_message(response: &serde_json::Value) -> String {
+    match (response["error"].as_str(), response["error_description"].as_str()) {
+        (Some(error), Some(description)) => format!("{error}: {description}"),
+        (Some(error), None) => error.into(),
+        _ => response.to_string(),
+    }
+}
+
+fn open_browser(url: &str) {
+    let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+    let _ = Command::new(opener)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Log in with the device authorization flow: the user opens a page of the provider, in a browser of any
+/// device, and enters the code printed by kubie, while kubie polls the provider for the tokens.
+fn device_flow(provider: &Provider) -> Result<Tokens> {
+    let endpoints = endpoints(provider)?;
+    let device_endpoint = endpoints
+        .device_authorization_endpoint
+        .context("The OIDC provider does not support the device authorization flow")?;
+    let (success, device) = post(provider, &device_endpoint, &[("scope", &provider.scope())])?;
+    if !success {
+        bail!("Could not start the login: {}", error_message(&device));
+    }
+    let device_code = device["device_code"]
+        .as_str()
+        .context("The OIDC provider did not return a device code")?;
+    let user_code = device["user_code"].as_str().unwrap_or_default();
+    let verification_uri = device["verification_uri"]
+        .as_str()
+        .or(device["verification_url"].as_str())
+        .unwrap_or_default();
+
+    eprintln!("To log in, open {verification_uri} and enter the code {user_code}");
+    if let Some(url) = device["verification_uri_complete"].as_str() {
+        open_browser(url);
+    }
+
+    let mut interval = device["interval"].as_u64().unwrap_or(5);
+    let deadline = Instant::now() + StdDuration::from_secs(device["expires_in"].as_u64().unwrap_or(600));
+    loop {
+        thread::sleep(StdDuration::from_secs(interval));
+        if Instant::now() >= deadline {
+            bail!("The code expired before the login was completed");
+        }
+        let params = [("grant_type", DEVICE_CODE_GRANT), ("device_code", device_code)];
+        let (success, response) = post(provider, &endpoints.token_endpoint, &params)?;
+        if success {
+            return Tokens::from_response(&response, None);
+        }
+        match response["error"].as_str() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += 5,
+            _ => bail!("Login failed: {}", error_message(&response)),
+        }
+    }
+}
+
+fn refresh(provider: &Provider, refresh_token: &str) -> Result<Tokens> {
+    let endpoints = endpoints(provider)?;
+    let params = [("grant_type", "refresh_token"), ("refresh_token", refresh_token)];
+    let (success, response) = post(provider, &endpoints.token_endpoint, &params)?;
+    if !success {
+        bail!("Could not refresh the token: {}", error_message(&response));
+    }
+    Tokens::from_response(&response, Some(refresh_token))
+}
+
+/// Log in to the OIDC provider of the context's user, keeping the tokens for `kubie oidc-token`.
+pub fn login(settings: &Settings, context_name: &str) -> Result<()> {
+    let installed = kubeconfig::get_installed_contexts(settings)?;
+    let kubeconfig = installed.make_kubeconfig_for_context(context_name, None::<&str>)?;
+    let provider = Provider::from_user(&kubeconfig.users[0].user)
+        .with_context(|| format!("The user of context {context_name} does not use OIDC"))?;
+
+    let tokens = device_flow(&provider)?;
+    ioutil::write_json(provider.tokens_path(), &tokens)?;
+    eprintln!("Logged in to {}", provider.issuer_url);
+    if !settings.oidc.enabled_for(context_name) {
+        eprintln!("The token is only used by kubie when oidc is enabled for context {context_name} in the settings.");
+    }
+    Ok(())
+}
+
+/// Print the ID token of the provider as the credentials of an exec plugin, refreshing it when it is about
+/// to expire. Without a refresh token, the user logs in again.
+pub fn token(provider: &Provider) -> Result<()> {
+    let path = provider.tokens_path();
+    let mut tokens: Option<Tokens> = ioutil::read_json(&path).ok();
+
+    if !tokens
+        .as_ref()
+        .is_some_and(|tokens| tokens.is_fresh(OffsetDateTime::now_utc()))
+    {
+        let refreshed = match tokens.as_ref().and_then(|tokens| tokens.refresh_token.as_deref()) {
+            Some(refresh_token) => refresh(provider, refresh_token)
+                .inspect_err(|err| eprintln!("Warning: {err:#}"))
+                .ok(),
+            None => None,
+        };
+        let fresh = match refreshed {
+            Some(fresh) => fresh,
+            None => device_flow(provider)?,
+        };
+        ioutil::write_json(&path, &fresh)?;
+        tokens = Some(fresh);
+    }
+
+    let tokens = tokens.expect("tokens are set");
+    let mut status = json!({ "token": tokens.id_token });
+    if let Some(expiration) = tokens
+        .expiration()
+        .and_then(|expiration| expiration.format(&Rfc3339).ok())
+    {
+        status["expirationTimestamp"] = expiration.into();
+    }
+    let credential = json!({
+        "apiVersion": "client.authentication.k8s.io/v1beta1",
+        "kind": "ExecCredential",
+        "status": status,
+    });
+    println!("{credential}");
+    Ok(())
+}
+
+#[test]
+fn test_from_user() {
+    let user: Mapping = serde_yaml::from_str(
+        r#"
+exec:
+  apiVersion: client.authentication.k8s.io/v1beta1
+  command: kubectl
+  args:
+    - oidc-login
+    - get-token
+    - --oidc-issuer-url=https://dex.example.com
+    - --oidc-client-id
+    - kubernetes
+    - --oidc-extra-scope=groups,email
+"#,
+    )
+    .unwrap();
+    let provider = Provider {
+        issuer_url: "https://dex.example.com".into(),
+        client_id: "kubernetes".into(),
+        client_secret: None,
+        scopes: vec!["groups".into(), "email".into()],
+    };
+    assert_eq!(Provider::from_user(&user), Some(provider.clone()));
+    assert_eq!(provider.scope(), "openid offline_access groups email");
+
+    let user: Mapping = serde_yaml::from_str(
+        r#"
+auth-provider:
+  name: oidc
+  config:
+    idp-issuer-url: https://dex.example.com
+    client-id: kubernetes
+    extra-scopes: groups,email
+"#,
+    )
+    .unwrap();
+    assert_eq!(Provider::from_user(&user), Some(provider));
+
+    let user: Mapping = serde_yaml::from_str("exec: {command: aws, args: [eks, get-token]}").unwrap();
+    assert_eq!(Provider::from_user(&user), None);
+
+    // {"sub":"jane","exp":1714564800}
+    let tokens = Tokens {
+        id_token: "e30.eyJzdWIiOiJqYW5lIiwiZXhwIjoxNzE0NTY0ODAwfQ.c2ln".into(),
+        refresh_token: None,
+    };
+    let now = OffsetDateTime::parse("2024-05-01T11:00:00Z", &Rfc3339).unwrap();
+    assert_eq!(
+        tokens.expiration(),
+        OffsetDateTime::parse("2024-05-01T12:00:00Z", &Rfc3339).ok()
+    );
+    assert!(tokens.is_fresh(now));
+    assert!(!tokens.is_fresh(now + Duration::hours(1)));
+}
@@ -0,0 +1,76 @@
+//! Contexts of OpenShift clusters, whose namespaces are listed and created as projects, and whose tokens
+//! obtained with `oc login` expire after a day or so.
+
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde_yaml::Value;
+
+use crate::kubeconfig::{self, Installed, KubeConfig};
+use crate::kubectl;
+use crate::output::note;
+use crate::settings::Settings;
+
+/// Whether the context of the kubeconfig is of an OpenShift cluster.
+pub fn is_openshift(settings: &Settings, kubeconfig: &KubeConfig) -> bool {
+    let context = &kubeconfig.contexts[0];
+    settings.openshift.is_openshift(&context.name, &context.context.user)
+}
+
+/// Run `oc login` when the token of an OpenShift context has expired, and use the new token. `oc login`
+/// writes it to the kubeconfig file which defines the user of the context.
+pub fn ensure_login(settings: &Settings, installed: &Installed, kubeconfig: &mut KubeConfig) -> Result<()> {
+    if !settings.openshift.login || !is_openshift(settings, kubeconfig) {
+        return Ok(());
+    }
+    // The users authenticated with certificates or exec plugins are not logged in by oc.
+    if !kubeconfig.users[0].user.contains_key("token") {
+        return Ok(());
+    }
+
+    let context_name = kubeconfig.contexts[0].name.clone();
+    let timeout = settings.behavior.namespace_lookup_timeout();
+    match kubectl::get_namespaces(
+        &*kubeconfig,
+        settings.configured_kubectl_for(&context_name).as_deref(),
+        true,
+        timeout,
+    ) {
+        Err(err) if format!("{err:#}").contains("Unauthorized") => {}
+        _ => return Ok(()),
+    }
+
+    let user_name = kubeconfig.contexts[0].context.user.clone();
+    let source = installed
+        .users
+        .iter()
+        .find(|user| user.item.name == user_name)
+        .map(|user| user.source.clone())
+        .with_context(|| format!("Could not find the kubeconfig of user {user_name}"))?;
+    let server = kubeconfig.clusters[0]
+        .cluster
+        .get("server")
+        .and_then(Value::as_str)
+        .with_context(|| format!("The cluster of context {context_name} has no server"))?
+        .to_string();
+
+    note!("The token of context {context_name} has expired, logging in with oc.");
+    let status = Command::new("oc")
+        .arg("login")
+        .arg(format!("--server={server}"))
+        .env("KUBECONFIG", &*source)
+        .status()
+        .context("Could not run oc")?;
+    if !status.success() {
+        bail!("oc login {} exited with {}", server, status);
+    }
+
+    let refreshed = kubeconfig::read_kubeconfig(&source)?;
+    let user = refreshed
+        .users
+        .into_iter()
+        .find(|user| user.name == user_name)
+        .with_context(|| format!("oc login did not write user {user_name} to {}", source.display()))?;
+    kubeconfig.users[0].user = user.user;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Output modes of kubie, set by its global flags: plain output without colors with `--no-color` or the
+//! NO_COLOR variable (https://no-color.org), and quiet output without the banners, headers and messages of
+//! kubie and its hooks with `--quiet`. Warnings and errors are always printed.
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Set the output modes. `--no-color` sets NO_COLOR, so that the prompt of the shells spawned by kubie and
+/// the commands run in them are not colored either.
+pub fn init(no_color: bool, quiet: bool) {
+    if no_color {
+        env::set_var("NO_COLOR", "1");
+    }
+    let color = env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+    COLOR.store(color, Ordering::Relaxed);
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether the output may contain ANSI colors.
+pub fn color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// Whether the banners, headers and messages are left out.
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print a message about what kubie does to stderr, unless the output is quiet.
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use note;
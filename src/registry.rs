@@ -0,0 +1,163 @@
+//! Registry of the running kubie shells listed by `kubie sessions`, with a file per shell named after its PID.
+//! The files of shells which crashed are removed by `kubie prune`.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::ephemeral;
+use crate::hooks::HookEnv;
+use crate::ioutil;
+use crate::kubeconfig::KubeConfig;
+use crate::state;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Entry {
+    /// PID of the shell.
+    pub pid: u32,
+    /// Context and namespace in which the shell was spawned.
+    pub context: String,
+    pub namespace: Option<String>,
+    /// Kubeconfig of the shell, which has its current context and namespace.
+    pub kubeconfig: Option<OsString>,
+    pub depth: u32,
+    pub started: String,
+    /// Terminal from which the shell was spawned.
+    pub tty: Option<String>,
+    /// Start time of the shell process, which tells it apart from a later process given the same PID.
+    #[serde(default)]
+    pub start_time: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(pid: u32, env: &HookEnv) -> Entry {
+        Entry {
+            pid,
+            context: env.context.clone(),
+            namespace: env.namespace.clone(),
+            kubeconfig: env.kubeconfig.clone(),
+            depth: env.depth,
+            started: OffsetDateTime::now_utc()
+                .replace_nanosecond(0)
+                .ok()
+                .and_then(|time| time.format(&Rfc3339).ok())
+                .unwrap_or_default(),
+            tty: tty(),
+            start_time: start_time(pid),
+        }
+    }
+
+    /// The current context and namespace of the shell, which may have switched since it was spawned.
+    pub fn current(&self) -> (String, Option<String>) {
+        let config: Option<KubeConfig> = self.kubeconfig.as_ref().and_then(|path| ioutil::read_yaml(path).ok());
+        match config {
+            Some(config) if !config.contexts.is_empty() => (
+                config.contexts[0].name.clone(),
+                config.contexts[0].context.namespace.clone(),
+            ),
+            _ => (self.context.clone(), self.namespace.clone()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn tty() -> Option<String> {
+    let name = unsafe { libc::ttyname(libc::STDIN_FILENO) };
+    if name.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn tty() -> Option<String> {
+    None
+}
+
+/// Start time of a process in clock ticks since boot, from the 22nd field of its stat file. The fields
+/// follow the command name, which is in parentheses and may contain spaces.
+#[cfg(target_os = "linux")]
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let fields = stat.get(stat.rfind(')')? + 2..)?;
+    fields.split(' ').nth(19)?.parse().ok()
+}
+
+/// Start time of a process in microseconds since the epoch.
+#[cfg(target_os = "macos")]
+fn start_time(pid: u32) -> Option<u64> {
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    let result = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            &mut info as *mut libc::proc_bsdinfo as *mut libc::c_void,
+            size,
+        )
+    };
+    (result == size).then(|| info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Whether the shell of an entry is still running. A process given the PID of a shell which has exited
+/// has another start time, where the system tells it.
+pub fn is_live(entry: &Entry) -> bool {
+    ephemeral::is_running(entry.pid) && entry.start_time == start_time(entry.pid)
+}
+
+pub fn dir() -> PathBuf {
+    state::paths::data_dir().join("sessions")
+}
+
+fn path(pid: u32) -> PathBuf {
+    dir().join(format!("{pid}.json"))
+}
+
+pub fn register(entry: &Entry) -> Result<()> {
+    ioutil::write_json(path(entry.pid), entry)
+}
+
+pub fn unregister(pid: u32) {
+    let _ = fs::remove_file(path(pid));
+}
+
+/// The registered shells which are still running, the oldest first.
+pub fn list() -> Vec<Entry> {
+    let Ok(files) = fs::read_dir(dir()) else {
+        return vec![];
+    };
+    let mut entries: Vec<Entry> = files
+        .flatten()
+        .filter_map(|file| ioutil::read_json(file.path()).ok())
+        .filter(is_live)
+        .collect();
+    entries.sort_by(|a, b| a.started.cmp(&b.started));
+    entries
+}
+
+pub fn get(pid: u32) -> Option<Entry> {
+    ioutil::read_json(path(pid)).ok()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_is_live() {
+    let mut entry = Entry::new(std::process::id(), &HookEnv::default());
+    assert!(entry.start_time.is_some());
+    assert!(is_live(&entry));
+
+    entry.start_time = entry.start_time.map(|time| time + 1);
+    assert!(!is_live(&entry));
+}
@@ -4,12 +4,10 @@ use std::process::Command;
 use anyhow::Result;
 
 use super::ShellSpawnInfo;
+use crate::ephemeral;
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
-    let temp_rc_file = tempfile::Builder::new()
-        .prefix("kubie-bashrc")
-        .suffix(".bash")
-        .tempfile()?;
+    let temp_rc_file = ephemeral::tempfile("bashrc", ".bash")?;
     let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
 
     write!(
@@ -51,24 +49,38 @@ function __kubie_cmd_pre_exec__() {{
 }}
 
 trap '__kubie_cmd_pre_exec__' DEBUG
+
+# Update the variables which follow the context and the namespace after they are switched in place.
+function __kubie_env_update__() {{
+    if [[ -s "$KUBIE_ENV_UPDATE" ]] ; then
+        source "$KUBIE_ENV_UPDATE"
+        : > "$KUBIE_ENV_UPDATE"
+    fi
+}}
+
+PROMPT_COMMAND="__kubie_env_update__${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
 "#
     )?;
 
-    if !info.settings.prompt.disable {
+    if info.settings.prompt.is_injected() {
         write!(
             temp_rc_file_buf,
             r#"
-KUBIE_PROMPT='{}'
-PS1="$KUBIE_PROMPT $PS1"
-unset KUBIE_PROMPT
+KUBIE_PS1='{}'
+PS1="$KUBIE_PS1 {}"
+unset KUBIE_PS1
 "#,
             info.prompt,
+            if info.settings.prompt.is_replacing() {
+                ""
+            } else {
+                "$PS1"
+            },
         )?;
     }
 
-    if !info.settings.hooks.start_ctx.is_empty() {
-        write!(temp_rc_file_buf, "{}", info.settings.hooks.start_ctx)?;
-    }
+    write!(temp_rc_file_buf, "{}", info.start_ctx_script())?;
+    writeln!(temp_rc_file_buf, "{}", info.rc)?;
 
     temp_rc_file_buf.flush()?;
 
@@ -77,22 +89,20 @@ unset KUBIE_PROMPT
     cmd.arg(temp_rc_file.path());
     info.env_vars.apply(&mut cmd);
 
-    let mut child = cmd.spawn()?;
-    child.wait()?;
+    super::run_shell(&mut cmd, info)?;
 
-    if !info.settings.hooks.start_ctx.is_empty() {
-        let temp_exit_hook_file = tempfile::Builder::new()
-            .prefix("kubie-bash-exit-hook")
-            .suffix(".bash")
-            .tempfile()?;
+    if !info.stop_ctx.is_empty() {
+        let temp_exit_hook_file = ephemeral::tempfile("bash-exit-hook", ".bash")?;
         let mut temp_exit_hook_file_buf = BufWriter::new(temp_exit_hook_file.as_file());
 
-        write!(temp_exit_hook_file_buf, "{}", info.settings.hooks.stop_ctx)?;
+        let hook_env = info.stop_ctx_env();
+        write!(temp_exit_hook_file_buf, "{}", hook_env.expand(&info.stop_ctx))?;
 
         temp_exit_hook_file_buf.flush()?;
         let mut exit_cmd = Command::new("bash");
         exit_cmd.arg(temp_exit_hook_file.path());
         info.env_vars.apply(&mut exit_cmd);
+        exit_cmd.envs(hook_env.vars());
 
         let mut child = exit_cmd.spawn()?;
         child.wait()?;
@@ -1,3 +1,5 @@
+use std::fs;
+use std::path::Path;
 use std::process::Command;
 use std::str;
 
@@ -10,6 +12,8 @@ pub enum ShellKind {
     Xonsh,
     Zsh,
     Nu,
+    PowerShell,
+    Elvish,
 }
 
 impl ShellKind {
@@ -20,6 +24,8 @@ impl ShellKind {
             "xonsh" | "python" => ShellKind::Xonsh,
             "zsh" => ShellKind::Zsh,
             "nu" => ShellKind::Nu,
+            "pwsh" | "powershell" => ShellKind::PowerShell,
+            "elvish" => ShellKind::Elvish,
             _ => return None,
         })
     }
@@ -37,7 +43,25 @@ fn run_ps(args: &[&str]) -> Result<Vec<String>> {
     Ok(text.split('\n').filter(|x| !x.is_empty()).map(String::from).collect())
 }
 
+/// Read a process' details from /proc, which avoids spawning `ps` for every ancestor.
+/// Returns `None` when /proc is not available, such as on macOS.
+fn read_proc(pid: &str, file: &str) -> Option<String> {
+    let bytes = fs::read(Path::new("/proc").join(pid).join(file)).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Extract the parent pid from the content of /proc/<pid>/stat. The command name in the
+/// second field is wrapped in parentheses and may itself contain spaces and parentheses.
+fn parse_stat_ppid(stat: &str) -> Option<String> {
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().nth(1).map(String::from)
+}
+
 fn parent_of(pid: &str) -> Result<String> {
+    if let Some(ppid) = read_proc(pid, "stat").as_deref().and_then(parse_stat_ppid) {
+        return Ok(ppid);
+    }
+
     let lines = run_ps(&["-o", "ppid=", pid])?;
     lines
         .into_iter()
@@ -47,6 +71,13 @@ fn parent_of(pid: &str) -> Result<String> {
 }
 
 fn command_of(pid: &str) -> Result<String> {
+    if let Some(cmdline) = read_proc(pid, "cmdline") {
+        let args: Vec<&str> = cmdline.split('\0').filter(|x| !x.is_empty()).collect();
+        if !args.is_empty() {
+            return Ok(args.join(" "));
+        }
+    }
+
     let lines = run_ps(&["-o", "args=", pid])?;
     lines
         .into_iter()
@@ -59,6 +90,7 @@ fn parse_command(cmd: &str) -> &str {
     let binary_path = &cmd[..first_space];
     let last_path_sep = binary_path.rfind('/').map(|x| x + 1).unwrap_or(0);
     let binary = &binary_path[last_path_sep..];
+    let binary = binary.strip_suffix(".exe").unwrap_or(binary);
     binary
         .trim_start_matches('-')
         .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
@@ -70,16 +102,17 @@ fn parse_command(cmd: &str) -> &str {
 /// If any of kubie's ancestor is a known shell, we have found which shell is in
 /// use.
 ///
-/// This functions depends on the `ps` command being installed and available in
-/// the PATH variable.
+/// The process tree is read from /proc when it is available. Otherwise, this function
+/// depends on the `ps` command being installed and available in the PATH variable.
 ///
 /// The SHELL environment variable corresponds to the user's configured SHELL, not
 /// the shell currently in use.
+#[cfg(unix)]
 pub fn detect() -> Result<ShellKind> {
     let kubie_pid = format!("{}", std::process::id());
     let mut parent_pid = parent_of(&kubie_pid)?;
     loop {
-        if parent_pid == "1" {
+        if parent_pid == "1" || parent_pid == "0" {
             return Err(anyhow!("Could not detect shell in use"));
         }
 
@@ -93,6 +126,12 @@ pub fn detect() -> Result<ShellKind> {
     }
 }
 
+/// There is no `ps` on Windows, PowerShell is assumed unless the `shell` setting says otherwise.
+#[cfg(not(unix))]
+pub fn detect() -> Result<ShellKind> {
+    Ok(ShellKind::PowerShell)
+}
+
 #[test]
 fn test_parse_command_simple() {
     assert_eq!(parse_command("bash"), "bash");
@@ -127,3 +166,17 @@ fn test_parse_command_versioned_intepreter() {
 fn test_parse_command_nu() {
     assert_eq!(parse_command("/bin/nu"), "nu");
 }
+
+#[test]
+fn test_parse_command_windows_exe() {
+    assert_eq!(parse_command("pwsh.exe -NoLogo"), "pwsh");
+}
+
+#[test]
+fn test_parse_stat_ppid() {
+    assert_eq!(
+        parse_stat_ppid("4242 (tmux: server) S 1 4242 4242 0 -1").as_deref(),
+        Some("1")
+    );
+    assert_eq!(parse_stat_ppid("77 (a) b) R 12 77").as_deref(), Some("12"));
+}
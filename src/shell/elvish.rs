@@ -0,0 +1,75 @@
+use std::io::{BufWriter, Write};
+use std::process::Command;
+
+use anyhow::Result;
+
+use crate::ephemeral;
+use crate::shell::ShellSpawnInfo;
+
+pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
+    if !info.start_ctx.is_empty() {
+        info.run_hook(&info.start_ctx, &info.hook_env)?;
+    }
+
+    let temp_rc_file = ephemeral::tempfile("elvish", ".elv")?;
+    let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
+
+    write!(
+        temp_rc_file_buf,
+        r#"
+use os
+use path
+
+# Elvish only reads a single rc file, load the user's rc file into the REPL namespace.
+var kubie-user-rc = (path:join (or $E:XDG_CONFIG_HOME (path:join $E:HOME .config)) elvish rc.elv)
+if (os:exists $kubie-user-rc) {{
+    eval (slurp < $kubie-user-rc) &on-end={{|ns|
+        for name [(keys $ns)] {{
+            edit:add-var $name $ns[$name]
+        }}
+    }}
+}}
+
+# Set the proper KUBECONFIG variable before each command runs,
+# to prevent the user from overwriting it.
+set edit:after-readline = [$@edit:after-readline {{|_| set-env KUBECONFIG $E:KUBIE_KUBECONFIG }}]
+
+# Update the variables which follow the context and the namespace after they are switched in place.
+set edit:before-readline = [$@edit:before-readline {{
+    var update = (slurp < $E:KUBIE_ENV_UPDATE)
+    if (!=s $update '') {{
+        print > $E:KUBIE_ENV_UPDATE
+        eval $update
+    }}
+}}]
+
+if ${inject} {{
+    var kubie-original-prompt = $edit:prompt
+    set edit:prompt = {{ put {prompt} ' '{original} }}
+}}
+"#,
+        inject = info.settings.prompt.is_injected(),
+        prompt = info.prompt,
+        // With prompt.integration set to replace, the existing prompt is not shown.
+        original = if info.settings.prompt.is_replacing() {
+            ""
+        } else {
+            "; $kubie-original-prompt"
+        },
+    )?;
+    writeln!(temp_rc_file_buf, "{}", info.rc)?;
+    temp_rc_file_buf.flush()?;
+
+    let mut cmd = Command::new("elvish");
+    cmd.arg("-rc");
+    cmd.arg(temp_rc_file.path());
+    info.env_vars.apply(&mut cmd);
+
+    super::run_shell(&mut cmd, info)?;
+
+    if !info.stop_ctx.is_empty() {
+        info.run_hook(&info.stop_ctx, &info.stop_ctx_env())?;
+    }
+
+    Ok(())
+}
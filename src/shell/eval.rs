@@ -0,0 +1,260 @@
+use std::fs;
+
+use anyhow::Result;
+
+use crate::ephemeral;
+use crate::hooks::HookEnv;
+use crate::kubeconfig::KubeConfig;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::vars;
+
+use super::{generate_session_id, prompt, quote, session_env_vars, update};
+
+/// Shells which can evaluate the output of `kubie ctx --eval`.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum EvalShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Quote a string for fish, which does not end single quoted strings with a backslash.
+pub fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Pid of the shell evaluating kubie's output. Its kubeconfig and session files are reused
+/// when switching again from the same shell.
+#[cfg(unix)]
+fn shell_pid() -> u32 {
+    std::os::unix::process::parent_id()
+}
+
+#[cfg(not(unix))]
+fn shell_pid() -> u32 {
+    std::process::id()
+}
+
+/// Print the commands which make the current shell use the given kubeconfig.
+pub fn print_eval(settings: &Settings, config: &KubeConfig, session: &mut Session, shell: EvalShell) -> Result<()> {
+    // The files of shells which have exited are pruned when kubie starts.
+    let dir = ephemeral::eval_dir();
+    fs::create_dir_all(&dir)?;
+
+    let pid = shell_pid();
+    let config_path = dir.join(format!("{pid}.yaml"));
+    let session_path = dir.join(format!("{pid}.json"));
+    let update_path = dir.join(format!("{pid}.env"));
+    config.write_to_file(&config_path)?;
+    fs::write(&update_path, "")?;
+    session.set_prompt(prompt::text(settings, config, vars::get_depth() + 1));
+    session.save(Some(&session_path))?;
+
+    let session_id = generate_session_id();
+    let mut env_vars = session_env_vars(settings, config, &config_path, &session_path, &session_id);
+    env_vars.insert(update::FILE_VAR, &update_path);
+    env_vars.insert(
+        "KUBIE_SHELL",
+        match shell {
+            EvalShell::Bash => "bash",
+            EvalShell::Zsh => "zsh",
+            EvalShell::Fish => "fish",
+        },
+    );
+
+    let mut vars: Vec<_> = env_vars.vars.iter().collect();
+    vars.sort();
+    for (name, value) in vars {
+        let value = value.to_string_lossy();
+        match shell {
+            EvalShell::Bash | EvalShell::Zsh => println!("export {}={};", name, quote(&value)),
+            EvalShell::Fish => println!("set -gx {} {};", name, quote_fish(&value)),
+        }
+    }
+
+    // The start hook runs from the user's shell, which lets it print to the terminal.
+    let start_ctx = settings.hooks.start_ctx_for(&config.contexts[0].name);
+    if !start_ctx.is_empty() {
+        let hook_env = HookEnv {
+            context: config.contexts[0].name.clone(),
+            namespace: config.contexts[0].context.namespace.clone(),
+            ..Default::default()
+        };
+        let script = hook_env.expand(&start_ctx);
+        let (context, namespace) = (hook_env.context, hook_env.namespace.unwrap_or_default());
+        match shell {
+            EvalShell::Bash | EvalShell::Zsh => println!(
+                "KUBIE_CONTEXT={} KUBIE_NAMESPACE={} sh -c {};",
+                quote(&context),
+                quote(&namespace),
+                quote(&script)
+            ),
+            EvalShell::Fish => println!(
+                "env KUBIE_CONTEXT={} KUBIE_NAMESPACE={} sh -c {};",
+                quote_fish(&context),
+                quote_fish(&namespace),
+                quote_fish(&script)
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a function wrapping kubie, which evaluates the output of `kubie ctx --eval` outside of
+/// kubie shells. Inside of them, `kubie ctx` already switches the context in place, and the function
+/// updates the variables which follow the context afterwards. The flags which list the contexts, print
+/// the help, write a kubeconfig or spawn a shell are passed to kubie as is.
+///
+/// It is followed by the widgets bound to Ctrl-K Ctrl-X and Ctrl-K Ctrl-N, which open the menu of
+/// `kubie ctx` and `kubie ns` from the prompt and redraw it, keeping the command being typed.
+pub fn print_hook(shell: EvalShell) {
+    print!("{}", hook(shell));
+}
+
+fn hook(shell: EvalShell) -> String {
+    let function = match shell {
+        EvalShell::Bash | EvalShell::Zsh => format!(
+            r#"kubie() {{
+    local kubie_arg kubie_eval=1
+    for kubie_arg in "$@"; do
+        case "$kubie_arg" in
+            -h|--help|--list|-w|--wide|--no-shell|-r|--recursive) kubie_eval=0 ;;
+        esac
+    done
+    if {{ [ "$1" = ctx ] || [ "$1" = up ]; }} && [ "$KUBIE_ACTIVE" != 1 ] && [ "$kubie_eval" = 1 ]; then
+        local kubie_output
+        kubie_output="$(command kubie "$@" --eval={shell})" && eval "$kubie_output"
+    else
+        command kubie "$@"
+    fi
+    local kubie_status=$?
+    if [ -s "$KUBIE_ENV_UPDATE" ]; then
+        . "$KUBIE_ENV_UPDATE"
+        : > "$KUBIE_ENV_UPDATE"
+    fi
+    return $kubie_status
+}}
+"#,
+            shell = match shell {
+                EvalShell::Zsh => "zsh",
+                _ => "bash",
+            }
+        ),
+        EvalShell::Fish => r#"function kubie --wraps kubie
+    if contains -- "$argv[1]" ctx up; and test "$KUBIE_ACTIVE" != 1; \
+        and not string match -qr -- '^(-h|--help|--list|-w|--wide|--no-shell|-r|--recursive)$' $argv[2..]
+        set -l kubie_eval (command kubie $argv --eval=fish | string collect)
+        and eval $kubie_eval
+    else
+        command kubie $argv
+    end
+    set -l kubie_status $status
+    if test -s "$KUBIE_ENV_UPDATE"
+        source "$KUBIE_ENV_UPDATE"
+        true > "$KUBIE_ENV_UPDATE"
+    end
+    return $kubie_status
+end
+"#
+        .to_string(),
+    };
+    function + widgets(shell)
+}
+
+/// Widgets switching the context or the namespace from the prompt.
+fn widgets(shell: EvalShell) -> &'static str {
+    match shell {
+        // The widget is bound to a hidden key sequence. The visible one saves the line being typed, with a
+        // space so that an empty line does not yank an older kill, runs the widget, accepts the empty line so
+        // that bash draws the prompt again, and restores the line without the space.
+        EvalShell::Bash => {
+            r#"__kubie_widget() {
+    kubie "$@" </dev/tty
+}
+if [[ $- == *i* ]]; then
+    bind -x '"\C-x\C-kx": __kubie_widget ctx'
+    bind -x '"\C-x\C-kn": __kubie_widget ns'
+    bind '"\C-k\C-x": "\C-e \C-u\C-x\C-kx\C-m\C-y\C-h"'
+    bind '"\C-k\C-n": "\C-e \C-u\C-x\C-kn\C-m\C-y\C-h"'
+fi
+"#
+        }
+        EvalShell::Zsh => {
+            r#"kubie-ctx-widget() {
+    zle -I
+    kubie ctx </dev/tty
+    zle reset-prompt
+}
+kubie-ns-widget() {
+    zle -I
+    kubie ns </dev/tty
+    zle reset-prompt
+}
+zle -N kubie-ctx-widget
+zle -N kubie-ns-widget
+bindkey '^K^X' kubie-ctx-widget
+bindkey '^K^N' kubie-ns-widget
+"#
+        }
+        EvalShell::Fish => {
+            r#"function __kubie_widget
+    kubie $argv </dev/tty
+    commandline -f repaint
+end
+bind \ck\cx '__kubie_widget ctx'
+bind \ck\cn '__kubie_widget ns'
+"#
+        }
+    }
+}
+
+#[test]
+fn test_quote_fish() {
+    assert_eq!(quote_fish(r"it's a\b"), r"'it\'s a\\b'");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hook_bash() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    // A kubie which prints its arguments, and a command printing them once evaluated with --eval.
+    let dir = tempfile::tempdir().unwrap();
+    let kubie = dir.path().join("kubie");
+    fs::write(
+        &kubie,
+        "#!/bin/sh\ncase \"$*\" in *--eval=bash) echo \"echo eval $*\" ;; *) echo \"run $*\" ;; esac\n",
+    )
+    .unwrap();
+    fs::set_permissions(&kubie, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", dir.path().display(), std::env::var("PATH").unwrap_or_default());
+
+    let run = |args: &str| {
+        let script = format!("{}kubie {args}", hook(EvalShell::Bash));
+        let output = Command::new("bash")
+            .args(["-c", &script])
+            .env("PATH", &path)
+            .env_remove("KUBIE_ACTIVE")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run("ctx prod"), "eval ctx prod --eval=bash\n");
+    assert_eq!(run("up"), "eval up --eval=bash\n");
+    assert_eq!(run("ns dev"), "run ns dev\n");
+    for args in [
+        "ctx -h",
+        "ctx --help",
+        "ctx --list",
+        "ctx -w",
+        "ctx --wide",
+        "ctx prod --no-shell",
+        "ctx -r prod",
+        "up -r",
+    ] {
+        assert_eq!(run(args), format!("run {args}\n"));
+    }
+}
@@ -5,6 +5,15 @@ use anyhow::Result;
 use super::ShellSpawnInfo;
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
+    // With prompt.integration set to replace, the existing prompt is not shown.
+    let original = |function: &str| {
+        if info.settings.prompt.is_replacing() {
+            "set -l original".to_string()
+        } else {
+            format!("set -l original ({function})")
+        }
+    };
+
     let mut cmd = Command::new("fish");
     // run fish as an interactive login shell
     cmd.arg("-ilC");
@@ -16,7 +25,15 @@ function kubie_preexec --on-event fish_preexec
     set -xg KUBECONFIG "$KUBIE_KUBECONFIG"
 end
 
-if test "$KUBIE_PROMPT_DISABLE" = "0"
+# Update the variables which follow the context and the namespace after they are switched in place.
+function kubie_env_update --on-event fish_prompt
+    if test -s "$KUBIE_ENV_UPDATE"
+        source "$KUBIE_ENV_UPDATE"
+        true > "$KUBIE_ENV_UPDATE"
+    end
+end
+
+if {inject}
     # The general idea behind the prompt substitions is to save the existing
     # prompt's output _before_ anything else is run. This is important since the
     # existing prompt might be dependent on say the status of the executed command.
@@ -27,7 +44,7 @@ if test "$KUBIE_PROMPT_DISABLE" = "0"
         or function fish_right_prompt_original; end
 
         function fish_right_prompt
-            set -l original (fish_right_prompt_original)
+            {right_original}
 
             # Fish's right prompt does not support newlines, so there's no point in
             # iterating through the (potentially) existing prompt's lines.
@@ -36,7 +53,7 @@ if test "$KUBIE_PROMPT_DISABLE" = "0"
     else
         functions --copy fish_prompt fish_prompt_original
         function fish_prompt
-            set -l original (fish_prompt_original)
+            {left_original}
 
             printf '%s ' (string unescape {prompt})
 
@@ -50,12 +67,21 @@ if test "$KUBIE_PROMPT_DISABLE" = "0"
         end
     end
 end
+
+{rc}
     "#,
+        inject = if info.settings.prompt.is_injected() {
+            "true"
+        } else {
+            "false"
+        },
         prompt = info.prompt,
+        right_original = original("fish_right_prompt_original"),
+        left_original = original("fish_prompt_original"),
+        rc = info.rc,
     ));
     info.env_vars.apply(&mut cmd);
 
-    let mut child = cmd.spawn()?;
-    child.wait()?;
+    super::run_shell(&mut cmd, info)?;
     Ok(())
 }
@@ -1,21 +1,39 @@
 use std::collections::HashMap;
+use std::env;
 use std::ffi::OsString;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
-use self::detect::{detect, ShellKind};
+pub use self::detect::{detect, ShellKind};
+pub use self::eval::EvalShell;
+use self::supervisor::Supervisor;
+use self::watchdog::Watchdog;
+use crate::ephemeral;
+use crate::hooks::HookEnv;
+use crate::ioutil;
 use crate::kubeconfig::KubeConfig;
+use crate::kubectl;
+use crate::registry;
 use crate::session::Session;
-use crate::settings::Settings;
+use crate::settings::{MaxDepthAction, Settings};
 use crate::state;
+use crate::tmux;
 use crate::vars;
 
 mod bash;
 mod detect;
+mod elvish;
+pub mod eval;
 mod fish;
 mod nu;
-mod prompt;
+mod powershell;
+pub mod prompt;
+mod supervisor;
+pub mod update;
+mod watchdog;
 mod xonsh;
 mod zsh;
 
@@ -43,41 +61,188 @@ pub struct ShellSpawnInfo<'s, 'n> {
     settings: &'s Settings,
     env_vars: EnvVars<'n>,
     prompt: String,
+    start_ctx: String,
+    stop_ctx: String,
+    /// Snippet of the `shells` settings of the context, run last by the startup script of the shell.
+    rc: String,
+    hook_env: HookEnv,
 }
 
-pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -> Result<()> {
-    let kind = match &settings.shell {
-        Some(shell) => ShellKind::from_str(shell).ok_or_else(|| anyhow!("Invalid shell setting: {}", shell))?,
-        None => detect()?,
-    };
+impl ShellSpawnInfo<'_, '_> {
+    /// Script running the start_ctx hook from the shell's rc file. The context and namespace
+    /// variables are only exported to the hook since they become stale once the shell switches.
+    fn start_ctx_script(&self) -> String {
+        if self.start_ctx.is_empty() {
+            return String::new();
+        }
+        format!(
+            "export KUBIE_CONTEXT={} KUBIE_NAMESPACE={}\n{}\nunset KUBIE_CONTEXT KUBIE_NAMESPACE\n",
+            quote(&self.hook_env.context),
+            quote(self.hook_env.namespace.as_deref().unwrap_or("")),
+            self.hook_env.expand(&self.start_ctx),
+        )
+    }
 
-    let temp_config_file = tempfile::Builder::new()
-        .prefix("kubie-config")
-        .suffix(".yaml")
-        .tempfile()?;
-    config.write_to_file(temp_config_file.path())?;
+    /// Refresh the hook environment from the shell's kubeconfig before running the stop_ctx hook,
+    /// since the context or the namespace may have been changed inside the shell.
+    fn stop_ctx_env(&self) -> HookEnv {
+        let mut env = self.hook_env.clone();
+        if let Some(path) = &env.kubeconfig {
+            if let Ok(config) = ioutil::read_yaml::<_, KubeConfig>(PathBuf::from(path)) {
+                env.context = config.contexts[0].name.clone();
+                env.namespace = config.contexts[0].context.namespace.clone();
+            }
+        }
+        env
+    }
+    /// Run a hook with `sh` outside of the shell, for shells which cannot run POSIX hooks from their
+    /// startup script.
+    fn run_hook(&self, hook: &str, env: &HookEnv) -> Result<()> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(env.expand(hook));
+        self.env_vars.apply(&mut cmd);
+        cmd.envs(env.vars());
+        cmd.status()?;
+        Ok(())
+    }
+}
 
-    let temp_session_file = tempfile::Builder::new()
-        .prefix("kubie-session")
-        .suffix(".json")
-        .tempfile()?;
-    session.save(Some(temp_session_file.path()))?;
+/// Quote a string for POSIX shells.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
 
-    let depth = vars::get_depth();
-    let next_depth = depth + 1;
+/// Quote a command line argument when a shell would not read it as is.
+pub fn quote_if_needed(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        quote(arg)
+    }
+}
 
+fn generate_session_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}-{:x}", process::id(), nanos)
+}
+
+/// Variables which make a shell use the given kubeconfig and session files, along with the
+/// variables configured for the context.
+fn session_env_vars<'n>(
+    settings: &'n Settings,
+    config: &KubeConfig,
+    config_path: &Path,
+    session_path: &Path,
+    session_id: &str,
+) -> EnvVars<'n> {
     let mut env_vars = EnvVars::new();
 
     // Pre-insert the KUBECONFIG variable into the shell.
     // This will make sure any shell plugins/add-ons which require this env variable
     // will have it available at the beginninng of the .rc file
-    env_vars.insert("KUBECONFIG", temp_config_file.path());
+    env_vars.insert("KUBECONFIG", config_path);
     env_vars.insert("KUBIE_ACTIVE", "1");
-    env_vars.insert("KUBIE_DEPTH", next_depth.to_string());
-    env_vars.insert("KUBIE_KUBECONFIG", temp_config_file.path());
-    env_vars.insert("KUBIE_SESSION", temp_session_file.path());
+    env_vars.insert("KUBIE_DEPTH", (vars::get_depth() + 1).to_string());
+    env_vars.insert("KUBIE_KUBECONFIG", config_path);
+    env_vars.insert("KUBIE_SESSION", session_path);
+    env_vars.insert("KUBIE_SESSION_ID", session_id);
     env_vars.insert("KUBIE_STATE", state::paths::state());
+    if !vars::is_kubie_active() {
+        // Remember the user's KUBECONFIG, which is replaced inside of kubie shells.
+        if let Some(kubeconfig) = env::var_os("KUBECONFIG") {
+            env_vars.insert("KUBIE_USER_KUBECONFIG", kubeconfig);
+        }
+    }
+
+    if let Some(prompt) = prompt::text(settings, config, vars::get_depth() + 1) {
+        env_vars.insert("KUBIE_PROMPT", prompt);
+    }
+
+    for (name, value) in settings.env_for(&config.contexts[0].name) {
+        env_vars.insert(name, value);
+    }
+    env_vars.insert(update::SAVED_VAR, update::saved(settings, &config.contexts[0].name));
+
+    env_vars
+}
+
+/// Run the shell until it exits, keeping it in the registry of running shells meanwhile, and watching
+/// whether it is left idle in a sensitive context.
+fn run_shell(cmd: &mut Command, info: &ShellSpawnInfo) -> Result<()> {
+    let mut supervisor = Supervisor::new();
+    let mut child = cmd.spawn()?;
+    supervisor.watch(child.id());
+    let kubeconfig = info.hook_env.kubeconfig.as_ref().map(PathBuf::from);
+    let _watchdog = kubeconfig.and_then(|path| Watchdog::start(info.settings, child.id(), path));
+    if let Err(err) = registry::register(&registry::Entry::new(child.id(), &info.hook_env)) {
+        eprintln!("Warning: could not register the shell: {err}");
+    }
+    let status = child.wait();
+    registry::unregister(child.id());
+    status?;
+    Ok(())
+}
+
+pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &mut Session) -> Result<()> {
+    let next_depth = vars::get_depth() + 1;
+    if let Some(max_depth) = settings.behavior.max_depth.filter(|max_depth| next_depth > *max_depth) {
+        let message = format!("this would nest {next_depth} kubie shells, more than behavior.max_depth ({max_depth})");
+        match settings.behavior.max_depth_action {
+            MaxDepthAction::Error => bail!("Could not spawn a shell, {}", message),
+            MaxDepthAction::Warn => eprintln!("Warning: {message}"),
+        }
+    }
+
+    let kind = match settings.shell_for(&config.contexts[0].name) {
+        Some(shell) => ShellKind::from_str(shell).ok_or_else(|| anyhow!("Invalid shell setting: {}", shell))?,
+        None => detect()?,
+    };
+
+    let temp_config_file = ephemeral::tempfile("config", ".yaml")?;
+    config.write_to_file(temp_config_file.path())?;
 
+    let temp_session_file = ephemeral::tempfile("session", ".json")?;
+    session.set_prompt(prompt::text(settings, &config, next_depth));
+    session.save(Some(temp_session_file.path()))?;
+
+    // Written when the context or the namespace is switched in place.
+    let temp_update_file = ephemeral::tempfile("env", "")?;
+
+    let session_id = generate_session_id();
+
+    let mut env_vars = session_env_vars(
+        settings,
+        &config,
+        temp_config_file.path(),
+        temp_session_file.path(),
+        &session_id,
+    );
+    env_vars.insert(update::FILE_VAR, temp_update_file.path());
+
+    // The shim is removed when the shell exits.
+    let kubectl_shim = match kubectl::create_shim(settings, &config.contexts[0].name) {
+        Ok(shim) => shim,
+        Err(err) => {
+            eprintln!("Warning: could not create the kubectl shim: {err:#}");
+            None
+        }
+    };
+    match &kubectl_shim {
+        Some(dir) => {
+            let paths = env::var_os("PATH").unwrap_or_default();
+            let paths = std::iter::once(dir.path().to_path_buf()).chain(env::split_paths(&paths));
+            env_vars.insert("PATH", env::join_paths(paths)?);
+            env_vars.insert(kubectl::SHIM_DIR_VAR, dir.path());
+        }
+        // The shim of an outer shell must not be rewritten from this one.
+        None => env_vars.insert(kubectl::SHIM_DIR_VAR, ""),
+    }
+
+    let prompt = prompt::generate_ps1(settings, next_depth, kind);
     env_vars.insert("KUBIE_PROMPT_DISABLE", if settings.prompt.disable { "1" } else { "0" });
     env_vars.insert(
         "KUBIE_ZSH_USE_RPS1",
@@ -112,19 +277,55 @@ pub fn spawn_shell(settings: &Settings, config: KubeConfig, session: &Session) -
         ShellKind::Nu => {
             env_vars.insert("KUBIE_SHELL", "nu");
         }
+        ShellKind::PowerShell => {
+            env_vars.insert("KUBIE_SHELL", "powershell");
+        }
+        ShellKind::Elvish => {
+            env_vars.insert("KUBIE_SHELL", "elvish");
+        }
     }
 
+    let context_name = &config.contexts[0].name;
+    let hook_env = HookEnv {
+        context: context_name.clone(),
+        namespace: config.contexts[0].context.namespace.clone(),
+        kubeconfig: Some(temp_config_file.path().into()),
+        depth: next_depth,
+        session_id: Some(session_id),
+    };
     let info = ShellSpawnInfo {
         settings,
         env_vars,
-        prompt: prompt::generate_ps1(settings, next_depth, kind),
+        prompt,
+        start_ctx: settings.hooks.start_ctx_for(context_name),
+        stop_ctx: settings.hooks.stop_ctx_for(context_name),
+        rc: hook_env.expand(&settings.rc_for(context_name)),
+        hook_env,
     };
 
-    match kind {
+    let window = tmux::rename_window(settings, context_name, info.hook_env.namespace.as_deref());
+
+    let result = match kind {
         ShellKind::Bash => bash::spawn_shell(&info),
         ShellKind::Fish => fish::spawn_shell(&info),
         ShellKind::Xonsh => xonsh::spawn_shell(&info),
         ShellKind::Zsh => zsh::spawn_shell(&info),
         ShellKind::Nu => nu::spawn_shell(&info),
+        ShellKind::PowerShell => powershell::spawn_shell(&info),
+        ShellKind::Elvish => elvish::spawn_shell(&info),
+    };
+
+    if let Some(window) = window {
+        tmux::restore_window(settings, window);
     }
+
+    result
+}
+
+#[test]
+fn test_quote_if_needed() {
+    assert_eq!(quote_if_needed("get"), "get");
+    assert_eq!(quote_if_needed("app=web,tier=db"), "app=web,tier=db");
+    assert_eq!(quote_if_needed("it's"), "'it'\\''s'");
+    assert_eq!(quote_if_needed(""), "''");
 }
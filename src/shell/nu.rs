@@ -1,40 +1,60 @@
-use anyhow::Result;
 use std::process::Command;
 
+use anyhow::Result;
+
 use crate::shell::ShellSpawnInfo;
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
-    // let dir = tempdir()?;
+    if !info.start_ctx.is_empty() {
+        info.run_hook(&info.start_ctx, &info.hook_env)?;
+    }
 
     let mut cmd = Command::new("nu");
-    let mut args = "".to_string();
-
-    for (name, value) in &info.env_vars.vars {
-        args.push_str(&format!(
-            r#"$env.{} = '{}';"#,
-            name,
-            value.as_os_str().to_str().unwrap()
-        ));
-
-        if String::from("KUBIE_PROMPT_DISABLE").eq(name) && value == "0" {
-            let mut _prompt = info.prompt.clone();
-            // TODO: This is improvable, but it works for now
-            _prompt = _prompt
-                .replace("\\[\\e[31m\\]", "")
-                .replace("\\[\\e[32m\\]", "")
-                .replace("\\[\\e[0m\\]", "")
-                .replace('$', "");
-            let prompt = format!(
-                r#"$env.PROMPT_COMMAND = {{ || $"{prompt}\n(create_left_prompt)" }};"#,
-                prompt = _prompt
-            );
-            args.push_str(prompt.as_str());
-        }
-    }
     cmd.arg("-e");
-    cmd.arg(args);
+    cmd.arg(format!(
+        r#"
+# Set the proper KUBECONFIG variable before each command runs,
+# to prevent the user from overwriting it.
+$env.config = ($env.config | upsert hooks.pre_execution (
+    $env.config.hooks?.pre_execution? | default [] | append {{|| $env.KUBECONFIG = $env.KUBIE_KUBECONFIG }}
+))
+
+# Update the variables which follow the context and the namespace after they are switched in place.
+$env.config = ($env.config | upsert hooks.pre_prompt (
+    $env.config.hooks?.pre_prompt? | default [] | append {{||
+        let update = (open --raw $env.KUBIE_ENV_UPDATE)
+        if ($update | str length) > 0 {{
+            "" | save -f $env.KUBIE_ENV_UPDATE
+            let update = ($update | from json)
+            hide-env -i ...$update.unset
+            load-env $update.set
+        }}
+    }}
+))
+
+if {inject} {{
+    let original_prompt = ($env.PROMPT_COMMAND? | default "")
+    $env.PROMPT_COMMAND = {{||
+        let original = if ($original_prompt | describe) == "closure" {{ do $original_prompt }} else {{ $original_prompt }}
+        $"{prompt} {original}"
+    }}
+}}
+
+{rc}
+"#,
+        inject = info.settings.prompt.is_injected(),
+        prompt = info.prompt,
+        // With prompt.integration set to replace, the existing prompt is not shown.
+        original = if info.settings.prompt.is_replacing() { "" } else { "($original)" },
+        rc = info.rc,
+    ));
+    info.env_vars.apply(&mut cmd);
+
+    super::run_shell(&mut cmd, info)?;
+
+    if !info.stop_ctx.is_empty() {
+        info.run_hook(&info.stop_ctx, &info.stop_ctx_env())?;
+    }
 
-    let mut child = cmd.spawn()?;
-    child.wait()?;
     Ok(())
 }
@@ -0,0 +1,64 @@
+use std::process::Command;
+
+use anyhow::Result;
+use which::which;
+
+use crate::shell::ShellSpawnInfo;
+
+pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
+    if !info.start_ctx.is_empty() {
+        info.run_hook(&info.start_ctx, &info.hook_env)?;
+    }
+
+    // PowerShell 7 is called pwsh, Windows PowerShell 5 is called powershell.
+    let program = if which("pwsh").is_ok() { "pwsh" } else { "powershell" };
+
+    let mut cmd = Command::new(program);
+    cmd.arg("-NoLogo");
+    cmd.arg("-NoExit");
+    cmd.arg("-Command");
+    cmd.arg(format!(
+        r#"
+$global:__kubie_original_prompt = $function:prompt
+
+function global:prompt {{
+    # PowerShell has no preexec hook, set the proper KUBECONFIG variable before each prompt
+    # to prevent the user from overwriting it.
+    $env:KUBECONFIG = $env:KUBIE_KUBECONFIG
+
+    # Update the variables which follow the context and the namespace after they are switched in place.
+    if ((Test-Path $env:KUBIE_ENV_UPDATE) -and (Get-Item $env:KUBIE_ENV_UPDATE).Length -gt 0) {{
+        Invoke-Expression (Get-Content -Raw $env:KUBIE_ENV_UPDATE)
+        Clear-Content $env:KUBIE_ENV_UPDATE
+    }}
+
+    $original = & $global:__kubie_original_prompt
+    if (${inject}) {{
+        "{prompt} {original}"
+    }} else {{
+        $original
+    }}
+}}
+
+{rc}
+"#,
+        inject = info.settings.prompt.is_injected(),
+        prompt = info.prompt,
+        // With prompt.integration set to replace, the existing prompt is not shown.
+        original = if info.settings.prompt.is_replacing() {
+            ""
+        } else {
+            "$original"
+        },
+        rc = info.rc,
+    ));
+    info.env_vars.apply(&mut cmd);
+
+    super::run_shell(&mut cmd, info)?;
+
+    if !info.stop_ctx.is_empty() {
+        info.run_hook(&info.stop_ctx, &info.stop_ctx_env())?;
+    }
+
+    Ok(())
+}
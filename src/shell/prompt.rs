@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::fmt::{self, Display};
 
-use crate::settings::Settings;
+use crate::kubeconfig::KubeConfig;
+use crate::settings::{PromptColor, Settings};
 use crate::shell::ShellKind;
 
 struct Command {
@@ -21,104 +23,384 @@ impl Command {
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.shell_kind {
-            ShellKind::Fish => write!(f, "({})", self.content),
+            ShellKind::Fish | ShellKind::Elvish => write!(f, "({})", self.content),
+            ShellKind::Nu => write!(f, "(^{} | str trim)", self.content),
+            ShellKind::PowerShell => write!(f, "$(& {})", self.content),
             _ => write!(f, "$({})", self.content),
         }
     }
 }
 
-struct Color<D> {
-    color: u32,
-    content: D,
-    shell_kind: ShellKind,
+/// Color of a part of the Elvish prompt. The color of the context is looked up in `prompt.colors` every time
+/// the prompt is shown, since the context can change in the shell, falling back to the color of its tag.
+enum ColorCode {
+    Fixed(PromptColor),
+    Context { exe: String, default: Option<PromptColor> },
 }
 
-impl<D> Color<D> {
-    fn new(color: u32, content: D, shell_kind: ShellKind) -> Color<D> {
-        Color {
-            color,
-            content,
-            shell_kind,
-        }
-    }
+/// Content of the Elvish prompt styled with a color. Elvish does not print escape sequences in its prompt,
+/// it uses styled text instead.
+struct Color<D> {
+    color: ColorCode,
+    content: D,
 }
 
-// FIXME: @Miuler Validate this implementation for nu shell
-impl<D> Color<D>
+impl<D> fmt::Display for Color<D>
 where
     D: Display,
 {
-    fn isolate<E>(&self, f: &mut fmt::Formatter, content: E) -> fmt::Result
-    where
-        E: Display,
-    {
-        match self.shell_kind {
-            ShellKind::Fish | ShellKind::Xonsh => write!(f, "{content}"),
-            ShellKind::Zsh => write!(f, "%{{{content}%}}"),
-            ShellKind::Bash => write!(f, "\\[{content}\\]"),
-            _ => write!(f, "{content}"),
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !crate::output::color() {
+            return write!(f, "{}", self.content);
+        }
+        match &self.color {
+            ColorCode::Fixed(color) => write!(f, "(styled {} {})", self.content, color.name()),
+            ColorCode::Context { exe, default } => {
+                let mut content = format!("{exe} info ctx --color --color-name");
+                if let Some(default) = default {
+                    content.push_str(&format!(" --default-color {}", default.name()));
+                }
+                let command = Command::new(content, ShellKind::Elvish);
+                write!(f, "(styled {} {})", self.content, command)
+            }
         }
     }
+}
 
-    fn start_color(&self, f: &mut fmt::Formatter, color: u32) -> fmt::Result {
-        match self.shell_kind {
-            ShellKind::Xonsh => self.isolate(f, format!("\\033[{color}m")),
-            _ => self.isolate(f, format!("\\e[{color}m")),
+/// Text of the prompt format, quoted for Elvish, which compounds it with the other parts of the prompt.
+struct Text<'a> {
+    content: &'a str,
+}
+
+impl fmt::Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.content.is_empty() {
+            return Ok(());
         }
+        write!(f, "'{}'", self.content.replace('\'', "''"))
     }
+}
 
-    fn end_color(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.shell_kind {
-            ShellKind::Xonsh => self.isolate(f, "\\033[0m"),
-            _ => self.isolate(f, "\\e[0m"),
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Context,
+    Namespace,
+    Depth,
+    Metadata(String),
+    Color(PromptColor, Vec<Segment>),
+}
+
+/// Parse a prompt format. `{context}`, `{namespace}` and `{depth}` are replaced with their values,
+/// `{meta:<key>}` with a field of the context's metadata, and `<color>...</color>` tags color their content.
+/// Anything else is kept as is.
+fn parse_format(format: &str) -> Vec<Segment> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        let field = [
+            ("{context}", Segment::Context),
+            ("{namespace}", Segment::Namespace),
+            ("{depth}", Segment::Depth),
+        ]
+        .into_iter()
+        .find(|(name, _)| rest.starts_with(name));
+        if let Some((name, segment)) = field {
+            segments.extend((!text.is_empty()).then(|| Segment::Text(std::mem::take(&mut text))));
+            segments.push(segment);
+            rest = &rest[name.len()..];
+            continue;
         }
+
+        // The key is inserted in a command of the prompt, it is not quoted for the shell.
+        let metadata = rest
+            .strip_prefix("{meta:")
+            .and_then(|inner| Some(inner.split_at(inner.find('}')?)))
+            .filter(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            });
+        if let Some((key, after)) = metadata {
+            segments.extend((!text.is_empty()).then(|| Segment::Text(std::mem::take(&mut text))));
+            segments.push(Segment::Metadata(key.into()));
+            rest = &after[1..];
+            continue;
+        }
+
+        let tag = PromptColor::ALL.into_iter().find_map(|color| {
+            let name = color.name();
+            let inner = rest.strip_prefix(&format!("<{name}>"))?;
+            let end = inner.find(&format!("</{name}>"))?;
+            Some((color, &inner[..end], &inner[end + name.len() + 3..]))
+        });
+        if let Some((color, inner, after)) = tag {
+            segments.extend((!text.is_empty()).then(|| Segment::Text(std::mem::take(&mut text))));
+            segments.push(Segment::Color(color, parse_format(inner)));
+            rest = after;
+            continue;
+        }
+
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
     }
+    segments.extend((!text.is_empty()).then_some(Segment::Text(text)));
+    segments
 }
 
-impl<D> fmt::Display for Color<D>
-where
-    D: Display,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.start_color(f, self.color)?;
-        write!(f, "{}", self.content)?;
-        self.end_color(f)?;
-        Ok(())
-    }
+/// Render the segments of a prompt format for Elvish. With `context_colors`, the context is colored
+/// according to `prompt.colors`, or with the color of its tag when no entry matches.
+fn render_elvish(segments: &[Segment], exe: &str, depth: u32, context_colors: bool) -> String {
+    let command = |content: String| Command::new(content, ShellKind::Elvish);
+    let context_color = |default| ColorCode::Context {
+        exe: exe.to_string(),
+        default,
+    };
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(content) => Text { content }.to_string(),
+            Segment::Context => {
+                let context = command(format!("{exe} info ctx --prompt"));
+                if context_colors {
+                    Color {
+                        color: context_color(None),
+                        content: context,
+                    }
+                    .to_string()
+                } else {
+                    context.to_string()
+                }
+            }
+            Segment::Namespace => command(format!("{exe} info ns")).to_string(),
+            Segment::Depth => depth.to_string(),
+            Segment::Metadata(key) => command(format!("{exe} info meta {key}")).to_string(),
+            Segment::Color(color, inner) => {
+                let color = if context_colors && inner.contains(&Segment::Context) {
+                    context_color(Some(*color))
+                } else {
+                    ColorCode::Fixed(*color)
+                };
+                Color {
+                    color,
+                    content: render_elvish(inner, exe, depth, false),
+                }
+                .to_string()
+            }
+        })
+        .collect()
 }
 
-const RED: u32 = 31;
-const GREEN: u32 = 32;
-const BLUE: u32 = 34;
+/// The prompt format of the settings, or one that shows the current context, namespace and depth.
+fn format(settings: &Settings, depth: u32) -> &str {
+    match &settings.prompt.format {
+        Some(format) => format.as_str(),
+        None if settings.prompt.show_depth && depth > 1 => {
+            "[<red>{context}</red>|<green>{namespace}</green>|<blue>{depth}</blue>]"
+        }
+        None => "[<red>{context}</red>|<green>{namespace}</green>]",
+    }
+}
 
-/// Generates a PS1 string that shows the current context, namespace and depth.
-///
-/// Makes sure to protect the escape sequences so that the shell will not count the escape
-/// sequences in the length calculation of the prompt.
+/// Generates a PS1 string which runs `kubie info prompt` every time the prompt is shown, so that the changes
+/// to the prompt settings apply to the running shells. `kubie info prompt` protects its escape sequences so
+/// that the shell does not count them in the length of the prompt. Elvish cannot show the escape sequences
+/// printed by a command, its PS1 string is generated from `prompt.format` once instead.
 pub fn generate_ps1(settings: &Settings, depth: u32, shell_kind: ShellKind) -> String {
     let current_exe_path = env::current_exe().expect("Could not get own binary path");
     let current_exe_path_str = current_exe_path.to_str().expect("Binary path is not unicode");
+    // Windows binaries commonly live in paths with spaces, such as Program Files.
+    let current_exe_path_str = match shell_kind {
+        ShellKind::PowerShell => format!("'{current_exe_path_str}'"),
+        _ => current_exe_path_str.to_string(),
+    };
 
-    let mut parts = vec![];
-    parts.push(
-        Color::new(
-            RED,
-            Command::new(format!("{current_exe_path_str} info ctx"), shell_kind),
-            shell_kind,
+    if shell_kind != ShellKind::Elvish {
+        return Command::new(format!("{current_exe_path_str} info prompt"), shell_kind).to_string();
+    }
+    let context_colors = !settings.prompt.colors.is_empty();
+    render_elvish(
+        &parse_format(format(settings, depth)),
+        &current_exe_path_str,
+        depth,
+        context_colors,
+    )
+}
+
+/// Values of the current shell which are shown in the prompt.
+pub struct Values<'a> {
+    /// Name of the context as shown in the prompt.
+    pub context: String,
+    /// Color of the context in `prompt.colors`.
+    pub context_color: Option<PromptColor>,
+    pub namespace: String,
+    pub depth: u32,
+    pub metadata: BTreeMap<&'a str, &'a str>,
+}
+
+impl<'a> Values<'a> {
+    /// Values of a shell using the kubeconfig.
+    pub fn new(settings: &'a Settings, config: &KubeConfig, depth: u32) -> Values<'a> {
+        let name = config.current_context.as_deref().unwrap_or("");
+        let mut context = settings.prompt.display_name(name);
+        if config.is_impersonating() {
+            context.push_str(" (ro)");
+        }
+        Values {
+            context,
+            context_color: settings.prompt.color_for(name),
+            namespace: config.contexts[0]
+                .context
+                .namespace
+                .clone()
+                .unwrap_or_else(|| "default".into()),
+            depth,
+            metadata: settings.metadata_for(name),
+        }
+    }
+}
+
+/// Escape sequence of a color printed by a command of the prompt, marked as not printed for the shells
+/// which count the length of the prompt.
+fn output_color(code: u32, shell_kind: ShellKind) -> String {
+    let sequence = format!("\x1b[{code}m");
+    match shell_kind {
+        ShellKind::Bash => format!("\x01{sequence}\x02"),
+        ShellKind::Zsh => format!("%{{{sequence}%}}"),
+        _ => sequence,
+    }
+}
+
+/// Render the segments of a prompt format with their values, as printed by `kubie info prompt`.
+fn render_values(segments: &[Segment], values: &Values, shell_kind: ShellKind, context_colors: bool) -> String {
+    // Zsh expands the prompt escapes of the output of the commands.
+    let text = |content: &str| match shell_kind {
+        ShellKind::Zsh => content.replace('%', "%%"),
+        _ => content.to_string(),
+    };
+    let colored = |color: PromptColor, content: String| {
+        if !crate::output::color() {
+            return content;
+        }
+        format!(
+            "{}{}{}",
+            output_color(color.code(), shell_kind),
+            content,
+            output_color(0, shell_kind)
         )
-        .to_string(),
+    };
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(content) => text(content),
+            Segment::Context => match values.context_color.filter(|_| context_colors) {
+                Some(color) => colored(color, text(&values.context)),
+                None => text(&values.context),
+            },
+            Segment::Namespace => text(&values.namespace),
+            Segment::Depth => values.depth.to_string(),
+            Segment::Metadata(key) => text(values.metadata.get(key.as_str()).unwrap_or(&"")),
+            Segment::Color(color, inner) => {
+                let color = match values.context_color {
+                    Some(context_color) if context_colors && inner.contains(&Segment::Context) => context_color,
+                    _ => *color,
+                };
+                colored(color, render_values(inner, values, shell_kind, false))
+            }
+        })
+        .collect()
+}
+
+/// Render the prompt of the current shell from the current settings, for `kubie info prompt`.
+pub fn render_current(settings: &Settings, values: &Values) -> String {
+    if settings.prompt.disable {
+        return String::new();
+    }
+    let shell_kind = env::var("KUBIE_SHELL")
+        .ok()
+        .and_then(|name| ShellKind::from_str(&name))
+        .unwrap_or(ShellKind::Bash);
+    let context_colors = !settings.prompt.colors.is_empty();
+    render_values(
+        &parse_format(format(settings, values.depth)),
+        values,
+        shell_kind,
+        context_colors,
+    )
+}
+
+/// Render the segments of a prompt format with their values as plain text, without colors.
+fn render_text(segments: &[Segment], values: &Values) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(content) => content.clone(),
+            Segment::Context => values.context.clone(),
+            Segment::Namespace => values.namespace.clone(),
+            Segment::Depth => values.depth.to_string(),
+            Segment::Metadata(key) => values.metadata.get(key.as_str()).unwrap_or(&"").to_string(),
+            Segment::Color(_, inner) => render_text(inner, values),
+        })
+        .collect()
+}
+
+/// The prompt of a shell using the kubeconfig as plain text, for the status bars and prompt tools which
+/// read it from KUBIE_PROMPT or from the session file instead of running kubie. `None` when the prompt is
+/// disabled.
+pub fn text(settings: &Settings, config: &KubeConfig, depth: u32) -> Option<String> {
+    if settings.prompt.disable {
+        return None;
+    }
+    let values = Values::new(settings, config, depth);
+    Some(render_text(&parse_format(format(settings, depth)), &values))
+}
+
+#[test]
+fn test_generate_ps1() {
+    let segments = parse_format("⎈ <cyan>{context}</cyan>:{namespace} ({depth}) <bold>");
+    assert_eq!(
+        segments,
+        [
+            Segment::Text("⎈ ".into()),
+            Segment::Color(PromptColor::Cyan, vec![Segment::Context]),
+            Segment::Text(":".into()),
+            Segment::Namespace,
+            Segment::Text(" (".into()),
+            Segment::Depth,
+            Segment::Text(") <bold>".into()),
+        ]
     );
-    parts.push(
-        Color::new(
-            GREEN,
-            Command::new(format!("{current_exe_path_str} info ns"), shell_kind),
-            shell_kind,
-        )
-        .to_string(),
+
+    assert!(generate_ps1(&Settings::default(), 1, ShellKind::Bash).ends_with(" info prompt)"));
+
+    let segments = parse_format("[<red>{context}</red>|<green>{namespace}</green>]");
+    assert_eq!(
+        render_elvish(&segments, "kubie", 1, false),
+        "'['(styled (kubie info ctx --prompt) red)'|'(styled (kubie info ns) green)']'"
+    );
+    assert_eq!(
+        render_elvish(&parse_format("it's {depth} {meta:owner}"), "kubie", 2, false),
+        "'it''s '2' '(kubie info meta owner)"
+    );
+    assert_eq!(
+        render_elvish(&parse_format("<red>{context}</red>"), "kubie", 1, true),
+        "(styled (kubie info ctx --prompt) (kubie info ctx --color --color-name --default-color red))"
     );
-    if settings.prompt.show_depth && depth > 1 {
-        parts.push(Color::new(BLUE, depth, shell_kind).to_string());
-    }
 
-    format!("[{}]", parts.join("|"))
+    let values = Values {
+        context: "prod".into(),
+        context_color: Some(PromptColor::Yellow),
+        namespace: "default".into(),
+        depth: 2,
+        metadata: BTreeMap::from([("owner", "50%")]),
+    };
+    let segments = parse_format("<red>{context}</red>|{namespace} {meta:owner}");
+    assert_eq!(
+        render_values(&segments, &values, ShellKind::Bash, false),
+        "\x01\x1b[31m\x02prod\x01\x1b[0m\x02|default 50%"
+    );
+    assert_eq!(
+        render_values(&segments, &values, ShellKind::Zsh, true),
+        "%{\x1b[33m%}prod%{\x1b[0m%}|default 50%%"
+    );
+    assert_eq!(render_text(&segments, &values), "prod|default 50%");
 }
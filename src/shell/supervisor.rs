@@ -0,0 +1,75 @@
+//! Supervision of a spawned shell. The SIGTERM and SIGHUP sent to kubie are forwarded to the shell instead of
+//! killing kubie, so that kubie outlives the shell and removes the files of its session. The terminal state
+//! is restored once the shell exits, in case the shell was killed before restoring it.
+
+#[cfg(unix)]
+use signal_hook::{consts::signal::*, iterator::backend::Handle, iterator::Signals};
+
+pub struct Supervisor {
+    #[cfg(unix)]
+    terminal: Option<libc::termios>,
+    #[cfg(unix)]
+    signals: Option<Handle>,
+}
+
+#[cfg(unix)]
+impl Supervisor {
+    /// Save the state of the terminal, before the shell changes it.
+    pub fn new() -> Supervisor {
+        let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+        let terminal = unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 1 && libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) == 0 {
+                Some(termios.assume_init())
+            } else {
+                None
+            }
+        };
+        Supervisor {
+            terminal,
+            signals: None,
+        }
+    }
+
+    /// Forward the signals sent to kubie to the shell until the supervisor is dropped. Interactive shells
+    /// ignore SIGTERM, so it is forwarded as SIGHUP, which makes them exit like a closed terminal does.
+    pub fn watch(&mut self, pid: u32) {
+        let mut signals = match Signals::new([SIGHUP, SIGTERM]) {
+            Ok(signals) => signals,
+            Err(err) => {
+                eprintln!("Warning: could not forward signals to the shell: {err}");
+                return;
+            }
+        };
+        self.signals = Some(signals.handle());
+        std::thread::spawn(move || {
+            for _ in signals.forever() {
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGHUP);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        if let Some(signals) = &self.signals {
+            signals.close();
+        }
+        if let Some(termios) = &self.terminal {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl Supervisor {
+    pub fn new() -> Supervisor {
+        Supervisor {}
+    }
+
+    pub fn watch(&mut self, _pid: u32) {}
+}
@@ -0,0 +1,197 @@
+//! Variables of a kubie shell which follow its context and namespace, such as KUBIE_PROMPT and the `env`
+//! settings of the context. kubie cannot change the variables of the shell it runs in, so when the context
+//! or the namespace is switched in place it writes the commands updating them to the file named by
+//! KUBIE_ENV_UPDATE. The shell runs them before showing its next prompt, and empties the file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use super::eval::quote_fish;
+use super::{quote, ShellKind};
+use crate::kubeconfig::KubeConfig;
+use crate::settings::Settings;
+use crate::shell::prompt;
+use crate::vars;
+
+/// Variable naming the file of the commands updating the variables of the shell.
+pub const FILE_VAR: &str = "KUBIE_ENV_UPDATE";
+
+/// Variable holding the values which the variables of the `env` settings had before kubie exported them, as
+/// a JSON object whose null values are the variables which were not set.
+pub const SAVED_VAR: &str = "KUBIE_ENV_SAVED";
+
+/// Values of the variables before kubie exported them, by name.
+type Saved = BTreeMap<String, Option<String>>;
+
+/// Value of SAVED_VAR for a shell spawned in the context, from the values of the variables of its `env`
+/// settings in the environment of kubie.
+pub fn saved(settings: &Settings, context: &str) -> String {
+    let saved: Saved = settings
+        .env_for(context)
+        .into_keys()
+        .map(|name| (name.to_string(), env::var(name).ok()))
+        .collect();
+    serde_json::to_string(&saved).expect("saved values are serializable")
+}
+
+/// Quote a string for the shells whose single quoted strings escape quotes by doubling them.
+fn quote_doubled(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Commands setting and unsetting variables in the language of the shell. Nushell reads them as JSON.
+fn script(kind: ShellKind, set: &[(String, String)], unset: &[String]) -> String {
+    if kind == ShellKind::Nu {
+        let set: serde_json::Map<_, _> = set
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone().into()))
+            .collect();
+        return serde_json::json!({ "set": set, "unset": unset }).to_string();
+    }
+
+    let mut script = String::new();
+    for name in unset {
+        let line = match kind {
+            ShellKind::Fish => format!("set -e {name}"),
+            ShellKind::Xonsh => format!("${{...}}.pop('{name}', None)"),
+            ShellKind::PowerShell => format!("Remove-Item -ErrorAction Ignore Env:{name}"),
+            ShellKind::Elvish => format!("unset-env {name}"),
+            _ => format!("unset {name}"),
+        };
+        script.push_str(&line);
+        script.push('\n');
+    }
+    for (name, value) in set {
+        let line = match kind {
+            ShellKind::Fish => format!("set -gx {name} {}", quote_fish(value)),
+            ShellKind::Xonsh => format!("${name} = {}", serde_json::Value::String(value.clone())),
+            ShellKind::PowerShell => format!("$env:{name} = {}", quote_doubled(value)),
+            ShellKind::Elvish => format!("set-env {name} {}", quote_doubled(value)),
+            _ => format!("export {name}={}", quote(value)),
+        };
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
+
+/// Variables to set and unset when the context of the shell switches to one whose `env` settings are `vars`.
+/// The variables which the new context does not set get back the values they had before kubie exported
+/// them, and the `current` values of the variables exported for the first time are saved.
+fn context_vars(
+    saved: &mut Saved,
+    vars: HashMap<&str, &str>,
+    current: impl Fn(&str) -> Option<String>,
+) -> (Vec<(String, String)>, Vec<String>) {
+    let mut set = vec![];
+    let mut unset = vec![];
+    saved.retain(|name, value| {
+        if vars.contains_key(name.as_str()) {
+            return true;
+        }
+        match value.take() {
+            Some(value) => set.push((name.clone(), value)),
+            None => unset.push(name.clone()),
+        }
+        false
+    });
+    for (name, value) in vars {
+        saved.entry(name.to_string()).or_insert_with(|| current(name));
+        set.push((name.to_string(), value.to_string()));
+    }
+    set.sort();
+    (set, unset)
+}
+
+/// Write the commands updating the variables of the current shell, once its kubeconfig is switched in
+/// place to `config`. Shells spawned by older versions of kubie have no file to write to.
+pub fn write(settings: &Settings, config: &KubeConfig) -> Result<()> {
+    let Some(path) = env::var_os(FILE_VAR).filter(|path| !path.is_empty()) else {
+        return Ok(());
+    };
+    let kind = env::var("KUBIE_SHELL")
+        .ok()
+        .and_then(|name| ShellKind::from_str(&name))
+        .unwrap_or(ShellKind::Bash);
+
+    let mut set = vec![];
+    let mut unset = vec![];
+    match prompt::text(settings, config, vars::get_depth()) {
+        Some(text) => set.push(("KUBIE_PROMPT".to_string(), text)),
+        None => unset.push("KUBIE_PROMPT".to_string()),
+    }
+
+    let mut saved: Saved = env::var(SAVED_VAR)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    let (vars, restored) = context_vars(&mut saved, settings.env_for(&config.contexts[0].name), |name| {
+        env::var(name).ok()
+    });
+    set.extend(vars);
+    unset.extend(restored);
+    set.push((
+        SAVED_VAR.to_string(),
+        serde_json::to_string(&saved).expect("saved values are serializable"),
+    ));
+
+    fs::write(&path, script(kind, &set, &unset)).with_context(|| format!("Could not write {}", path.to_string_lossy()))
+}
+
+#[test]
+fn test_script() {
+    let set = [("KUBIE_PROMPT".to_string(), "[prod|it's]".to_string())];
+    let unset = ["AWS_PROFILE".to_string()];
+    assert_eq!(
+        script(ShellKind::Bash, &set, &unset),
+        "unset AWS_PROFILE\nexport KUBIE_PROMPT='[prod|it'\\''s]'\n"
+    );
+    assert_eq!(
+        script(ShellKind::Fish, &set, &unset),
+        "set -e AWS_PROFILE\nset -gx KUBIE_PROMPT '[prod|it\\'s]'\n"
+    );
+    assert_eq!(
+        script(ShellKind::PowerShell, &set, &unset),
+        "Remove-Item -ErrorAction Ignore Env:AWS_PROFILE\n$env:KUBIE_PROMPT = '[prod|it''s]'\n"
+    );
+    assert_eq!(
+        script(ShellKind::Nu, &set, &unset),
+        r#"{"set":{"KUBIE_PROMPT":"[prod|it's]"},"unset":["AWS_PROFILE"]}"#
+    );
+}
+
+#[test]
+fn test_context_vars() {
+    let prod = HashMap::from([("AWS_PROFILE", "prod"), ("TF_WORKSPACE", "prod")]);
+    let dev = HashMap::from([("AWS_REGION", "eu-west-1")]);
+    let current = |name: &str| (name == "AWS_PROFILE").then(|| "default".to_string());
+    let mut saved = Saved::new();
+
+    let (set, unset) = context_vars(&mut saved, prod.clone(), current);
+    assert_eq!(
+        set,
+        [
+            ("AWS_PROFILE".to_string(), "prod".to_string()),
+            ("TF_WORKSPACE".to_string(), "prod".to_string())
+        ]
+    );
+    assert!(unset.is_empty());
+
+    // The variables of prod get back the values they had before, the profile of the user being kept.
+    let (set, unset) = context_vars(&mut saved, dev, current);
+    assert_eq!(
+        set,
+        [
+            ("AWS_PROFILE".to_string(), "default".to_string()),
+            ("AWS_REGION".to_string(), "eu-west-1".to_string())
+        ]
+    );
+    assert_eq!(unset, ["TF_WORKSPACE"]);
+    assert_eq!(saved, Saved::from([("AWS_REGION".to_string(), None)]));
+
+    let (_, unset) = context_vars(&mut saved, prod, current);
+    assert_eq!(unset, ["AWS_REGION"]);
+}
@@ -0,0 +1,112 @@
+//! Watchdog of a spawned shell, which warns, or exits the shell, once it has been idle at its prompt for
+//! `behavior.idle_timeout` in one of `behavior.idle_contexts`, so that a forgotten shell in a sensitive
+//! context is not reused hours later for the wrong command. The time of the last input is the access time
+//! of the terminal, as shown by `w`, and the shell is at its prompt when it is in the foreground of the
+//! terminal, which a running command is not.
+
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::fd::{AsFd, AsRawFd};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+#[cfg(unix)]
+use std::sync::mpsc::{self, RecvTimeoutError};
+#[cfg(unix)]
+use std::time::{Duration, SystemTime};
+
+#[cfg(unix)]
+use crate::ioutil;
+#[cfg(unix)]
+use crate::kubeconfig::KubeConfig;
+use crate::settings::Settings;
+#[cfg(unix)]
+use crate::settings::{format_duration, Behavior, IdleAction};
+
+/// Stops the watchdog when dropped, once the shell has exited.
+pub struct Watchdog {
+    _stop: Sender<()>,
+}
+
+/// Name of the current context of the shell, which can be switched inside of it.
+#[cfg(unix)]
+fn current_context(kubeconfig: &Path) -> Option<String> {
+    let config: KubeConfig = ioutil::read_yaml(kubeconfig).ok()?;
+    Some(config.contexts.first()?.name.clone())
+}
+
+/// Time of the last input of the terminal, if the shell is waiting for one at its prompt.
+#[cfg(unix)]
+fn last_input_at_prompt(terminal: &File, pid: u32) -> Option<SystemTime> {
+    if unsafe { libc::tcgetpgrp(terminal.as_raw_fd()) } != pid as libc::pid_t {
+        return None;
+    }
+    terminal.metadata().and_then(|meta| meta.accessed()).ok()
+}
+
+#[cfg(unix)]
+impl Watchdog {
+    /// Watch the shell with the given pid, whose kubeconfig gives its current context, when idle contexts
+    /// are configured and stdin is a terminal.
+    pub fn start(settings: &Settings, pid: u32, kubeconfig: PathBuf) -> Option<Watchdog> {
+        let timeout = settings.behavior.idle_timeout?;
+        if settings.behavior.idle_contexts.is_empty() || unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+            return None;
+        }
+        let terminal = File::from(std::io::stdin().as_fd().try_clone_to_owned().ok()?);
+        let behavior = Behavior {
+            idle_contexts: settings.behavior.idle_contexts.clone(),
+            idle_timeout: Some(timeout),
+            idle_action: settings.behavior.idle_action,
+            ..Behavior::default()
+        };
+        let interval = (timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(15));
+
+        let (stop, stopped) = mpsc::channel();
+        std::thread::spawn(move || {
+            // Last input which was warned about, to warn once per idle period.
+            let mut warned = None;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let Some(context) = current_context(&kubeconfig) else {
+                    continue;
+                };
+                let (Some(timeout), Some(last_input)) = (
+                    behavior.idle_timeout_for(&context),
+                    last_input_at_prompt(&terminal, pid),
+                ) else {
+                    continue;
+                };
+                if last_input.elapsed().unwrap_or_default() < timeout || warned == Some(last_input) {
+                    continue;
+                }
+                let idle = format_duration(timeout);
+                match behavior.idle_action {
+                    IdleAction::Warn => {
+                        eprintln!(
+                            "\nWarning: this shell in context {context} has been idle for more than {idle}, check \
+                             the context before running a command."
+                        );
+                        warned = Some(last_input);
+                    }
+                    IdleAction::Exit => {
+                        eprintln!("\nExiting this shell in context {context}, idle for more than {idle}.");
+                        unsafe {
+                            libc::kill(pid as libc::pid_t, libc::SIGHUP);
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+        Some(Watchdog { _stop: stop })
+    }
+}
+
+#[cfg(not(unix))]
+impl Watchdog {
+    pub fn start(_settings: &Settings, _pid: u32, _kubeconfig: PathBuf) -> Option<Watchdog> {
+        None
+    }
+}
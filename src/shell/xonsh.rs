@@ -4,12 +4,10 @@ use std::process::Command;
 use anyhow::Result;
 
 use super::ShellSpawnInfo;
+use crate::ephemeral;
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
-    let temp_rc_file = tempfile::Builder::new()
-        .prefix("kubie-xonshrc")
-        .suffix(".xsh")
-        .tempfile()?;
+    let temp_rc_file = ephemeral::tempfile("xonshrc", ".xsh")?;
     let mut temp_rc_file_buf = BufWriter::new(temp_rc_file.as_file());
 
     write!(
@@ -33,34 +31,53 @@ if Path("~/.config/xonsh/rc.d").is_dir():
 @events.on_precommand
 def __kubie_cmd_pre_exec__(cmd):
     $KUBECONFIG = $KUBIE_KUBECONFIG
+
+# Update the variables which follow the context and the namespace after they are switched in place.
+@events.on_pre_prompt
+def __kubie_env_update__():
+    update = Path($KUBIE_ENV_UPDATE)
+    if update.is_file() and update.stat().st_size > 0:
+        execx(update.read_text())
+        update.write_text('')
 "#
     )?;
 
-    if !info.settings.prompt.disable {
+    if info.settings.prompt.is_injected() {
         write!(
             temp_rc_file_buf,
             r#"
-$KUBIE_PROMPT='{}'
+$KUBIE_PS1='{prompt}'
 import re
 
 # Fanciful prompt-command replacement as xonsh forces the use of PROMPT_FIELDS
-for match in re.finditer(r'\$\(([^)]*)\)', $KUBIE_PROMPT):
+for match in re.finditer(r'\$\(([^)]*)\)', $KUBIE_PS1):
     command = match.group(1)
-    name = command.split().pop()
+    name = 'kubie_' + '_'.join(command.split()[2:]).replace('-', '')
     $PROMPT_FIELDS[name] = evalx(f'lambda: $({{command}}).strip()')
-    $KUBIE_PROMPT = $KUBIE_PROMPT.replace(f'$({{command}})', '{{' + name + '}}')
+    $KUBIE_PS1 = $KUBIE_PS1.replace(f'$({{command}})', '{{' + name + '}}')
 
 if $KUBIE_XONSH_USE_RIGHT_PROMPT == "1":
-    $RIGHT_PROMPT = $KUBIE_PROMPT + $RIGHT_PROMPT
+    $RIGHT_PROMPT = $KUBIE_PS1 + {right_prompt}
 else:
-    $PROMPT = $KUBIE_PROMPT + $PROMPT
+    $PROMPT = $KUBIE_PS1 + {left_prompt}
 
-del $KUBIE_PROMPT
+del $KUBIE_PS1
 "#,
-            info.prompt,
+            prompt = info.prompt,
+            right_prompt = if info.settings.prompt.is_replacing() {
+                "''"
+            } else {
+                "$RIGHT_PROMPT"
+            },
+            left_prompt = if info.settings.prompt.is_replacing() {
+                "' '"
+            } else {
+                "$PROMPT"
+            },
         )?;
     }
 
+    writeln!(temp_rc_file_buf, "{}", info.rc)?;
     temp_rc_file_buf.flush()?;
 
     let mut cmd = Command::new("xonsh");
@@ -68,8 +85,7 @@ del $KUBIE_PROMPT
     cmd.arg(temp_rc_file.path());
     info.env_vars.apply(&mut cmd);
 
-    let mut child = cmd.spawn()?;
-    child.wait()?;
+    super::run_shell(&mut cmd, info)?;
 
     Ok(())
 }
@@ -3,12 +3,12 @@ use std::io::{BufWriter, Write};
 use std::process::Command;
 
 use anyhow::{Context, Result};
-use tempfile::tempdir;
 
 use super::ShellSpawnInfo;
+use crate::ephemeral;
 
 pub fn spawn_shell(info: &ShellSpawnInfo) -> Result<()> {
-    let dir = tempdir()?;
+    let dir = ephemeral::tempdir("zsh")?;
     {
         let zshrc_path = dir.path().join(".zshrc");
         let zshrc = File::create(zshrc_path).context("Could not open zshrc file")?;
@@ -89,10 +89,34 @@ function __kubie_cmd_pre_exec__() {{
 }}
 
 add-zsh-hook preexec __kubie_cmd_pre_exec__
+
+# Update the variables which follow the context and the namespace after they are switched in place,
+# before the precmd hooks of prompt frameworks read them.
+function __kubie_env_update__() {{
+    if [[ -s "$KUBIE_ENV_UPDATE" ]] ; then
+        source "$KUBIE_ENV_UPDATE"
+        : > "$KUBIE_ENV_UPDATE"
+    fi
+}}
+
+precmd_functions=(__kubie_env_update__ $precmd_functions)
 "#,
         )?;
 
-        if !info.settings.prompt.disable {
+        if info.settings.prompt.is_injected() {
+            // With prompt.integration set to replace, kubie's prompt is the only prompt.
+            let replace = if info.settings.prompt.is_replacing() {
+                r#"
+    if [[ "$KUBIE_ZSH_USE_RPS1" == "1" ]] ; then
+        RPS1="$KUBIE_PS1"
+    else
+        PS1="$KUBIE_PS1 "
+    fi
+    return
+"#
+            } else {
+                ""
+            };
             write!(
                 zshrc_buf,
                 r#"
@@ -101,25 +125,25 @@ setopt PROMPT_SUBST
 
 # This function fixes the prompt via a precmd hook.
 function __kubie_cmd_pre_cmd__() {{
-    local KUBIE_PROMPT=$'{}'
-
+    local KUBIE_PS1=$'{prompt}'
+{replace}
     # If KUBIE_ZSH_USE_RPS1 is set, we use RPS1 instead of PS1.
     if [[ "$KUBIE_ZSH_USE_RPS1" == "1" ]] ; then
 
         # Avoid modifying RPS1 again if the RPS1 has not been reset.
-        if [[ "$RPS1" != *"$KUBIE_PROMPT"* ]] ; then
+        if [[ "$RPS1" != *"$KUBIE_PS1"* ]] ; then
 
             # If RPS1 is empty, we do not seperate with a space.
             if [[ -z "$RPS1" ]] ; then
-                RPS1="$KUBIE_PROMPT"
+                RPS1="$KUBIE_PS1"
             else
-                RPS1="$KUBIE_PROMPT $RPS1"
+                RPS1="$KUBIE_PS1 $RPS1"
             fi
         fi
     else
         # Avoid modifying PS1 again if the PS1 has not been reset.
-        if [[ "$PS1" != *"$KUBIE_PROMPT"* ]] ; then
-            PS1="$KUBIE_PROMPT $PS1"
+        if [[ "$PS1" != *"$KUBIE_PS1"* ]] ; then
+            PS1="$KUBIE_PS1 $PS1"
         fi
     fi
 }}
@@ -129,35 +153,32 @@ function __kubie_cmd_pre_cmd__() {{
 # also add our own precmd hook which modifies PS1 after promptinit themes.
 add-zsh-hook precmd __kubie_cmd_pre_cmd__
 "#,
-                info.prompt
+                prompt = info.prompt,
             )?;
         }
 
-        if !info.settings.hooks.start_ctx.is_empty() {
-            write!(zshrc_buf, "{}", info.settings.hooks.start_ctx)?;
-        }
+        write!(zshrc_buf, "{}", info.start_ctx_script())?;
+        writeln!(zshrc_buf, "{}", info.rc)?;
     }
 
     let mut cmd = Command::new("zsh");
     cmd.env("ZDOTDIR", dir.path());
     info.env_vars.apply(&mut cmd);
 
-    let mut child = cmd.spawn()?;
-    child.wait()?;
+    super::run_shell(&mut cmd, info)?;
 
-    if !info.settings.hooks.stop_ctx.is_empty() {
-        let temp_exit_hook_file = tempfile::Builder::new()
-            .prefix("kubie-zsh-exit-hook")
-            .suffix(".zsh")
-            .tempfile()?;
+    if !info.stop_ctx.is_empty() {
+        let temp_exit_hook_file = ephemeral::tempfile("zsh-exit-hook", ".zsh")?;
         let mut temp_exit_hook_file_buf = BufWriter::new(temp_exit_hook_file.as_file());
 
-        write!(temp_exit_hook_file_buf, "{}", info.settings.hooks.stop_ctx)?;
+        let hook_env = info.stop_ctx_env();
+        write!(temp_exit_hook_file_buf, "{}", hook_env.expand(&info.stop_ctx))?;
 
         temp_exit_hook_file_buf.flush()?;
         let mut exit_cmd = Command::new("zsh");
         exit_cmd.arg(temp_exit_hook_file.path());
         info.env_vars.apply(&mut exit_cmd);
+        exit_cmd.envs(hook_env.vars());
 
         let mut child = exit_cmd.spawn()?;
         child.wait()?;
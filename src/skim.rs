@@ -1,50 +1,166 @@
+use std::borrow::Cow;
 use std::io::Cursor;
 
-use anyhow::Result;
-use skim::prelude::{SkimItemReader, SkimOptionsBuilder};
-use skim::Skim;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use skim::prelude::{SkimItemReader, SkimOptions};
+use skim::{ItemPreview, PreviewContext, Skim, SkimItem};
 
+use crate::output;
 use crate::settings::Fzf;
+use crate::static_menu;
 
-fn build_options(fzf: &Fzf) -> Result<skim::SkimOptions> {
-    let mut options = SkimOptionsBuilder::default();
-
-    options
-        .no_multi(true)
-        .no_mouse(!fzf.mouse)
-        .reverse(fzf.reverse);
-
-    if let Some(color) = &fzf.color {
-        options.color(color.clone());
+/// Arguments of skim for the settings, followed by `fzf.extra_args` which override them.
+fn args(fzf: &Fzf) -> Vec<String> {
+    let mut args = vec!["sk".to_string(), "--no-multi".to_string()];
+    if !fzf.mouse {
+        args.push("--no-mouse".into());
+    }
+    if fzf.reverse {
+        args.push("--reverse".into());
+    }
+    if !output::color() {
+        args.push("--color=bw".into());
+    } else if let Some(color) = &fzf.color {
+        args.push(format!("--color={color}"));
     }
-
     if fzf.ignore_case {
-        options.case(skim::CaseMatching::Ignore);
+        args.push("--case=ignore".into());
     }
-
     if fzf.info_hidden {
-        options.no_info(true);
+        args.push("--no-info".into());
     }
-
     if let Some(height) = &fzf.height {
-        options.height(height.clone());
+        args.push(format!("--height={height}"));
     }
-
     if let Some(prompt) = &fzf.prompt {
-        options.prompt(prompt.clone());
+        args.push(format!("--prompt={prompt}"));
     }
+    args.extend(fzf.extra_args.iter().cloned());
+    args
+}
+
+fn build_options(fzf: &Fzf) -> Result<SkimOptions> {
+    let options = SkimOptions::try_parse_from(args(fzf)).map_err(|err| {
+        // The first line of clap's message is the error, the rest is the usage of skim.
+        let message = err.to_string();
+        let message = message.lines().next().unwrap_or_default().trim_start_matches("error: ");
+        anyhow!("Invalid fzf.extra_args: {message}")
+    })?;
+    Ok(options.build())
+}
 
-    options
-        .build()
-        .map_err(|e| anyhow::anyhow!("failed to build skim options: {}", e))
+/// Text and value of the items, for the numbered menu.
+fn entries(items: &[MenuItem]) -> Vec<(String, String)> {
+    items
+        .iter()
+        .map(|item| (item.text.clone(), item.value.clone()))
+        .collect()
+}
+
+/// Choose from the numbered menu, when the terminal cannot show skim. Like with skim, the items are given in
+/// the reverse order of the menu.
+fn select_static(entries: &[(String, String)], multi: bool) -> Result<Vec<String>> {
+    let texts: Vec<&str> = entries.iter().rev().map(|(text, _)| text.as_str()).collect();
+    let chosen = static_menu::choose(&texts, multi)?;
+    Ok(chosen
+        .into_iter()
+        .map(|index| entries[entries.len() - 1 - index].1.clone())
+        .collect())
+}
+
+fn warn_fallback(err: impl std::fmt::Display) {
+    eprintln!("Warning: could not show the menu, using a numbered menu instead: {err}");
 }
 
 /// Run skim with the given items and return the selected item, if any
 pub fn select(fzf: &Fzf, items: Vec<String>) -> Result<Option<String>> {
+    let entries: Vec<_> = items.iter().map(|item| (item.clone(), item.clone())).collect();
+    if static_menu::is_needed(fzf) {
+        return Ok(select_static(&entries, false)?.pop());
+    }
     let options = build_options(fzf)?;
     let reader = SkimItemReader::default();
     let rx = reader.of_bufread(Cursor::new(items.join("\n")));
-    let output = Skim::run_with(options, Some(rx)).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let output = match Skim::run_with(options, Some(rx)) {
+        Ok(output) => output,
+        Err(err) => {
+            warn_fallback(err);
+            return Ok(select_static(&entries, false)?.pop());
+        }
+    };
+
+    if output.is_abort || output.selected_items.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(output.selected_items[0].output().to_string()))
+    }
+}
+
+/// Run skim with the given items and return the values of the items selected with tab, or of the highlighted
+/// one if none is selected
+pub fn select_multi(fzf: &Fzf, items: Vec<MenuItem>) -> Result<Vec<String>> {
+    let entries = entries(&items);
+    if static_menu::is_needed(fzf) {
+        return select_static(&entries, true);
+    }
+    let mut options = build_options(fzf)?;
+    options.no_multi = false;
+    options.multi = true;
+    let output = match Skim::run_items(options, items) {
+        Ok(output) => output,
+        Err(err) => {
+            warn_fallback(err);
+            return select_static(&entries, true);
+        }
+    };
+
+    if output.is_abort {
+        Ok(vec![])
+    } else {
+        Ok(output.selected_items.iter().map(|i| i.output().to_string()).collect())
+    }
+}
+
+/// An item of a menu whose text differs from the value it stands for, optionally with a preview.
+pub struct MenuItem {
+    pub value: String,
+    pub text: String,
+    pub preview: Option<String>,
+}
+
+impl SkimItem for MenuItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.text)
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.value)
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::Text(self.preview.clone().unwrap_or_default())
+    }
+}
+
+/// Run skim with the given items and return the value of the selected item, if any
+pub fn select_items(fzf: &Fzf, items: Vec<MenuItem>) -> Result<Option<String>> {
+    let entries = entries(&items);
+    if static_menu::is_needed(fzf) {
+        return Ok(select_static(&entries, false)?.pop());
+    }
+    let mut options = build_options(fzf)?;
+    if items.iter().any(|item| item.preview.is_some()) {
+        // The preview comes from the items, an empty command only enables the preview window.
+        options.preview = Some(String::new());
+    }
+    let output = match Skim::run_items(options, items) {
+        Ok(output) => output,
+        Err(err) => {
+            warn_fallback(err);
+            return Ok(select_static(&entries, false)?.pop());
+        }
+    };
 
     if output.is_abort || output.selected_items.is_empty() {
         Ok(None)
@@ -52,3 +168,22 @@ pub fn select(fzf: &Fzf, items: Vec<String>) -> Result<Option<String>> {
         Ok(Some(output.selected_items[0].output().to_string()))
     }
 }
+
+#[test]
+fn test_build_options() {
+    let fzf = Fzf {
+        height: Some("40%".into()),
+        extra_args: vec!["--height=60%".into(), "--bind=ctrl-j:down".into()],
+        ..Fzf::default()
+    };
+    let options = build_options(&fzf).unwrap();
+    assert_eq!(options.height, "60%");
+    assert!(options.no_multi);
+    assert_eq!(options.bind, ["ctrl-j:down"]);
+
+    let fzf = Fzf {
+        extra_args: vec!["--no-such-option".into()],
+        ..Fzf::default()
+    };
+    assert!(build_options(&fzf).is_err());
+}
@@ -0,0 +1,75 @@
+//! Numbered menu for the terminals which cannot show the menu of skim, such as the dumb terminals of some
+//! IDEs. The items are printed to stderr with their number, and the numbers of the chosen ones are read
+//! from stdin.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::Result;
+
+use crate::settings::Fzf;
+
+/// Whether to use the numbered menu instead of skim: when configured, or when the terminal cannot move the
+/// cursor.
+pub fn is_needed(fzf: &Fzf) -> bool {
+    if fzf.static_menu {
+        return true;
+    }
+    #[cfg(unix)]
+    return std::env::var("TERM").map_or(true, |term| term.is_empty() || term == "dumb");
+    #[cfg(not(unix))]
+    false
+}
+
+/// Parse the numbers of the chosen items, separated by spaces or commas, into their indexes. An empty input
+/// chooses nothing.
+fn parse_choice(input: &str, count: usize, multi: bool) -> Option<Vec<usize>> {
+    let indexes = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|number| !number.is_empty())
+        .map(|number| {
+            let number: usize = number.parse().ok()?;
+            (1..=count).contains(&number).then(|| number - 1)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if indexes.len() > 1 && !multi {
+        return None;
+    }
+    Some(indexes)
+}
+
+/// Print the items with their number, and return the indexes of the chosen ones. Several items can be
+/// chosen with `multi`.
+pub fn choose(texts: &[&str], multi: bool) -> Result<Vec<usize>> {
+    let mut stderr = io::stderr();
+    for (index, text) in texts.iter().enumerate() {
+        writeln!(stderr, "{}) {}", index + 1, text)?;
+    }
+    loop {
+        if multi {
+            write!(stderr, "choose (numbers separated by spaces): ")?;
+        } else {
+            write!(stderr, "choose: ")?;
+        }
+        stderr.flush()?;
+
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(vec![]);
+        }
+        match parse_choice(&line, texts.len(), multi) {
+            Some(indexes) => return Ok(indexes),
+            None => writeln!(stderr, "Invalid choice: {}", line.trim())?,
+        }
+    }
+}
+
+#[test]
+fn test_parse_choice() {
+    assert_eq!(parse_choice("2\n", 3, false), Some(vec![1]));
+    assert_eq!(parse_choice("\n", 3, false), Some(vec![]));
+    assert_eq!(parse_choice("4\n", 3, false), None);
+    assert_eq!(parse_choice("0", 3, false), None);
+    assert_eq!(parse_choice("prod", 3, false), None);
+    assert_eq!(parse_choice("1 3", 3, false), None);
+    assert_eq!(parse_choice("1 3,2", 3, true), Some(vec![0, 2, 1]));
+}
@@ -0,0 +1,119 @@
+use std::env;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::settings::Settings;
+
+/// Name of a tmux window before kubie renamed it, to restore it when the shell exits.
+pub struct Window {
+    name: String,
+    automatic_rename: bool,
+}
+
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux").args(args).output().context("could not run tmux")?;
+    if !output.status.success() {
+        bail!(
+            "tmux {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches('\n')
+        .to_string())
+}
+
+/// The pane kubie runs in, when the tmux integration is enabled and kubie runs inside of tmux.
+fn pane(settings: &Settings) -> Option<String> {
+    if !settings.integrations.tmux || env::var_os("TMUX").is_none() {
+        return None;
+    }
+    Some(env::var("TMUX_PANE").unwrap_or_default())
+}
+
+fn target(pane: &str) -> Vec<&str> {
+    if pane.is_empty() {
+        vec![]
+    } else {
+        vec!["-t", pane]
+    }
+}
+
+fn window_title(settings: &Settings, context: &str, namespace: Option<&str>) -> String {
+    format!(
+        "{}/{}",
+        settings.prompt.display_name(context),
+        namespace.unwrap_or("default")
+    )
+}
+
+fn try_rename_window(pane: &str, title: &str) -> Result<Window> {
+    let mut args = vec!["display-message", "-p"];
+    args.extend(target(pane));
+    args.push("#{automatic-rename} #W");
+    let current = tmux(&args)?;
+    let (automatic_rename, name) = current.split_once(' ').unwrap_or(("0", &current));
+
+    let mut args = vec!["rename-window"];
+    args.extend(target(pane));
+    args.push(title);
+    tmux(&args)?;
+
+    Ok(Window {
+        name: name.to_string(),
+        automatic_rename: automatic_rename == "1" || automatic_rename == "on",
+    })
+}
+
+/// Rename the tmux window to the context and namespace, if the tmux integration is enabled.
+/// Returns the previous name of the window, for `restore_window`.
+pub fn rename_window(settings: &Settings, context: &str, namespace: Option<&str>) -> Option<Window> {
+    let pane = pane(settings)?;
+    match try_rename_window(&pane, &window_title(settings, context, namespace)) {
+        Ok(window) => Some(window),
+        Err(err) => {
+            eprintln!("Warning: could not rename the tmux window: {err}");
+            None
+        }
+    }
+}
+
+/// Give the tmux window back the name it had before `rename_window`.
+pub fn restore_window(settings: &Settings, window: Window) {
+    let Some(pane) = pane(settings) else {
+        return;
+    };
+    let mut args = if window.automatic_rename {
+        vec!["set-window-option"]
+    } else {
+        vec!["rename-window"]
+    };
+    args.extend(target(&pane));
+    if window.automatic_rename {
+        args.extend(["automatic-rename", "on"]);
+    } else {
+        args.push(&window.name);
+    }
+    if let Err(err) = tmux(&args) {
+        eprintln!("Warning: could not restore the tmux window name: {err}");
+    }
+}
+
+#[test]
+fn test_window_title() {
+    let settings: Settings = serde_yaml::from_str(
+        "prompt:\n  context_rewrite:\n    - pattern: '^arn:aws:eks:[^:]+:[0-9]+:cluster/'\n      replacement: ''\n",
+    )
+    .unwrap();
+    assert_eq!(
+        window_title(
+            &settings,
+            "arn:aws:eks:us-east-1:123456789012:cluster/prod",
+            Some("web")
+        ),
+        "prod/web"
+    );
+    assert_eq!(window_title(&settings, "dev", None), "dev/default");
+}
@@ -0,0 +1,160 @@
+use std::process::Command;
+use std::str;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use serde_yaml::Mapping;
+use wildmatch::WildMatch;
+
+use crate::kubeconfig::KubeConfig;
+use crate::settings::{Vault, VaultContext};
+
+/// Read the secret at the given path with the vault CLI and return its fields.
+fn read_secret(vault: &Vault, path: &str) -> Result<serde_json::Map<String, Value>> {
+    let mut cmd = Command::new("vault");
+    cmd.args(["kv", "get", "-format=json", path]);
+    if let Some(address) = &vault.address {
+        cmd.env("VAULT_ADDR", address);
+    }
+
+    let result = cmd.output().context("Could not spawn vault")?;
+    if !result.status.success() {
+        let stderr = str::from_utf8(&result.stderr).unwrap_or("could not decode stderr of vault as utf-8");
+        return Err(anyhow!("Error calling vault:\n{}", stderr));
+    }
+
+    let mut secret: Value = serde_json::from_slice(&result.stdout).context("Could not parse output of vault")?;
+    let data = secret["data"].take();
+    // KV version 2 nests the fields under data.data, next to the secret's metadata.
+    let fields = if data.get("metadata").is_some() {
+        data.get("data").cloned().unwrap_or_default()
+    } else {
+        data
+    };
+
+    match fields {
+        Value::Object(fields) => Ok(fields),
+        _ => bail!("Secret {} has no data", path),
+    }
+}
+
+fn get_field<'a>(fields: &'a serde_json::Map<String, Value>, name: &str, path: &str) -> Result<&'a str> {
+    fields
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Secret {} has no field {}", path, name))
+}
+
+fn apply(rule: &VaultContext, fields: &serde_json::Map<String, Value>, kubeconfig: &mut KubeConfig) -> Result<()> {
+    if let Some(field) = &rule.kubeconfig_field {
+        let fetched: KubeConfig = serde_yaml::from_str(get_field(fields, field, &rule.path)?)
+            .with_context(|| format!("Field {} of secret {} is not a kubeconfig", field, rule.path))?;
+        let context = match &fetched.current_context {
+            Some(name) => fetched.contexts.iter().find(|c| &c.name == name),
+            None => fetched.contexts.first(),
+        }
+        .ok_or_else(|| anyhow!("Kubeconfig in secret {} has no context", rule.path))?;
+
+        let cluster = fetched
+            .clusters
+            .iter()
+            .find(|c| c.name == context.context.cluster)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Kubeconfig in secret {} has no cluster {}",
+                    rule.path,
+                    context.context.cluster
+                )
+            })?;
+        let user = fetched
+            .users
+            .iter()
+            .find(|u| u.name == context.context.user)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Kubeconfig in secret {} has no user {}",
+                    rule.path,
+                    context.context.user
+                )
+            })?;
+
+        kubeconfig.clusters[0].cluster = cluster.cluster.clone();
+        kubeconfig.users[0].user = user.user.clone();
+    }
+
+    if let Some(field) = &rule.token_field {
+        let mut user = Mapping::new();
+        user.insert("token".into(), get_field(fields, field, &rule.path)?.into());
+        kubeconfig.users[0].user = user;
+    }
+
+    if let Some(field) = &rule.ca_field {
+        let cluster = &mut kubeconfig.clusters[0].cluster;
+        cluster.remove("certificate-authority");
+        cluster.insert(
+            "certificate-authority-data".into(),
+            get_field(fields, field, &rule.path)?.into(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Replace the credentials of the kubeconfig's context with the ones stored in vault, if a
+/// rule of the vault settings matches the context.
+pub fn inject_credentials(vault: &Vault, kubeconfig: &mut KubeConfig) -> Result<()> {
+    let context_name = kubeconfig.contexts[0].name.clone();
+    let Some(rule) = vault
+        .contexts
+        .iter()
+        .find(|rule| WildMatch::new(&rule.context).matches(&context_name))
+    else {
+        return Ok(());
+    };
+
+    let fields = read_secret(vault, &rule.path)?;
+    apply(rule, &fields, kubeconfig).with_context(|| format!("Could not use credentials from vault for {context_name}"))
+}
+
+#[test]
+fn test_apply_token_and_ca() {
+    let mut kubeconfig: KubeConfig = serde_yaml::from_str(
+        r#"
+clusters:
+  - name: prod
+    cluster:
+      server: https://prod.example.com
+      certificate-authority: /etc/prod-ca.crt
+users:
+  - name: admin
+    user:
+      client-certificate: /etc/admin.crt
+contexts:
+  - name: prod
+    context:
+      cluster: prod
+      user: admin
+"#,
+    )
+    .unwrap();
+    let rule = VaultContext {
+        context: "prod".into(),
+        path: "secret/kube/prod".into(),
+        kubeconfig_field: None,
+        token_field: Some("token".into()),
+        ca_field: Some("ca".into()),
+    };
+    let fields = serde_json::from_str(r#"{"token": "s3cr3t", "ca": "Q0EK"}"#).unwrap();
+
+    apply(&rule, &fields, &mut kubeconfig).unwrap();
+
+    let user = &kubeconfig.users[0].user;
+    assert_eq!(user.len(), 1);
+    assert_eq!(user.get("token").and_then(|v| v.as_str()), Some("s3cr3t"));
+    let cluster = &kubeconfig.clusters[0].cluster;
+    assert!(cluster.get("certificate-authority").is_none());
+    assert_eq!(
+        cluster.get("certificate-authority-data").and_then(|v| v.as_str()),
+        Some("Q0EK")
+    );
+}